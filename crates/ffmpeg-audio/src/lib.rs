@@ -1,8 +1,9 @@
 use ffmpeg_next::{self as ffmpeg};
-use hound::{WavReader, WavWriter};
+use hound::{WavReader, WavSpec, WavWriter};
 use std::error::Error;
 use std::fs;
-use std::path::Path;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
 
 pub struct AudioSplitter {
     chunk_duration: i64,
@@ -12,6 +13,209 @@ pub struct WavSplitter {
     chunk_duration: u32,
 }
 
+#[derive(Debug, Clone)]
+pub struct MediaInfo {
+    // seconds
+    pub duration: f64,
+    pub format_name: String,
+    pub bit_rate: i64,
+    pub audio_codec: Option<String>,
+    pub video_codec: Option<String>,
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+// read the container and codec details of a local video or audio file
+pub fn probe(input_file: &Path) -> Result<MediaInfo, ffmpeg::Error> {
+    ffmpeg::init()?;
+    let input_ctx = ffmpeg::format::input(input_file)?;
+
+    let video_codec = input_ctx
+        .streams()
+        .best(ffmpeg::media::Type::Video)
+        .map(|stream| stream.parameters().id().name().to_string());
+    let (audio_codec, sample_rate, channels) =
+        match input_ctx.streams().best(ffmpeg::media::Type::Audio) {
+            Some(stream) => {
+                let codec = ffmpeg::codec::context::Context::from_parameters(stream.parameters())?;
+                let codec_name = codec.id().name().to_string();
+                let audio = codec.decoder().audio()?;
+                (Some(codec_name), audio.rate(), audio.channels())
+            }
+            None => (None, 0, 0),
+        };
+
+    Ok(MediaInfo {
+        duration: input_ctx.duration().max(0) as f64 / f64::from(ffmpeg::ffi::AV_TIME_BASE),
+        format_name: input_ctx.format().name().to_string(),
+        bit_rate: input_ctx.bit_rate(),
+        audio_codec,
+        video_codec,
+        sample_rate,
+        channels,
+    })
+}
+
+// containers the transcription api accepts for a stream copy of each codec
+fn audio_extension(codec_id: ffmpeg::codec::Id) -> Option<&'static str> {
+    match codec_id {
+        ffmpeg::codec::Id::AAC | ffmpeg::codec::Id::ALAC => Some("m4a"),
+        ffmpeg::codec::Id::MP3 => Some("mp3"),
+        ffmpeg::codec::Id::OPUS | ffmpeg::codec::Id::VORBIS => Some("ogg"),
+        ffmpeg::codec::Id::FLAC => Some("flac"),
+        ffmpeg::codec::Id::PCM_S16LE => Some("wav"),
+        _ => None,
+    }
+}
+
+// extract the best audio stream of a local file into `output_dir`, returns the written file;
+// copied as is when the codec fits an accepted container, otherwise decoded to a 16khz mono wav
+pub fn extract_audio(input_file: &Path, output_dir: &Path) -> Result<PathBuf, ffmpeg::Error> {
+    extract_audio_until(input_file, output_dir, || false)
+}
+
+// like `extract_audio`, but gives up with `ffmpeg::Error::Exit` once `stop` is true,
+// leaving a partly written file
+pub fn extract_audio_until(
+    input_file: &Path,
+    output_dir: &Path,
+    stop: impl Fn() -> bool,
+) -> Result<PathBuf, ffmpeg::Error> {
+    ffmpeg::init()?;
+    if !output_dir.is_dir() {
+        std::fs::create_dir_all(output_dir).map_err(|_| ffmpeg::Error::Other { errno: -1 })?;
+    }
+
+    let input_ctx = ffmpeg::format::input(input_file)?;
+    let codec_id = input_ctx
+        .streams()
+        .best(ffmpeg::media::Type::Audio)
+        .ok_or(ffmpeg::Error::StreamNotFound)?
+        .parameters()
+        .id();
+    drop(input_ctx);
+
+    match audio_extension(codec_id) {
+        Some(extension) => {
+            let output_file = output_dir.join(format!("audio.{extension}"));
+            copy_audio_stream(input_file, &output_file, &stop)?;
+            Ok(output_file)
+        }
+        None => {
+            let output_file = output_dir.join("audio.wav");
+            decode_audio_to_wav(input_file, &output_file, &stop)?;
+            Ok(output_file)
+        }
+    }
+}
+
+fn copy_audio_stream(
+    input_file: &Path,
+    output_file: &Path,
+    stop: &impl Fn() -> bool,
+) -> Result<(), ffmpeg::Error> {
+    let mut input_ctx = ffmpeg::format::input(input_file)?;
+    let audio_stream = input_ctx
+        .streams()
+        .best(ffmpeg::media::Type::Audio)
+        .ok_or(ffmpeg::Error::StreamNotFound)?;
+    let stream_index = audio_stream.index();
+    let input_time_base = audio_stream.time_base();
+    let audio_parameters = audio_stream.parameters();
+
+    let mut output_ctx = ffmpeg::format::output(output_file)?;
+    let mut output_stream =
+        output_ctx.add_stream(ffmpeg::encoder::find(ffmpeg::codec::Id::None))?;
+    output_stream.set_parameters(audio_parameters);
+    // the codec tag of the source container may be invalid in the new one
+    unsafe {
+        (*output_stream.parameters().as_mut_ptr()).codec_tag = 0;
+    }
+
+    output_ctx.set_metadata(input_ctx.metadata().to_owned());
+    output_ctx.write_header()?;
+    let output_time_base = output_ctx.stream(0).unwrap().time_base();
+
+    for (stream, mut packet) in input_ctx.packets() {
+        if stop() {
+            return Err(ffmpeg::Error::Exit);
+        }
+        if stream.index() != stream_index {
+            continue;
+        }
+        packet.rescale_ts(input_time_base, output_time_base);
+        packet.set_position(-1);
+        packet.set_stream(0);
+        packet.write_interleaved(&mut output_ctx)?;
+    }
+    output_ctx.write_trailer()?;
+    Ok(())
+}
+
+fn decode_audio_to_wav(
+    input_file: &Path,
+    output_file: &Path,
+    stop: &impl Fn() -> bool,
+) -> Result<(), ffmpeg::Error> {
+    let mut input_ctx = ffmpeg::format::input(input_file)?;
+    let audio_stream = input_ctx
+        .streams()
+        .best(ffmpeg::media::Type::Audio)
+        .ok_or(ffmpeg::Error::StreamNotFound)?;
+    let stream_index = audio_stream.index();
+    let mut decoder = ffmpeg::codec::context::Context::from_parameters(audio_stream.parameters())?
+        .decoder()
+        .audio()?;
+    let mut resampler = decoder.resampler(
+        ffmpeg::format::Sample::I16(ffmpeg::format::sample::Type::Packed),
+        ffmpeg::ChannelLayout::MONO,
+        16000,
+    )?;
+
+    let spec = WavSpec {
+        channels: 1,
+        sample_rate: 16000,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer =
+        WavWriter::create(output_file, spec).map_err(|_| ffmpeg::Error::Other { errno: -1 })?;
+
+    for (stream, packet) in input_ctx.packets() {
+        if stop() {
+            return Err(ffmpeg::Error::Exit);
+        }
+        if stream.index() != stream_index {
+            continue;
+        }
+        decoder.send_packet(&packet)?;
+        write_decoded_frames(&mut decoder, &mut resampler, &mut writer)?;
+    }
+    decoder.send_eof()?;
+    write_decoded_frames(&mut decoder, &mut resampler, &mut writer)?;
+    writer
+        .finalize()
+        .map_err(|_| ffmpeg::Error::Other { errno: -1 })
+}
+
+fn write_decoded_frames(
+    decoder: &mut ffmpeg::decoder::Audio,
+    resampler: &mut ffmpeg::software::resampling::Context,
+    writer: &mut WavWriter<BufWriter<fs::File>>,
+) -> Result<(), ffmpeg::Error> {
+    let mut decoded = ffmpeg::frame::Audio::empty();
+    while decoder.receive_frame(&mut decoded).is_ok() {
+        let mut resampled = ffmpeg::frame::Audio::empty();
+        resampler.run(&decoded, &mut resampled)?;
+        for &sample in resampled.plane::<i16>(0) {
+            writer
+                .write_sample(sample)
+                .map_err(|_| ffmpeg::Error::Other { errno: -1 })?;
+        }
+    }
+    Ok(())
+}
+
 impl AudioSplitter {
     pub fn new(duration_seconds: i64) -> Self {
         Self {
@@ -20,6 +224,17 @@ impl AudioSplitter {
     }
 
     pub fn split(&self, input_file: &Path, output_dir: &Path) -> Result<(), ffmpeg::Error> {
+        self.split_until(input_file, output_dir, || false)
+    }
+
+    // like `split`, but gives up with `ffmpeg::Error::Exit` once `stop` is true,
+    // leaving the chunks written so far
+    pub fn split_until(
+        &self,
+        input_file: &Path,
+        output_dir: &Path,
+        stop: impl Fn() -> bool,
+    ) -> Result<(), ffmpeg::Error> {
         ffmpeg::init()?;
         if !output_dir.is_dir() {
             std::fs::create_dir_all(output_dir).map_err(|_| ffmpeg::Error::Other { errno: -1 })?;
@@ -66,6 +281,9 @@ impl AudioSplitter {
             let mut pts_offset = 0;
             let mut first_pts = None;
             for (stream, packet) in input_ctx.packets() {
+                if stop() {
+                    return Err(ffmpeg::Error::Exit);
+                }
                 let pts = packet.pts().unwrap_or(0);
                 let current_time = pts * ffmpeg::ffi::AV_TIME_BASE as i64
                     / stream.time_base().denominator() as i64;
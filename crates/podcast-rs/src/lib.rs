@@ -0,0 +1,365 @@
+use chrono::DateTime;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use reqwest::{header::USER_AGENT, Client, Proxy};
+use serde::{Deserialize, Serialize};
+use std::{error::Error, fs::File, io::Write, path::Path};
+use tube_rs::fnv1a;
+
+pub struct PodcastClient {
+    client: Client,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct PodcastFeed {
+    pub title: String,
+    pub description: Option<String>,
+    pub language: Option<String>,
+    pub image_url: Option<String>,
+    pub episodes: Vec<Episode>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Episode {
+    pub guid: String,
+    pub title: String,
+    pub description: Option<String>,
+    // publish time in microseconds, the same unit youtube reports
+    pub published: u64,
+    pub enclosure_url: String,
+    pub enclosure_size: u64,
+    pub mime_type: String,
+    // seconds, 0 when the feed does not say
+    pub duration: u64,
+    pub image_url: Option<String>,
+    pub keywords: Option<Vec<String>>,
+    pub transcripts: Vec<Transcript>,
+}
+
+// a `podcast:transcript` tag
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Transcript {
+    pub url: String,
+    pub mime_type: String,
+    pub language: Option<String>,
+}
+
+impl Transcript {
+    // srt and webvtt can be turned into timed segments, other formats can not
+    pub fn is_timed(&self) -> bool {
+        matches!(
+            self.mime_type.as_str(),
+            "application/srt" | "application/x-subrip" | "text/srt" | "text/vtt"
+        )
+    }
+}
+
+impl Episode {
+    // stable short id derived from the guid, used to store the episode like a video
+    pub fn episode_id(&self) -> String {
+        format!("{:016x}", fnv1a(&self.guid))
+    }
+
+    pub fn timed_transcript(&self) -> Option<&Transcript> {
+        self.transcripts.iter().find(|t| t.is_timed())
+    }
+}
+
+// "1:02:03", "02:03" or plain seconds
+fn parse_duration(text: &str) -> u64 {
+    text.trim()
+        .split(':')
+        .map(|part| part.trim().parse::<f64>().unwrap_or(0.0) as u64)
+        .fold(0, |total, part| total * 60 + part)
+}
+
+// rss uses rfc 2822 dates and atom uses rfc 3339
+fn parse_date(text: &str) -> u64 {
+    let text = text.trim();
+    DateTime::parse_from_rfc2822(text)
+        .or_else(|_| DateTime::parse_from_rfc3339(text))
+        .map(|date| date.timestamp_micros().max(0) as u64)
+        .unwrap_or(0)
+}
+
+fn attributes(element: &BytesStart) -> Vec<(String, String)> {
+    element
+        .attributes()
+        .flatten()
+        .map(|attribute| {
+            (
+                String::from_utf8_lossy(attribute.key.as_ref()).to_string(),
+                attribute
+                    .unescape_value()
+                    .map(|value| value.to_string())
+                    .unwrap_or_default(),
+            )
+        })
+        .collect()
+}
+
+fn attribute(attributes: &[(String, String)], name: &str) -> Option<String> {
+    attributes
+        .iter()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.clone())
+}
+
+fn set_once(field: &mut Option<String>, text: &str) {
+    if field.is_none() && !text.trim().is_empty() {
+        *field = Some(text.trim().to_string());
+    }
+}
+
+fn handle_attributes(
+    feed: &mut PodcastFeed,
+    episode: Option<&mut Episode>,
+    name: &str,
+    attributes: &[(String, String)],
+) {
+    let Some(episode) = episode else {
+        if name == "itunes:image" {
+            feed.image_url = feed.image_url.take().or(attribute(attributes, "href"));
+        }
+        return;
+    };
+    match name {
+        "enclosure" => {
+            episode.enclosure_url = attribute(attributes, "url").unwrap_or_default();
+            episode.enclosure_size = attribute(attributes, "length")
+                .and_then(|length| length.parse().ok())
+                .unwrap_or(0);
+            episode.mime_type = attribute(attributes, "type").unwrap_or_default();
+        }
+        "link" if attribute(attributes, "rel").as_deref() == Some("enclosure") => {
+            episode.enclosure_url = attribute(attributes, "href").unwrap_or_default();
+            episode.enclosure_size = attribute(attributes, "length")
+                .and_then(|length| length.parse().ok())
+                .unwrap_or(0);
+            episode.mime_type = attribute(attributes, "type").unwrap_or_default();
+        }
+        "itunes:image" => episode.image_url = attribute(attributes, "href"),
+        "podcast:transcript" => {
+            if let Some(url) = attribute(attributes, "url") {
+                episode.transcripts.push(Transcript {
+                    url,
+                    mime_type: attribute(attributes, "type").unwrap_or_default(),
+                    language: attribute(attributes, "language"),
+                });
+            }
+        }
+        _ => {}
+    }
+}
+
+fn handle_text(feed: &mut PodcastFeed, episode: Option<&mut Episode>, name: &str, text: &str) {
+    let Some(episode) = episode else {
+        match name {
+            "title" if feed.title.is_empty() => feed.title = text.trim().to_string(),
+            "description" | "subtitle" | "itunes:summary" => set_once(&mut feed.description, text),
+            "language" => set_once(&mut feed.language, text),
+            "url" => set_once(&mut feed.image_url, text),
+            _ => {}
+        }
+        return;
+    };
+    match name {
+        "title" => episode.title = text.trim().to_string(),
+        "itunes:title" if episode.title.is_empty() => episode.title = text.trim().to_string(),
+        "description" | "summary" | "content:encoded" | "itunes:summary" => {
+            set_once(&mut episode.description, text)
+        }
+        "guid" | "id" => episode.guid = text.trim().to_string(),
+        "pubDate" | "published" => episode.published = parse_date(text),
+        "updated" if episode.published == 0 => episode.published = parse_date(text),
+        "itunes:duration" => episode.duration = parse_duration(text),
+        "itunes:keywords" => {
+            episode.keywords = Some(
+                text.split(',')
+                    .map(|k| k.trim().to_string())
+                    .filter(|k| !k.is_empty())
+                    .collect(),
+            )
+        }
+        _ => {}
+    }
+}
+
+// parse an rss 2.0 or atom feed, episodes without an audio enclosure are skipped
+pub fn parse_feed(xml: &str) -> Result<PodcastFeed, Box<dyn Error>> {
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut feed = PodcastFeed::default();
+    let mut episode: Option<Episode> = None;
+    let mut stack: Vec<String> = Vec::new();
+    let mut text = String::new();
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(element) => {
+                let name = String::from_utf8_lossy(element.name().as_ref()).to_string();
+                if name == "item" || name == "entry" {
+                    episode = Some(Episode::default());
+                }
+                handle_attributes(&mut feed, episode.as_mut(), &name, &attributes(&element));
+                stack.push(name);
+                text.clear();
+            }
+            Event::Empty(element) => {
+                let name = String::from_utf8_lossy(element.name().as_ref()).to_string();
+                handle_attributes(&mut feed, episode.as_mut(), &name, &attributes(&element));
+            }
+            Event::Text(content) => text.push_str(&content.unescape()?),
+            Event::CData(content) => {
+                text.push_str(&String::from_utf8_lossy(&content.into_inner()));
+            }
+            Event::End(_) => {
+                let name = stack.pop().unwrap_or_default();
+                if name == "item" || name == "entry" {
+                    if let Some(mut finished) = episode.take() {
+                        if !finished.enclosure_url.is_empty() {
+                            if finished.guid.is_empty() {
+                                finished.guid = finished.enclosure_url.clone();
+                            }
+                            feed.episodes.push(finished);
+                        }
+                    }
+                } else {
+                    handle_text(&mut feed, episode.as_mut(), &name, &text);
+                }
+                text.clear();
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(feed)
+}
+
+impl PodcastClient {
+    pub fn new(proxy: Option<&str>) -> Self {
+        let client_builder = Client::builder();
+        let client = match proxy {
+            Some(proxy_str) => match Proxy::https(proxy_str) {
+                Ok(ok_proxy) => client_builder.proxy(ok_proxy).build().unwrap(),
+                Err(_) => client_builder.build().unwrap(),
+            },
+            _ => client_builder.build().unwrap(),
+        };
+        Self { client }
+    }
+
+    // fetch a text document such as a feed or a `podcast:transcript` file
+    pub async fn download_text(&self, url: &str) -> Result<String, Box<dyn Error>> {
+        let response = self
+            .client
+            .get(url)
+            .header(USER_AGENT, "Mozilla/5.0")
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(response.text().await?)
+    }
+
+    pub async fn fetch_feed(&self, feed_url: &str) -> Result<PodcastFeed, Box<dyn Error>> {
+        let xml = self.download_text(feed_url).await?;
+        parse_feed(&xml)
+    }
+
+    pub async fn download_enclosure(
+        &self,
+        enclosure_url: &str,
+        file_path: &Path,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut response = self
+            .client
+            .get(enclosure_url)
+            .header(USER_AGENT, "Mozilla/5.0")
+            .send()
+            .await?
+            .error_for_status()?;
+        let mut file = File::create(file_path)?;
+        while let Some(chunk) = response.chunk().await? {
+            file.write_all(&chunk)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_rss_feed_works() {
+        let feed = parse_feed(include_str!("../fixtures/rss_feed.xml")).unwrap();
+        assert_eq!(feed.title, "Deep Work Radio");
+        assert_eq!(
+            feed.description.as_deref(),
+            Some("Conversations about focus & craft.")
+        );
+        assert_eq!(feed.language.as_deref(), Some("en-us"));
+        assert_eq!(
+            feed.image_url.as_deref(),
+            Some("https://example.com/cover.jpg")
+        );
+        // the post without an enclosure is not an episode
+        assert_eq!(feed.episodes.len(), 2);
+
+        let episode = &feed.episodes[0];
+        assert_eq!(episode.guid, "ep-0002");
+        assert_eq!(episode.episode_id().len(), 16);
+        assert_ne!(episode.episode_id(), feed.episodes[1].episode_id());
+        assert_eq!(episode.title, "Episode 2: Slow Productivity");
+        assert_eq!(
+            episode.description.as_deref(),
+            Some("<p>Doing fewer things, <b>better</b>.</p>")
+        );
+        assert_eq!(episode.enclosure_url, "https://example.com/audio/ep2.mp3");
+        assert_eq!(episode.enclosure_size, 52_428_800);
+        assert_eq!(episode.mime_type, "audio/mpeg");
+        assert_eq!(episode.duration, 3723);
+        assert_eq!(episode.published, 1_709_634_600_000_000);
+        assert_eq!(episode.keywords.as_ref().unwrap().len(), 2);
+        assert_eq!(episode.transcripts.len(), 2);
+        assert_eq!(
+            episode.timed_transcript().unwrap().url,
+            "https://example.com/transcripts/ep2.srt"
+        );
+
+        let episode = &feed.episodes[1];
+        assert_eq!(episode.duration, 754);
+        assert_eq!(
+            episode.image_url.as_deref(),
+            Some("https://example.com/ep1.jpg")
+        );
+        assert!(episode.timed_transcript().is_none());
+    }
+
+    #[test]
+    fn parse_atom_feed_works() {
+        let feed = parse_feed(include_str!("../fixtures/atom_feed.xml")).unwrap();
+        assert_eq!(feed.title, "Lecture Audio");
+        assert_eq!(feed.description.as_deref(), Some("Recorded lectures"));
+        assert_eq!(feed.episodes.len(), 1);
+        let episode = &feed.episodes[0];
+        assert_eq!(episode.title, "Lecture 3");
+        assert!(episode.guid.starts_with("urn:uuid:"));
+        assert_eq!(episode.enclosure_url, "https://example.com/lectures/3.ogg");
+        assert_eq!(episode.enclosure_size, 2048);
+        assert_eq!(episode.mime_type, "audio/ogg");
+        assert_eq!(episode.published, 1_709_634_600_000_000);
+    }
+
+    #[test]
+    fn parse_duration_works() {
+        assert_eq!(parse_duration("1:02:03"), 3723);
+        assert_eq!(parse_duration("02:03"), 123);
+        assert_eq!(parse_duration("754"), 754);
+        assert_eq!(parse_duration(""), 0);
+    }
+}
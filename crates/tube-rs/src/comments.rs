@@ -0,0 +1,283 @@
+use super::{extract_id, find_all, innertube_text, parse_count, YoutubeAudio};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::error::Error;
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Comment {
+    pub comment_id: String,
+    pub parent_id: Option<String>,
+    pub author: String,
+    pub text: String,
+    pub likes: u64,
+    pub reply_count: u64,
+    // relative time as shown by youtube, e.g. "2 weeks ago"
+    pub published_time: String,
+}
+
+struct CommentPage {
+    comments: Vec<Comment>,
+    // (comment id, continuation token) for threads that have replies
+    reply_tokens: Vec<(String, String)>,
+    next_token: Option<String>,
+}
+
+fn continuation_token(value: &Value) -> Option<String> {
+    let mut commands = Vec::new();
+    find_all(value, "continuationCommand", &mut commands);
+    commands
+        .into_iter()
+        .find_map(|command| command.get("token").and_then(|t| t.as_str()))
+        .map(str::to_string)
+}
+
+// the first page token lives in the item section with identifier "comment-item-section"
+fn comment_section_token(watch: &Value) -> Option<String> {
+    let mut sections = Vec::new();
+    find_all(watch, "itemSectionRenderer", &mut sections);
+    sections
+        .into_iter()
+        .filter(|section| {
+            section.get("sectionIdentifier").and_then(|s| s.as_str())
+                == Some("comment-item-section")
+        })
+        .find_map(continuation_token)
+}
+
+fn parse_entity_payload(payload: &Value) -> Option<Comment> {
+    let properties = payload.get("properties")?;
+    let toolbar = payload.get("toolbar");
+    let count = |key: &str| {
+        toolbar
+            .and_then(|t| t.get(key))
+            .and_then(|c| c.as_str())
+            .map(parse_count)
+            .unwrap_or(0)
+    };
+    Some(Comment {
+        comment_id: properties.get("commentId")?.as_str()?.to_string(),
+        parent_id: None,
+        author: payload
+            .get("author")
+            .and_then(|a| a.get("displayName"))
+            .and_then(|a| a.as_str())
+            .unwrap_or_default()
+            .to_string(),
+        text: properties
+            .get("content")
+            .and_then(innertube_text)
+            .unwrap_or_default(),
+        likes: count("likeCountNotliked"),
+        reply_count: count("replyCount"),
+        published_time: properties
+            .get("publishedTime")
+            .and_then(|p| p.as_str())
+            .unwrap_or_default()
+            .to_string(),
+    })
+}
+
+// older responses embed the comment directly as a `commentRenderer`
+fn parse_comment_renderer(renderer: &Value) -> Option<Comment> {
+    let text_of = |key: &str| renderer.get(key).and_then(innertube_text);
+    Some(Comment {
+        comment_id: renderer.get("commentId")?.as_str()?.to_string(),
+        parent_id: None,
+        author: text_of("authorText").unwrap_or_default(),
+        text: text_of("contentText").unwrap_or_default(),
+        likes: text_of("voteCount").map(|c| parse_count(&c)).unwrap_or(0),
+        reply_count: renderer
+            .get("replyCount")
+            .and_then(|c| c.as_u64())
+            .unwrap_or(0),
+        published_time: text_of("publishedTimeText").unwrap_or_default(),
+    })
+}
+
+fn thread_comment_id(thread: &Value) -> Option<String> {
+    thread
+        .pointer("/commentViewModel/commentViewModel/commentId")
+        .or_else(|| thread.pointer("/comment/commentRenderer/commentId"))
+        .and_then(|id| id.as_str())
+        .map(str::to_string)
+}
+
+fn parse_comment_page(page: &Value, parent_id: Option<&str>) -> CommentPage {
+    let mut payloads = Vec::new();
+    find_all(page, "commentEntityPayload", &mut payloads);
+    let mut comments: Vec<Comment> = payloads
+        .into_iter()
+        .filter_map(parse_entity_payload)
+        .collect();
+
+    if comments.is_empty() {
+        let mut renderers = Vec::new();
+        find_all(page, "commentRenderer", &mut renderers);
+        comments = renderers
+            .into_iter()
+            .filter_map(parse_comment_renderer)
+            .collect();
+    }
+
+    for comment in comments.iter_mut() {
+        comment.parent_id = parent_id.map(str::to_string);
+    }
+
+    let mut threads = Vec::new();
+    find_all(page, "commentThreadRenderer", &mut threads);
+    let reply_tokens = threads
+        .into_iter()
+        .filter_map(|thread| {
+            let token = continuation_token(thread.get("replies")?)?;
+            Some((thread_comment_id(thread)?, token))
+        })
+        .collect();
+
+    let mut item_lists = Vec::new();
+    find_all(page, "continuationItems", &mut item_lists);
+    let next_token = item_lists.into_iter().find_map(|items| {
+        items
+            .as_array()?
+            .last()?
+            .get("continuationItemRenderer")
+            .and_then(continuation_token)
+    });
+
+    CommentPage {
+        comments,
+        reply_tokens,
+        next_token,
+    }
+}
+
+impl YoutubeAudio {
+    // page through the top comments of a video, optionally expanding each thread's replies
+    pub async fn get_comments(
+        &self,
+        url: &str,
+        max_comments: usize,
+        with_replies: bool,
+    ) -> Result<Vec<Comment>, Box<dyn Error>> {
+        if max_comments == 0 {
+            return Ok(Vec::new());
+        }
+        let video_id = extract_id(url).ok_or("failed to parse the video id")?;
+        let watch = self.post_next(Some(&video_id), None).await?;
+
+        let mut comments = Vec::new();
+        let mut token = comment_section_token(&watch);
+        while let Some(current) = token.take() {
+            let page = self.post_next(None, Some(&current)).await?;
+            let parsed = parse_comment_page(&page, None);
+            comments.extend(parsed.comments);
+            if comments.len() >= max_comments {
+                break;
+            }
+
+            if with_replies {
+                for (comment_id, reply_token) in parsed.reply_tokens {
+                    let mut reply_token = Some(reply_token);
+                    while let Some(current) = reply_token.take() {
+                        let page = self.post_next(None, Some(&current)).await?;
+                        let replies = parse_comment_page(&page, Some(&comment_id));
+                        comments.extend(replies.comments);
+                        // enough already, no further page is fetched
+                        if comments.len() >= max_comments {
+                            comments.truncate(max_comments);
+                            return Ok(comments);
+                        }
+                        reply_token = replies.next_token;
+                    }
+                }
+            }
+            token = parsed.next_token;
+        }
+        comments.truncate(max_comments);
+        Ok(comments)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_count_works() {
+        assert_eq!(parse_count("12"), 12);
+        assert_eq!(parse_count("1.2K"), 1200);
+        assert_eq!(parse_count("3M"), 3_000_000);
+        assert_eq!(parse_count("1,024"), 1024);
+        assert_eq!(parse_count(""), 0);
+    }
+
+    #[test]
+    fn parse_comment_page_works() {
+        let page = serde_json::json!({
+            "onResponseReceivedEndpoints": [{
+                "reloadContinuationItemsCommand": {
+                    "continuationItems": [
+                        {
+                            "commentThreadRenderer": {
+                                "commentViewModel": {"commentViewModel": {"commentId": "c1"}},
+                                "replies": {"commentRepliesRenderer": {"contents": [{
+                                    "continuationItemRenderer": {"continuationEndpoint": {
+                                        "continuationCommand": {"token": "replies-c1"}
+                                    }}
+                                }]}}
+                            }
+                        },
+                        {
+                            "continuationItemRenderer": {"continuationEndpoint": {
+                                "continuationCommand": {"token": "next-page"}
+                            }}
+                        }
+                    ]
+                }
+            }],
+            "frameworkUpdates": {"entityBatchUpdate": {"mutations": [{
+                "payload": {"commentEntityPayload": {
+                    "properties": {
+                        "commentId": "c1",
+                        "content": {"content": "Great explanation"},
+                        "publishedTime": "2 weeks ago"
+                    },
+                    "author": {"displayName": "@viewer"},
+                    "toolbar": {"likeCountNotliked": "1.5K", "replyCount": "3"}
+                }}
+            }]}}
+        });
+
+        let parsed = parse_comment_page(&page, None);
+        assert_eq!(parsed.comments.len(), 1);
+        let comment = &parsed.comments[0];
+        assert_eq!(comment.author, "@viewer");
+        assert_eq!(comment.text, "Great explanation");
+        assert_eq!(comment.likes, 1500);
+        assert_eq!(comment.reply_count, 3);
+        assert_eq!(
+            parsed.reply_tokens,
+            vec![("c1".to_string(), "replies-c1".to_string())]
+        );
+        assert_eq!(parsed.next_token.as_deref(), Some("next-page"));
+    }
+
+    #[test]
+    fn comment_section_token_works() {
+        let watch = serde_json::json!({
+            "contents": {"twoColumnWatchNextResults": {"results": {"results": {"contents": [
+                {"itemSectionRenderer": {"sectionIdentifier": "other", "contents": []}},
+                {"itemSectionRenderer": {
+                    "sectionIdentifier": "comment-item-section",
+                    "contents": [{"continuationItemRenderer": {"continuationEndpoint": {
+                        "continuationCommand": {"token": "comments-token"}
+                    }}}]
+                }}
+            ]}}}}
+        });
+        assert_eq!(
+            comment_section_token(&watch).as_deref(),
+            Some("comments-token")
+        );
+    }
+}
@@ -0,0 +1,170 @@
+use super::{extract_id, AudioData, AudioStream, Html5Extractor, SubtitleEntry, YoutubeAudio};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::{error::Error, path::Path};
+
+pub type ExtractorError = Box<dyn Error + Send + Sync>;
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CaptionTrack {
+    pub lang: String,
+    pub url: String,
+}
+
+// a source platform that can turn a page url into metadata, captions and an audio stream
+#[async_trait]
+pub trait Extractor: Send + Sync {
+    // unique short name; non-youtube video ids are stored as `name:id`
+    fn name(&self) -> &'static str;
+
+    fn matches(&self, url: &str) -> bool;
+
+    async fn fetch_metadata(&self, url: &str) -> Result<AudioData, ExtractorError>;
+
+    async fn list_captions(&self, url: &str) -> Result<Vec<CaptionTrack>, ExtractorError>;
+
+    async fn fetch_caption(
+        &self,
+        caption: &CaptionTrack,
+    ) -> Result<Vec<SubtitleEntry>, ExtractorError>;
+
+    async fn resolve_audio_stream(&self, url: &str) -> Result<AudioStream, ExtractorError>;
+
+    async fn download_audio(
+        &self,
+        audio_url: &str,
+        file_size: u64,
+        file_path: &Path,
+    ) -> Result<(), ExtractorError>;
+}
+
+#[async_trait]
+impl Extractor for YoutubeAudio {
+    fn name(&self) -> &'static str {
+        "youtube"
+    }
+
+    fn matches(&self, url: &str) -> bool {
+        (url.contains("youtube.com") || url.contains("youtu.be")) && extract_id(url).is_some()
+    }
+
+    async fn fetch_metadata(&self, url: &str) -> Result<AudioData, ExtractorError> {
+        self.get_video_info(url)
+            .await
+            .ok_or_else(|| "failed to parse audio info".into())
+    }
+
+    async fn list_captions(&self, url: &str) -> Result<Vec<CaptionTrack>, ExtractorError> {
+        let video_id = extract_id(url).ok_or("failed to parse the video id")?;
+        let response = self
+            .get_player_response(&video_id)
+            .await
+            .ok_or("failed to get the player response")?;
+        Ok(response
+            .captions
+            .map(|captions| {
+                captions
+                    .player_captions_tracklist_renderer
+                    .caption_tracks
+                    .into_iter()
+                    .map(|item| CaptionTrack {
+                        lang: item.vss_id,
+                        url: item.base_url,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    async fn fetch_caption(
+        &self,
+        caption: &CaptionTrack,
+    ) -> Result<Vec<SubtitleEntry>, ExtractorError> {
+        YoutubeAudio::download_caption(self, &caption.url, &caption.lang)
+            .await
+            .map_err(|e| e.to_string().into())
+    }
+
+    async fn resolve_audio_stream(&self, url: &str) -> Result<AudioStream, ExtractorError> {
+        let audio_data = self.fetch_metadata(url).await?;
+        Ok(AudioStream {
+            url: audio_data.audio_url,
+            filesize: audio_data.audio_filesize,
+            mime_type: audio_data.mime_type,
+        })
+    }
+
+    async fn download_audio(
+        &self,
+        audio_url: &str,
+        file_size: u64,
+        file_path: &Path,
+    ) -> Result<(), ExtractorError> {
+        YoutubeAudio::download_audio(self, audio_url, file_size, file_path)
+            .await
+            .map_err(|e| e.to_string().into())
+    }
+}
+
+// extractors in priority order, the generic html5 page extractor is the last fallback
+pub struct ExtractorRegistry {
+    extractors: Vec<Box<dyn Extractor>>,
+}
+
+impl ExtractorRegistry {
+    pub fn new(proxy: Option<&str>, tube_api_url: Option<String>) -> Self {
+        Self {
+            extractors: vec![
+                Box::new(YoutubeAudio::new(proxy, tube_api_url)),
+                Box::new(Html5Extractor::new(proxy)),
+            ],
+        }
+    }
+
+    pub fn find(&self, url: &str) -> Option<&dyn Extractor> {
+        self.extractors
+            .iter()
+            .find(|extractor| extractor.matches(url))
+            .map(|extractor| extractor.as_ref())
+    }
+
+    // youtube ids are stored bare for compatibility, everything else carries its extractor prefix
+    pub fn find_for_video_id(&self, video_id: &str) -> Option<&dyn Extractor> {
+        let name = match video_id.split_once(':') {
+            Some((prefix, _)) => prefix,
+            None => "youtube",
+        };
+        self.extractors
+            .iter()
+            .find(|extractor| extractor.name() == name)
+            .map(|extractor| extractor.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registry_find_works() {
+        let registry = ExtractorRegistry::new(None, None);
+        let name = |url: &str| registry.find(url).map(|e| e.name());
+        assert_eq!(name("https://youtu.be/FdeioVndUhs"), Some("youtube"));
+        assert_eq!(
+            name("https://www.youtube.com/watch?v=FdeioVndUhs"),
+            Some("youtube")
+        );
+        assert_eq!(name("https://example.com/talk.html"), Some("html5"));
+        assert_eq!(name("not a url"), None);
+    }
+
+    #[test]
+    fn registry_find_for_video_id_works() {
+        let registry = ExtractorRegistry::new(None, None);
+        let name = |id: &str| registry.find_for_video_id(id).map(|e| e.name());
+        assert_eq!(name("FdeioVndUhs"), Some("youtube"));
+        assert_eq!(name("html5:0123456789abcdef"), Some("html5"));
+        assert_eq!(name("unknown:1"), None);
+    }
+}
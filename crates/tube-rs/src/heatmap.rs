@@ -0,0 +1,120 @@
+use super::{extract_id, find_all, YoutubeAudio};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::error::Error;
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct HeatMarker {
+    // start and duration in milliseconds
+    pub start: u64,
+    pub duration: u64,
+    // replay intensity normalized to 0..1 by youtube
+    pub intensity: f64,
+}
+
+fn millis(value: Option<&Value>) -> u64 {
+    match value {
+        Some(Value::String(text)) => text.parse().unwrap_or(0),
+        Some(value) => value.as_u64().unwrap_or(0),
+        None => 0,
+    }
+}
+
+fn parse_heatmap(watch: &Value) -> Vec<HeatMarker> {
+    // current responses ship the markers as a framework entity
+    let mut marker_lists = Vec::new();
+    find_all(watch, "macroMarkersListEntity", &mut marker_lists);
+    let mut markers: Vec<HeatMarker> = marker_lists
+        .into_iter()
+        .filter_map(|entity| entity.get("markersList"))
+        .filter(|list| {
+            list.get("markerType").and_then(|t| t.as_str()) == Some("MARKER_TYPE_HEATMAP")
+        })
+        .filter_map(|list| list.get("markers")?.as_array())
+        .flatten()
+        .map(|marker| HeatMarker {
+            start: millis(marker.get("startMillis")),
+            duration: millis(marker.get("durationMillis")),
+            intensity: marker
+                .get("intensityScoreNormalized")
+                .and_then(|i| i.as_f64())
+                .unwrap_or(0.0),
+        })
+        .collect();
+
+    // older responses keep them in the player bar overlay
+    if markers.is_empty() {
+        let mut renderers = Vec::new();
+        find_all(watch, "heatMarkerRenderer", &mut renderers);
+        markers = renderers
+            .into_iter()
+            .map(|marker| HeatMarker {
+                start: millis(marker.get("timeRangeStartMillis")),
+                duration: millis(marker.get("markerDurationMillis")),
+                intensity: marker
+                    .get("heatMarkerIntensityScoreNormalized")
+                    .and_then(|i| i.as_f64())
+                    .unwrap_or(0.0),
+            })
+            .collect();
+    }
+
+    markers.sort_by_key(|marker| marker.start);
+    markers
+}
+
+impl YoutubeAudio {
+    // fetch the "most replayed" heatmap of a video, empty when youtube does not show one
+    pub async fn get_heatmap(&self, url: &str) -> Result<Vec<HeatMarker>, Box<dyn Error>> {
+        let video_id = extract_id(url).ok_or("failed to parse the video id")?;
+        let watch = self.post_next(Some(&video_id), None).await?;
+        Ok(parse_heatmap(&watch))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_heatmap_entity_works() {
+        let watch = serde_json::json!({
+            "frameworkUpdates": {"entityBatchUpdate": {"mutations": [
+                {"payload": {"macroMarkersListEntity": {"markersList": {
+                    "markerType": "MARKER_TYPE_TIMESTAMPS",
+                    "markers": [{"startMillis": "0", "durationMillis": "1000", "intensityScoreNormalized": 1}]
+                }}}},
+                {"payload": {"macroMarkersListEntity": {"markersList": {
+                    "markerType": "MARKER_TYPE_HEATMAP",
+                    "markers": [
+                        {"startMillis": "5000", "durationMillis": "5000", "intensityScoreNormalized": 0.25},
+                        {"startMillis": "0", "durationMillis": "5000", "intensityScoreNormalized": 1}
+                    ]
+                }}}}
+            ]}}
+        });
+        let markers = parse_heatmap(&watch);
+        assert_eq!(markers.len(), 2);
+        assert_eq!(markers[0].start, 0);
+        assert_eq!(markers[0].intensity, 1.0);
+        assert_eq!(markers[1].duration, 5000);
+    }
+
+    #[test]
+    fn parse_heatmap_renderer_works() {
+        let watch = serde_json::json!({
+            "playerOverlays": {"heatMarkers": [
+                {"heatMarkerRenderer": {
+                    "timeRangeStartMillis": 2000,
+                    "markerDurationMillis": 2000,
+                    "heatMarkerIntensityScoreNormalized": 0.5
+                }}
+            ]}
+        });
+        let markers = parse_heatmap(&watch);
+        assert_eq!(markers.len(), 1);
+        assert_eq!(markers[0].start, 2000);
+        assert_eq!(markers[0].intensity, 0.5);
+    }
+}
@@ -0,0 +1,482 @@
+use super::extractor::{CaptionTrack, Extractor, ExtractorError};
+use super::hash::fnv1a;
+use super::{build_client, AudioData, AudioStream, SubtitleEntry};
+use async_trait::async_trait;
+use regex::Regex;
+use reqwest::{
+    header::{CONTENT_LENGTH, USER_AGENT},
+    Client, Url,
+};
+use std::{fs::File, io::Write, path::Path};
+
+// generic extractor for any page that embeds its media with html5 `<audio>` or `<video>` tags
+pub struct Html5Extractor {
+    client: Client,
+}
+
+struct MediaSource {
+    url: String,
+    mime_type: String,
+    is_audio: bool,
+}
+
+struct MediaPage {
+    title: String,
+    description: Option<String>,
+    keywords: Option<Vec<String>>,
+    thumbnail_url: String,
+    duration: u64,
+    sources: Vec<MediaSource>,
+    tracks: Vec<CaptionTrack>,
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+}
+
+fn parse_attributes(tag: &str) -> Vec<(String, String)> {
+    let re =
+        Regex::new(r#"([a-zA-Z_:][-a-zA-Z0-9_:.]*)\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s"'>]+))"#)
+            .unwrap();
+    re.captures_iter(tag)
+        .map(|caps| {
+            let value = caps
+                .get(2)
+                .or_else(|| caps.get(3))
+                .or_else(|| caps.get(4))
+                .map_or("", |m| m.as_str());
+            (caps[1].to_lowercase(), decode_entities(value))
+        })
+        .collect()
+}
+
+fn attribute<'a>(attributes: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    attributes
+        .iter()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.as_str())
+}
+
+fn guess_mime_type(url: &str, is_audio: bool) -> String {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    let extension = path.rsplit('.').next().unwrap_or_default().to_lowercase();
+    let mime_type = match extension.as_str() {
+        "mp3" => "audio/mpeg",
+        "m4a" | "aac" => "audio/mp4",
+        "ogg" | "oga" | "opus" => "audio/ogg",
+        "wav" => "audio/wav",
+        "flac" => "audio/flac",
+        "webm" if is_audio => "audio/webm",
+        "webm" => "video/webm",
+        _ if is_audio => "audio/mp4",
+        _ => "video/mp4",
+    };
+    mime_type.to_string()
+}
+
+fn parse_media_page(html: &str, page_url: &Url) -> MediaPage {
+    let resolve = |src: &str| page_url.join(src).map(|u| u.to_string()).ok();
+
+    let mut metas = Vec::new();
+    let meta_re = Regex::new(r"(?is)<meta\b([^>]*)>").unwrap();
+    for caps in meta_re.captures_iter(html) {
+        let attributes = parse_attributes(&caps[1]);
+        let key = attribute(&attributes, "property").or_else(|| attribute(&attributes, "name"));
+        if let (Some(key), Some(content)) = (key, attribute(&attributes, "content")) {
+            metas.push((key.to_lowercase(), content.to_string()));
+        }
+    }
+    let meta = |key: &str| {
+        metas
+            .iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, content)| content.clone())
+    };
+
+    let title = meta("og:title")
+        .or_else(|| {
+            Regex::new(r"(?is)<title[^>]*>(.*?)</title>")
+                .unwrap()
+                .captures(html)
+                .map(|caps| decode_entities(caps[1].trim()))
+        })
+        .unwrap_or_else(|| page_url.to_string());
+
+    let mut sources = Vec::new();
+    let mut tracks = Vec::new();
+    let mut poster = None;
+    let media_re = Regex::new(r"(?is)<(video|audio)\b([^>]*)>").unwrap();
+    let source_re = Regex::new(r"(?is)<source\b([^>]*)>").unwrap();
+    let track_re = Regex::new(r"(?is)<track\b([^>]*)>").unwrap();
+    for caps in media_re.captures_iter(html) {
+        let tag = caps[1].to_lowercase();
+        let is_audio = tag == "audio";
+        let attributes = parse_attributes(&caps[2]);
+        let body_start = caps.get(0).unwrap().end();
+        let body_end = html[body_start..]
+            .to_ascii_lowercase()
+            .find(&format!("</{tag}>"))
+            .map_or(html.len(), |end| body_start + end);
+        let body = &html[body_start..body_end];
+
+        if poster.is_none() {
+            poster = attribute(&attributes, "poster").and_then(resolve);
+        }
+
+        let mut element_sources = Vec::new();
+        if let Some(src) = attribute(&attributes, "src") {
+            element_sources.push((src.to_string(), None));
+        }
+        for source in source_re.captures_iter(body) {
+            let attributes = parse_attributes(&source[1]);
+            if let Some(src) = attribute(&attributes, "src") {
+                element_sources.push((
+                    src.to_string(),
+                    attribute(&attributes, "type").map(str::to_string),
+                ));
+            }
+        }
+        for (src, mime_type) in element_sources {
+            if let Some(url) = resolve(&src) {
+                sources.push(MediaSource {
+                    mime_type: mime_type.unwrap_or_else(|| guess_mime_type(&url, is_audio)),
+                    url,
+                    is_audio,
+                });
+            }
+        }
+
+        for track in track_re.captures_iter(body) {
+            let attributes = parse_attributes(&track[1]);
+            let kind = attribute(&attributes, "kind").unwrap_or("subtitles");
+            if kind != "subtitles" && kind != "captions" {
+                continue;
+            }
+            if let Some(url) = attribute(&attributes, "src").and_then(resolve) {
+                tracks.push(CaptionTrack {
+                    lang: attribute(&attributes, "srclang")
+                        .unwrap_or("und")
+                        .to_string(),
+                    url,
+                });
+            }
+        }
+    }
+
+    MediaPage {
+        title,
+        description: meta("og:description").or_else(|| meta("description")),
+        keywords: meta("keywords").map(|keywords| {
+            keywords
+                .split(',')
+                .map(|k| k.trim().to_string())
+                .filter(|k| !k.is_empty())
+                .collect()
+        }),
+        thumbnail_url: poster
+            .or_else(|| meta("og:image").and_then(|image| resolve(&image)))
+            .unwrap_or_default(),
+        duration: meta("og:video:duration")
+            .or_else(|| meta("video:duration"))
+            .and_then(|duration| duration.trim().parse().ok())
+            .unwrap_or(0),
+        sources,
+        tracks,
+    }
+}
+
+// "01:02:03.456", "02:03.456" or the srt style "01:02:03,456" to milliseconds
+fn parse_cue_time(text: &str) -> Option<u64> {
+    let text = text.trim().replace(',', ".");
+    let (clock, millis) = text.split_once('.').unwrap_or((text.as_str(), "0"));
+    let seconds = clock
+        .split(':')
+        .map(|part| part.parse::<u64>().ok())
+        .try_fold(0, |total, part| part.map(|part| total * 60 + part))?;
+    // only the leading digits count, padded or cut to three on chars, not bytes
+    let millis: String = millis
+        .chars()
+        .take_while(|c| c.is_ascii_digit())
+        .chain(std::iter::repeat('0'))
+        .take(3)
+        .collect();
+    let millis: u64 = millis.parse().ok()?;
+    Some(seconds * 1000 + millis)
+}
+
+// parse a WebVTT or SRT caption file, markup inside the cues is dropped
+pub fn parse_text_track(content: &str) -> Vec<SubtitleEntry> {
+    let tag_re = Regex::new(r"<[^>]+>").unwrap();
+    let mut entries = Vec::new();
+    let mut lines = content.lines().peekable();
+    while let Some(line) = lines.next() {
+        let Some((start, end)) = line.split_once("-->") else {
+            continue;
+        };
+        // cue settings follow the end time, e.g. "align:start position:10%"
+        let end = end.split_whitespace().next().unwrap_or_default();
+        let (Some(start), Some(end)) = (parse_cue_time(start), parse_cue_time(end)) else {
+            continue;
+        };
+        let mut text = Vec::new();
+        while let Some(next) = lines.peek() {
+            if next.trim().is_empty() {
+                break;
+            }
+            text.push(tag_re.replace_all(next.trim(), "").to_string());
+            lines.next();
+        }
+        entries.push(SubtitleEntry {
+            timestamp: start,
+            duration: end.saturating_sub(start) as u32,
+            text: decode_entities(&text.join(" ")),
+        });
+    }
+    entries
+}
+
+impl Html5Extractor {
+    pub fn new(proxy: Option<&str>) -> Self {
+        Self {
+            client: build_client(proxy),
+        }
+    }
+
+    async fn fetch_page(&self, url: &str) -> Result<MediaPage, ExtractorError> {
+        let page_url = Url::parse(url)?;
+        let html = self
+            .client
+            .get(page_url.clone())
+            .header(USER_AGENT, "Mozilla/5.0")
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        Ok(parse_media_page(&html, &page_url))
+    }
+
+    async fn content_length(&self, url: &str) -> u64 {
+        match self.client.head(url).send().await {
+            Ok(response) => response
+                .headers()
+                .get(CONTENT_LENGTH)
+                .and_then(|length| length.to_str().ok())
+                .and_then(|length| length.parse().ok())
+                .unwrap_or(0),
+            Err(_) => 0,
+        }
+    }
+}
+
+fn best_source(page: &MediaPage) -> Option<&MediaSource> {
+    page.sources
+        .iter()
+        .find(|source| source.is_audio)
+        .or_else(|| page.sources.first())
+}
+
+#[async_trait]
+impl Extractor for Html5Extractor {
+    fn name(&self) -> &'static str {
+        "html5"
+    }
+
+    fn matches(&self, url: &str) -> bool {
+        Url::parse(url).is_ok_and(|url| url.scheme() == "http" || url.scheme() == "https")
+    }
+
+    async fn fetch_metadata(&self, url: &str) -> Result<AudioData, ExtractorError> {
+        let page = self.fetch_page(url).await?;
+        let source = best_source(&page).ok_or("no audio or video found on the page")?;
+        let caption = page.tracks.first();
+        Ok(AudioData {
+            video_id: format!("{}:{:016x}", self.name(), fnv1a(&source.url)),
+            title: page.title.clone(),
+            channel: Url::parse(url)
+                .ok()
+                .and_then(|u| u.host_str().map(str::to_string)),
+            duration: page.duration,
+            timestamp: 0,
+            keywords: page.keywords.clone(),
+            description: page.description.clone(),
+            caption_lang: caption.map(|c| c.lang.clone()),
+            caption_url: caption.map(|c| c.url.clone()),
+            audio_url: source.url.clone(),
+            audio_filesize: self.content_length(&source.url).await,
+            thumbnail_url: page.thumbnail_url.clone(),
+            mime_type: source.mime_type.clone(),
+        })
+    }
+
+    async fn list_captions(&self, url: &str) -> Result<Vec<CaptionTrack>, ExtractorError> {
+        Ok(self.fetch_page(url).await?.tracks)
+    }
+
+    async fn fetch_caption(
+        &self,
+        caption: &CaptionTrack,
+    ) -> Result<Vec<SubtitleEntry>, ExtractorError> {
+        let content = self
+            .client
+            .get(&caption.url)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        Ok(parse_text_track(&content))
+    }
+
+    async fn resolve_audio_stream(&self, url: &str) -> Result<AudioStream, ExtractorError> {
+        let page = self.fetch_page(url).await?;
+        let source = best_source(&page).ok_or("no audio or video found on the page")?;
+        Ok(AudioStream {
+            filesize: self.content_length(&source.url).await,
+            url: source.url.clone(),
+            mime_type: source.mime_type.clone(),
+        })
+    }
+
+    async fn download_audio(
+        &self,
+        audio_url: &str,
+        _file_size: u64,
+        file_path: &Path,
+    ) -> Result<(), ExtractorError> {
+        let mut response = self
+            .client
+            .get(audio_url)
+            .header(USER_AGENT, "Mozilla/5.0")
+            .send()
+            .await?
+            .error_for_status()?;
+        let mut file = File::create(file_path)?;
+        while let Some(chunk) = response.chunk().await? {
+            file.write_all(&chunk)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    const PAGE: &str = include_str!("../fixtures/html5_page.html");
+    const CAPTIONS: &str = include_str!("../fixtures/html5_captions.vtt");
+    const AUDIO: &[u8] = include_bytes!("../fixtures/html5_audio.mp3");
+
+    // serve the fixtures over plain http so the extractor runs its real requests
+    async fn serve_fixtures() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut buffer = vec![0; 4096];
+                let size = stream.read(&mut buffer).await.unwrap_or(0);
+                let request = String::from_utf8_lossy(&buffer[..size]).to_string();
+                let mut parts = request.split_whitespace();
+                let method = parts.next().unwrap_or_default();
+                let path = parts.next().unwrap_or_default();
+                let (status, body): (&str, &[u8]) = match path {
+                    "/talk.html" => ("200 OK", PAGE.as_bytes()),
+                    "/media/lecture-3.en.vtt" => ("200 OK", CAPTIONS.as_bytes()),
+                    "/media/lecture-3.mp3" => ("200 OK", AUDIO),
+                    _ => ("404 Not Found", b""),
+                };
+                let header = format!(
+                    "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    body.len()
+                );
+                let _ = stream.write_all(header.as_bytes()).await;
+                if method != "HEAD" {
+                    let _ = stream.write_all(body).await;
+                }
+            }
+        });
+        format!("http://{address}")
+    }
+
+    #[test]
+    fn parse_media_page_works() {
+        let page_url = Url::parse("https://example.com/courses/talk.html").unwrap();
+        let page = parse_media_page(PAGE, &page_url);
+        assert_eq!(page.title, "Weekly Lecture & Notes");
+        assert_eq!(page.duration, 754);
+        assert_eq!(
+            page.thumbnail_url,
+            "https://example.com/images/poster.jpg".to_string()
+        );
+        assert_eq!(page.keywords.as_ref().unwrap().len(), 3);
+        assert_eq!(page.sources.len(), 2);
+        assert_eq!(page.sources[0].mime_type, "video/mp4");
+        assert_eq!(page.sources[1].mime_type, "audio/mpeg");
+        // the chapters track is not a caption
+        assert_eq!(page.tracks.len(), 1);
+        assert_eq!(
+            page.tracks[0].url,
+            "https://example.com/media/lecture-3.en.vtt"
+        );
+        assert_eq!(
+            best_source(&page).unwrap().url,
+            "https://example.com/media/lecture-3.mp3"
+        );
+    }
+
+    #[test]
+    fn parse_text_track_works() {
+        let entries = parse_text_track(CAPTIONS);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].timestamp, 1000);
+        assert_eq!(entries[0].duration, 3500);
+        assert_eq!(entries[0].text, "Welcome to the third lecture.");
+        assert_eq!(entries[1].text, "Today we talk about gradient descent.");
+    }
+
+    #[test]
+    fn parse_cue_time_works() {
+        assert_eq!(parse_cue_time("00:01:02.500"), Some(62_500));
+        assert_eq!(parse_cue_time("01:02.5"), Some(62_500));
+        assert_eq!(parse_cue_time("00:00:03,250"), Some(3_250));
+        assert_eq!(parse_cue_time("abc"), None);
+        // multi-byte characters after the fraction do not panic
+        assert_eq!(parse_cue_time("00:01.1€"), Some(1_100));
+        assert_eq!(parse_cue_time("00:01.€"), Some(1_000));
+        assert_eq!(parse_cue_time("00:01.12345"), Some(1_123));
+    }
+
+    #[tokio::test]
+    async fn extract_local_page_works() {
+        let base_url = serve_fixtures().await;
+        let extractor = Html5Extractor::new(None);
+        let page_url = format!("{base_url}/talk.html");
+        assert!(extractor.matches(&page_url));
+
+        let audio_data = extractor.fetch_metadata(&page_url).await.unwrap();
+        assert!(audio_data.video_id.starts_with("html5:"));
+        assert_eq!(audio_data.title, "Weekly Lecture & Notes");
+        assert_eq!(audio_data.audio_filesize, AUDIO.len() as u64);
+        assert_eq!(audio_data.caption_lang.as_deref(), Some("en"));
+
+        let captions = extractor.list_captions(&page_url).await.unwrap();
+        let entries = extractor.fetch_caption(&captions[0]).await.unwrap();
+        assert_eq!(entries.len(), 2);
+
+        let file_path = std::env::temp_dir().join(format!("{}.mp3", fnv1a(&base_url)));
+        extractor
+            .download_audio(&audio_data.audio_url, audio_data.audio_filesize, &file_path)
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read(&file_path).unwrap(), AUDIO);
+        std::fs::remove_file(file_path).unwrap();
+    }
+}
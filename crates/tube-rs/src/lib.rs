@@ -7,6 +7,24 @@ use serde::{Deserialize, Serialize};
 use serde_xml_rs::from_str;
 use std::{error::Error, fs::File, io::Write, path::Path, time::Duration};
 
+mod comments;
+mod extractor;
+mod hash;
+mod heatmap;
+mod html5;
+mod live_chat;
+mod search;
+
+pub use comments::Comment;
+pub use extractor::{CaptionTrack, Extractor, ExtractorError, ExtractorRegistry};
+pub use hash::{fnv1a, Fnv1a};
+pub use heatmap::HeatMarker;
+pub use html5::{parse_text_track, Html5Extractor};
+pub use live_chat::ChatMessage;
+pub use search::{DurationFilter, SearchFilter, SearchResult, UploadDateFilter};
+
+const INNERTUBE_URL: &str = "https://www.youtube.com/youtubei/v1";
+
 pub struct YoutubeAudio {
     client: Client,
     tube_api_url: Option<String>,
@@ -78,6 +96,16 @@ struct RequestBody {
     service_integrity_dimensions: ServiceIntegrityDimensions,
 }
 
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct NextRequestBody {
+    context: RequestContext,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    video_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    continuation: Option<String>,
+}
+
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct ResponseBody {
@@ -91,6 +119,7 @@ struct ResponseBody {
 struct VideoDetail {
     // video_id: String,
     title: String,
+    author: Option<String>,
     length_seconds: String,
     keywords: Option<Vec<String>>,
     short_description: Option<String>,
@@ -134,10 +163,10 @@ struct StreamingData {
 }
 
 #[derive(Deserialize, Debug)]
-struct AudioStream {
-    url: String,
-    filesize: u64,
-    mime_type: String,
+pub struct AudioStream {
+    pub url: String,
+    pub filesize: u64,
+    pub mime_type: String,
 }
 
 #[derive(Deserialize, Debug)]
@@ -151,6 +180,9 @@ struct AudioResponsBody {
 pub struct AudioData {
     pub video_id: String,
     pub title: String,
+    // youtube channel, podcast name or site the media was found on
+    #[serde(default)]
+    pub channel: Option<String>,
     pub duration: u64,
     pub timestamp: u64,
     pub keywords: Option<Vec<String>>,
@@ -190,6 +222,83 @@ fn extract_id(url: &str) -> Option<String> {
     None
 }
 
+fn build_client(proxy: Option<&str>) -> Client {
+    let client_builder = Client::builder();
+    match proxy {
+        Some(proxy_str) => match Proxy::https(proxy_str) {
+            Ok(ok_proxy) => client_builder.proxy(ok_proxy).build().unwrap(),
+            Err(_) => client_builder.build().unwrap(),
+        },
+        _ => client_builder.build().unwrap(),
+    }
+}
+
+fn innertube_headers() -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(USER_AGENT, HeaderValue::from_static("Mozilla/5.0"));
+    headers.insert("X-Youtube-Client-Name", HeaderValue::from_static("1"));
+    headers.insert(
+        "X-Youtube-Client-Version",
+        HeaderValue::from_static("2.20240726.00.00"),
+    );
+    headers.insert(ACCEPT_LANGUAGE, HeaderValue::from_static("en-us,en"));
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    headers
+}
+
+// parse the short counts shown by youtube such as "1.2K" or "3M"
+fn parse_count(text: &str) -> u64 {
+    let text = text.trim().replace(',', "");
+    let (number, multiplier) = match text.chars().last() {
+        Some('K') | Some('k') => (&text[..text.len() - 1], 1_000.0),
+        Some('M') | Some('m') => (&text[..text.len() - 1], 1_000_000.0),
+        Some('B') | Some('b') => (&text[..text.len() - 1], 1_000_000_000.0),
+        _ => (text.as_str(), 1.0),
+    };
+    number
+        .trim()
+        .parse::<f64>()
+        .map(|value| (value * multiplier).round() as u64)
+        .unwrap_or(0)
+}
+
+// depth-first search for every object stored under `key` in an innertube response
+fn find_all<'a>(value: &'a serde_json::Value, key: &str, found: &mut Vec<&'a serde_json::Value>) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (k, v) in map {
+                if k == key {
+                    found.push(v);
+                }
+                find_all(v, key, found);
+            }
+        }
+        serde_json::Value::Array(array) => {
+            for v in array {
+                find_all(v, key, found);
+            }
+        }
+        _ => {}
+    }
+}
+
+// join the text of either `simpleText` or `runs` style innertube text objects
+fn innertube_text(value: &serde_json::Value) -> Option<String> {
+    if let Some(text) = value.get("simpleText").and_then(|t| t.as_str()) {
+        return Some(text.to_string());
+    }
+    if let Some(text) = value.get("content").and_then(|t| t.as_str()) {
+        return Some(text.to_string());
+    }
+    let runs = value.get("runs")?.as_array()?;
+    Some(
+        runs.iter()
+            .filter_map(|run| run.get("text").and_then(|t| t.as_str()))
+            .collect::<Vec<&str>>()
+            .join(""),
+    )
+}
+
 fn preprocess_xml(xml_content: &str) -> String {
     // Remove XML declaration
     let without_declaration = xml_content
@@ -264,20 +373,69 @@ fn parse_xml(xml: &str) -> Vec<SubtitleEntry> {
 
 impl YoutubeAudio {
     pub fn new(proxy: Option<&str>, tube_api_url: Option<String>) -> Self {
-        let client_builder = Client::builder();
-        let client = match proxy {
-            Some(proxy_str) => match Proxy::https(proxy_str) {
-                Ok(ok_proxy) => client_builder.proxy(ok_proxy).build().unwrap(),
-                Err(_) => client_builder.build().unwrap(),
-            },
-            _ => client_builder.build().unwrap(),
-        };
         Self {
-            client,
+            client: build_client(proxy),
             tube_api_url,
         }
     }
 
+    async fn post_innertube<T: Serialize>(
+        &self,
+        endpoint: &str,
+        body: &T,
+    ) -> Result<serde_json::Value, Box<dyn Error>> {
+        const MAX_RETRIES: u32 = 3;
+        const INITIAL_BACKOFF_MS: u64 = 1000;
+
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let result = self
+                .client
+                .post(format!("{INNERTUBE_URL}/{endpoint}?prettyPrint=false"))
+                .headers(innertube_headers())
+                .json(body)
+                .send()
+                .await;
+            match result {
+                Ok(response) => match response.json::<serde_json::Value>().await {
+                    Ok(data) => return Ok(data),
+                    Err(e) => {
+                        eprintln!("Failed to parse the {endpoint} response {e}");
+                        if attempt >= MAX_RETRIES {
+                            return Err(Box::new(e));
+                        }
+                    }
+                },
+                Err(e) => {
+                    eprintln!("Failed to get {endpoint} response from youtube {e}");
+                    if attempt >= MAX_RETRIES {
+                        return Err(Box::new(e));
+                    }
+                }
+            }
+            let backoff_duration =
+                Duration::from_millis(INITIAL_BACKOFF_MS * 2u64.pow(attempt - 1));
+            tokio::time::sleep(backoff_duration).await;
+        }
+    }
+
+    // request the watch-next data of a video, or the next page of a continuation
+    async fn post_next(
+        &self,
+        video_id: Option<&str>,
+        continuation: Option<&str>,
+    ) -> Result<serde_json::Value, Box<dyn Error>> {
+        let body = NextRequestBody {
+            context: RequestContext {
+                client: ClientInfo::default(String::new()),
+            },
+            video_id: video_id.map(str::to_string),
+            continuation: continuation.map(str::to_string),
+        };
+        self.post_innertube("next", &body).await
+    }
+
     async fn get_auth_info(&self, video_id: &str) -> Result<AuthData, String> {
         let url = format!("https://www.youtube.com/watch?v={video_id}");
         // parse js_url
@@ -317,13 +475,8 @@ impl YoutubeAudio {
         })
     }
 
-    pub async fn get_video_info(&self, url: &str) -> Option<AudioData> {
-        let video_id = match extract_id(url) {
-            Some(_id) => _id,
-            None => return None,
-        };
-
-        let auth_data = self.get_auth_info(&video_id).await.ok()?;
+    async fn get_player_response(&self, video_id: &str) -> Option<ResponseBody> {
+        let auth_data = self.get_auth_info(video_id).await.ok()?;
 
         let mut headers = HeaderMap::new();
 
@@ -388,6 +541,16 @@ impl YoutubeAudio {
                 Duration::from_millis(INITIAL_BACKOFF_MS * 2u64.pow(attempt - 1));
             tokio::time::sleep(backoff_duration).await;
         };
+        Some(response_data)
+    }
+
+    pub async fn get_video_info(&self, url: &str) -> Option<AudioData> {
+        let video_id = match extract_id(url) {
+            Some(_id) => _id,
+            None => return None,
+        };
+
+        let response_data = self.get_player_response(&video_id).await?;
 
         let mut all_formats = Vec::new();
 
@@ -443,6 +606,7 @@ impl YoutubeAudio {
         Some(AudioData {
             video_id,
             title: response_data.video_details.title,
+            channel: response_data.video_details.author,
             duration: response_data
                 .video_details
                 .length_seconds
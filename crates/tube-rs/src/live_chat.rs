@@ -0,0 +1,203 @@
+use super::{extract_id, find_all, innertube_text, ClientInfo, RequestContext, YoutubeAudio};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::error::Error;
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ChatMessage {
+    // offset from the start of the video in milliseconds
+    pub offset: u64,
+    pub author: String,
+    pub text: String,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct PlayerState {
+    player_offset_ms: String,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ChatReplayRequestBody {
+    context: RequestContext,
+    continuation: String,
+    current_player_state: PlayerState,
+}
+
+struct ChatPage {
+    messages: Vec<ChatMessage>,
+    next_token: Option<String>,
+}
+
+// the replay token is only present in the conversation bar of recorded livestreams
+fn chat_replay_token(watch: &Value) -> Option<String> {
+    let mut renderers = Vec::new();
+    find_all(watch, "liveChatRenderer", &mut renderers);
+    renderers.into_iter().find_map(|renderer| {
+        renderer
+            .get("continuations")?
+            .as_array()?
+            .iter()
+            .find_map(|c| c.pointer("/reloadContinuationData/continuation"))
+            .and_then(|c| c.as_str())
+            .map(str::to_string)
+    })
+}
+
+// chat messages mix text runs with emoji runs, keep the emoji shortcut as text
+fn message_text(message: &Value) -> String {
+    let Some(runs) = message.get("runs").and_then(|r| r.as_array()) else {
+        return innertube_text(message).unwrap_or_default();
+    };
+    runs.iter()
+        .filter_map(|run| {
+            run.get("text").and_then(|t| t.as_str()).or_else(|| {
+                run.pointer("/emoji/shortcuts/0")
+                    .or_else(|| run.pointer("/emoji/emojiId"))
+                    .and_then(|e| e.as_str())
+            })
+        })
+        .collect::<Vec<&str>>()
+        .join("")
+}
+
+fn parse_chat_page(page: &Value) -> ChatPage {
+    let mut replay_actions = Vec::new();
+    find_all(page, "replayChatItemAction", &mut replay_actions);
+
+    let mut messages = Vec::new();
+    for action in replay_actions {
+        let offset = action
+            .get("videoOffsetTimeMsec")
+            .and_then(|o| o.as_str())
+            .and_then(|o| o.parse::<u64>().ok())
+            .unwrap_or(0);
+
+        let mut items = Vec::new();
+        find_all(action, "liveChatTextMessageRenderer", &mut items);
+        find_all(action, "liveChatPaidMessageRenderer", &mut items);
+        for item in items {
+            let text = item.get("message").map(message_text).unwrap_or_default();
+            if text.trim().is_empty() {
+                continue;
+            }
+            messages.push(ChatMessage {
+                offset,
+                author: item
+                    .get("authorName")
+                    .and_then(innertube_text)
+                    .unwrap_or_default(),
+                text,
+            });
+        }
+    }
+
+    let next_token = page
+        .pointer("/continuationContents/liveChatContinuation/continuations")
+        .and_then(|c| c.as_array())
+        .and_then(|continuations| {
+            continuations
+                .iter()
+                .find_map(|c| c.pointer("/liveChatReplayContinuationData/continuation"))
+        })
+        .and_then(|c| c.as_str())
+        .map(str::to_string);
+
+    ChatPage {
+        messages,
+        next_token,
+    }
+}
+
+impl YoutubeAudio {
+    // download the chat replay of a recorded livestream with the video offset of every message
+    pub async fn get_live_chat_replay(
+        &self,
+        url: &str,
+        max_messages: usize,
+    ) -> Result<Vec<ChatMessage>, Box<dyn Error>> {
+        let video_id = extract_id(url).ok_or("failed to parse the video id")?;
+        let watch = self.post_next(Some(&video_id), None).await?;
+        let mut token = Some(chat_replay_token(&watch).ok_or("no live chat replay available")?);
+
+        let mut messages = Vec::new();
+        while let Some(continuation) = token.take() {
+            let body = ChatReplayRequestBody {
+                context: RequestContext {
+                    client: ClientInfo::default(String::new()),
+                },
+                continuation,
+                current_player_state: PlayerState {
+                    player_offset_ms: messages
+                        .last()
+                        .map_or(0, |m: &ChatMessage| m.offset)
+                        .to_string(),
+                },
+            };
+            let page = self
+                .post_innertube("live_chat/get_live_chat_replay", &body)
+                .await?;
+            let parsed = parse_chat_page(&page);
+            if parsed.messages.is_empty() && parsed.next_token.is_none() {
+                break;
+            }
+            messages.extend(parsed.messages);
+            if messages.len() >= max_messages {
+                break;
+            }
+            token = parsed.next_token;
+        }
+        messages.truncate(max_messages);
+        Ok(messages)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_chat_page_works() {
+        let page = serde_json::json!({
+            "continuationContents": {"liveChatContinuation": {
+                "continuations": [{"liveChatReplayContinuationData": {"continuation": "next-chat"}}],
+                "actions": [
+                    {"replayChatItemAction": {
+                        "videoOffsetTimeMsec": "65000",
+                        "actions": [{"addChatItemAction": {"item": {"liveChatTextMessageRenderer": {
+                            "authorName": {"simpleText": "viewer"},
+                            "message": {"runs": [
+                                {"text": "hello "},
+                                {"emoji": {"emojiId": "👋", "shortcuts": [":wave:"]}}
+                            ]}
+                        }}}}]
+                    }},
+                    {"replayChatItemAction": {
+                        "videoOffsetTimeMsec": "70000",
+                        "actions": [{"addChatItemAction": {"item": {"liveChatViewerEngagementMessageRenderer": {}}}}]
+                    }}
+                ]
+            }}
+        });
+
+        let parsed = parse_chat_page(&page);
+        assert_eq!(parsed.messages.len(), 1);
+        assert_eq!(parsed.messages[0].offset, 65000);
+        assert_eq!(parsed.messages[0].author, "viewer");
+        assert_eq!(parsed.messages[0].text, "hello :wave:");
+        assert_eq!(parsed.next_token.as_deref(), Some("next-chat"));
+    }
+
+    #[test]
+    fn chat_replay_token_works() {
+        let watch = serde_json::json!({
+            "contents": {"twoColumnWatchNextResults": {"conversationBar": {"liveChatRenderer": {
+                "continuations": [{"reloadContinuationData": {"continuation": "replay-token"}}]
+            }}}}
+        });
+        assert_eq!(chat_replay_token(&watch).as_deref(), Some("replay-token"));
+        assert!(chat_replay_token(&serde_json::json!({})).is_none());
+    }
+}
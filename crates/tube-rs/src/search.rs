@@ -0,0 +1,265 @@
+use super::{find_all, innertube_text, parse_count, ClientInfo, RequestContext, YoutubeAudio};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::error::Error;
+
+// a query keeps returning continuations, stop after this many pages
+const MAX_SEARCH_PAGES: usize = 10;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum DurationFilter {
+    // under 4 minutes
+    Short,
+    // 4 - 20 minutes
+    Medium,
+    // over 20 minutes
+    Long,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum UploadDateFilter {
+    LastHour,
+    Today,
+    ThisWeek,
+    ThisMonth,
+    ThisYear,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchFilter {
+    pub duration: Option<DurationFilter>,
+    pub upload_date: Option<UploadDateFilter>,
+    #[serde(default)]
+    pub captions_only: bool,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchResult {
+    pub video_id: String,
+    pub title: String,
+    pub channel: String,
+    // seconds, 0 for live streams
+    pub duration: u64,
+    pub published_time: String,
+    pub view_count: u64,
+    pub has_captions: bool,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct SearchRequestBody {
+    context: RequestContext,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    query: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    params: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    continuation: Option<String>,
+}
+
+fn base64_encode(bytes: &[u8]) -> String {
+    const TABLE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::new();
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let triple = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(TABLE[(triple >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+// the `params` field is the base64 of a small protobuf, the same blob as the `sp=` url parameter
+fn encode_search_params(filter: &SearchFilter) -> String {
+    // field 2 = type, always restrict to videos so every result can be imported
+    let mut filters = vec![0x10, 0x01];
+    if let Some(upload_date) = filter.upload_date {
+        let value = match upload_date {
+            UploadDateFilter::LastHour => 1,
+            UploadDateFilter::Today => 2,
+            UploadDateFilter::ThisWeek => 3,
+            UploadDateFilter::ThisMonth => 4,
+            UploadDateFilter::ThisYear => 5,
+        };
+        filters.extend([0x08, value]);
+    }
+    if let Some(duration) = filter.duration {
+        let value = match duration {
+            DurationFilter::Short => 1,
+            DurationFilter::Long => 2,
+            DurationFilter::Medium => 3,
+        };
+        filters.extend([0x18, value]);
+    }
+    if filter.captions_only {
+        filters.extend([0x28, 0x01]);
+    }
+
+    let mut message = vec![0x12, filters.len() as u8];
+    message.extend(filters);
+    base64_encode(&message)
+}
+
+// "1:02:03" or "12:34" to seconds
+fn parse_length(text: &str) -> u64 {
+    text.split(':')
+        .map(|part| part.trim().parse::<u64>().unwrap_or(0))
+        .fold(0, |total, part| total * 60 + part)
+}
+
+fn parse_video_renderer(renderer: &Value) -> Option<SearchResult> {
+    let text_of = |key: &str| renderer.get(key).and_then(innertube_text);
+    let mut badges = Vec::new();
+    find_all(renderer, "metadataBadgeRenderer", &mut badges);
+    let has_captions = badges.iter().any(|badge| {
+        matches!(
+            badge.get("label").and_then(|l| l.as_str()),
+            Some("CC") | Some("Subtitles")
+        )
+    });
+    Some(SearchResult {
+        video_id: renderer.get("videoId")?.as_str()?.to_string(),
+        title: text_of("title").unwrap_or_default(),
+        channel: text_of("ownerText")
+            .or_else(|| text_of("longBylineText"))
+            .unwrap_or_default(),
+        duration: text_of("lengthText").map(|l| parse_length(&l)).unwrap_or(0),
+        published_time: text_of("publishedTimeText").unwrap_or_default(),
+        view_count: text_of("viewCountText")
+            .map(|v| parse_count(v.split_whitespace().next().unwrap_or_default()))
+            .unwrap_or(0),
+        has_captions,
+    })
+}
+
+fn parse_search_page(page: &Value) -> (Vec<SearchResult>, Option<String>) {
+    let mut renderers = Vec::new();
+    find_all(page, "videoRenderer", &mut renderers);
+    let results = renderers
+        .into_iter()
+        .filter_map(parse_video_renderer)
+        .collect();
+
+    let mut continuations = Vec::new();
+    find_all(page, "continuationItemRenderer", &mut continuations);
+    let next_token = continuations.into_iter().find_map(|item| {
+        item.pointer("/continuationEndpoint/continuationCommand/token")
+            .and_then(|t| t.as_str())
+            .map(str::to_string)
+    });
+    (results, next_token)
+}
+
+impl YoutubeAudio {
+    // search youtube for videos matching `query`, ads and non-video results are skipped
+    pub async fn search(
+        &self,
+        query: &str,
+        filter: &SearchFilter,
+        max_results: usize,
+    ) -> Result<Vec<SearchResult>, Box<dyn Error>> {
+        let mut body = SearchRequestBody {
+            context: RequestContext {
+                client: ClientInfo::default(String::new()),
+            },
+            query: Some(query.to_string()),
+            params: Some(encode_search_params(filter)),
+            continuation: None,
+        };
+
+        // captions_only is sent in the params, the "CC" badge is missing on many captioned
+        // videos so the results are not filtered by it again
+        let mut results: Vec<SearchResult> = Vec::new();
+        for _ in 0..MAX_SEARCH_PAGES {
+            let page = self.post_innertube("search", &body).await?;
+            let (page_results, next_token) = parse_search_page(&page);
+            if page_results.is_empty() {
+                break;
+            }
+            for result in page_results {
+                if !results.iter().any(|r| r.video_id == result.video_id) {
+                    results.push(result);
+                }
+            }
+            match next_token {
+                Some(token) if results.len() < max_results => {
+                    body.query = None;
+                    body.params = None;
+                    body.continuation = Some(token);
+                }
+                _ => break,
+            }
+        }
+        results.truncate(max_results);
+        Ok(results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_search_params_works() {
+        assert_eq!(encode_search_params(&SearchFilter::default()), "EgIQAQ==");
+        let filter = SearchFilter {
+            duration: Some(DurationFilter::Long),
+            upload_date: Some(UploadDateFilter::ThisWeek),
+            captions_only: true,
+        };
+        // 0x12 0x08 | 0x10 0x01 | 0x08 0x03 | 0x18 0x02 | 0x28 0x01
+        assert_eq!(encode_search_params(&filter), "EggQAQgDGAIoAQ==");
+    }
+
+    #[test]
+    fn parse_length_works() {
+        assert_eq!(parse_length("12:34"), 754);
+        assert_eq!(parse_length("1:02:03"), 3723);
+        assert_eq!(parse_length(""), 0);
+    }
+
+    #[test]
+    fn parse_search_page_works() {
+        let page = serde_json::json!({
+            "contents": {"sectionListRenderer": {"contents": [
+                {"itemSectionRenderer": {"contents": [
+                    {"adSlotRenderer": {}},
+                    {"videoRenderer": {
+                        "videoId": "abc123",
+                        "title": {"runs": [{"text": "Intro to RLHF"}]},
+                        "ownerText": {"runs": [{"text": "Some Channel"}]},
+                        "lengthText": {"simpleText": "10:05"},
+                        "publishedTimeText": {"simpleText": "3 days ago"},
+                        "viewCountText": {"simpleText": "1,234 views"},
+                        "badges": [{"metadataBadgeRenderer": {"label": "CC"}}]
+                    }}
+                ]}},
+                {"continuationItemRenderer": {"continuationEndpoint": {
+                    "continuationCommand": {"token": "more"}
+                }}}
+            ]}}
+        });
+        let (results, token) = parse_search_page(&page);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].video_id, "abc123");
+        assert_eq!(results[0].title, "Intro to RLHF");
+        assert_eq!(results[0].channel, "Some Channel");
+        assert_eq!(results[0].duration, 605);
+        assert_eq!(results[0].view_count, 1234);
+        assert!(results[0].has_captions);
+        assert_eq!(token.as_deref(), Some("more"));
+    }
+}
@@ -0,0 +1,40 @@
+-- a database as the first release created it, schema version 0
+CREATE TABLE IF NOT EXISTS audio (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    video_id TEXT NOT NULL UNIQUE,
+    title TEXT NOT NULL,
+    duration INTEGER NOT NULL,
+    upload_date INTEGER NOT NULL,
+    keywords TEXT,
+    description TEXT,
+    caption_lang TEXT,
+    caption_url TEXT,
+    audio_url TEXT NOT NULL,
+    audio_filesize INTEGER NOT NULL,
+    mime_type TEXT NOT NULL,
+    thumbnail_url TEXT NOT NULL,
+    transcripts TEXT,
+    summary TEXT,
+    timestamp INTEGER DEFAULT (strftime('%s', 'now'))
+);
+
+INSERT INTO audio (
+    video_id, title, duration, upload_date, keywords, description, caption_lang, caption_url,
+    audio_url, audio_filesize, mime_type, thumbnail_url, transcripts, summary
+) VALUES (
+    'FdeioVndUhs', 'Rust ownership explained', 734, 1709596800000000, 'rust ownership',
+    '00:00 Intro
+01:05 Ownership', 'en', NULL, 'https://example.com/audio.m4a', 5242880,
+    'audio/mp4; codecs="mp4a.40.2"', 'https://i.ytimg.com/vi/FdeioVndUhs/hqdefault.jpg',
+    '[{"start":0.0,"end":12.5,"text":" Welcome back"},{"start":12.5,"end":20.0,"text":" today we borrow a value"}]',
+    '## Key Points
+- ownership moves values'
+);
+
+INSERT INTO audio (
+    video_id, title, duration, upload_date, keywords, description, audio_url, audio_filesize,
+    mime_type, thumbnail_url
+) VALUES (
+    'dQw4w9WgXcQ', 'Not transcribed yet', 212, 1256515200000000, '', '',
+    'https://example.com/other.webm', 3407872, 'audio/webm', ''
+);
@@ -0,0 +1,315 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tauri::Manager;
+use tokio_util::sync::CancellationToken;
+
+use super::setting::AppSettings;
+
+// each run works in a directory of its own under the cache, named by the id of the video,
+// so two videos processed at once never share a download or a chunk; the directory goes away
+// with a finished or cancelled run and is kept after a failure, so a retry skips the download
+
+const DEFAULT_LIMIT_MB: u64 = 2 * 1024;
+// how long the directory of a failed run waits for a retry
+const STALE_AFTER: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+#[derive(Debug, Clone)]
+pub struct CacheManager {
+    root: PathBuf,
+    // bytes the directories may take together
+    limit: u64,
+    // videos with a run working in their directory, never removed to make room
+    active: Arc<Mutex<HashSet<i64>>>,
+}
+
+// the working directory of one run, removed by `close` unless the run failed
+#[derive(Debug)]
+pub struct JobDir {
+    manager: CacheManager,
+    id: i64,
+    path: PathBuf,
+}
+
+impl JobDir {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // end the run; a failure keeps what was downloaded for the retry and makes room for it,
+    // the files are removed off the async threads
+    pub async fn close<T>(self, result: &Result<T, String>, cancel: &CancellationToken) {
+        let keep = result.is_err() && !cancel.is_cancelled();
+        let manager = self.manager.clone();
+        let _ = tauri::async_runtime::spawn_blocking(move || {
+            if !keep {
+                let _ = fs::remove_dir_all(&self.path);
+            }
+            drop(self);
+            if keep {
+                let _ = manager.clean(STALE_AFTER);
+            }
+        })
+        .await;
+    }
+}
+
+// a run that never got to `close` leaves its files to the next clean
+impl Drop for JobDir {
+    fn drop(&mut self) {
+        self.manager.release(self.id);
+    }
+}
+
+impl CacheManager {
+    pub fn new(root: PathBuf, settings: Option<&AppSettings>) -> CacheManager {
+        let limit_mb = settings
+            .and_then(|settings| settings.cache_limit_mb)
+            .unwrap_or(DEFAULT_LIMIT_MB);
+        CacheManager::with_limit(root, limit_mb * 1024 * 1024)
+    }
+
+    fn with_limit(root: PathBuf, limit: u64) -> CacheManager {
+        CacheManager {
+            root,
+            limit,
+            active: Arc::new(Mutex::new(HashSet::new())),
+        }
+    }
+
+    // claim the directory of a video for a run, refused while another run holds it
+    pub fn open(&self, id: i64) -> Result<JobDir, String> {
+        let mut active = self.active.lock().map_err(|e| e.to_string())?;
+        if !active.insert(id) {
+            return Err(format!("the files of video {id} are in use by another run"));
+        }
+        drop(active);
+        let path = self.root.join(id.to_string());
+        if let Err(error) = fs::create_dir_all(&path) {
+            self.release(id);
+            return Err(error.to_string());
+        }
+        Ok(JobDir {
+            manager: self.clone(),
+            id,
+            path,
+        })
+    }
+
+    fn release(&self, id: i64) {
+        if let Ok(mut active) = self.active.lock() {
+            active.remove(&id);
+        }
+    }
+
+    // remove what is not the directory of a video and the directories untouched for `max_age`,
+    // then the oldest ones until the cache is under its limit; returns the bytes freed
+    pub fn clean(&self, max_age: Duration) -> io::Result<u64> {
+        let entries = match fs::read_dir(&self.root) {
+            Ok(entries) => entries,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(0),
+            Err(error) => return Err(error),
+        };
+        let active = match self.active.lock() {
+            Ok(active) => active.clone(),
+            Err(_) => return Ok(0),
+        };
+        let now = SystemTime::now();
+        let mut freed = 0;
+        let mut total = 0;
+        let mut candidates = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            let id = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.parse::<i64>().ok());
+            let (size, modified) = usage(&path)?;
+            if id.is_some_and(|id| active.contains(&id)) {
+                total += size;
+                continue;
+            }
+            let stale = now.duration_since(modified).unwrap_or_default() > max_age;
+            if id.is_none() || stale {
+                remove(&path)?;
+                freed += size;
+            } else {
+                total += size;
+                candidates.push((modified, size, path));
+            }
+        }
+
+        candidates.sort_by_key(|(modified, _, _)| *modified);
+        for (_, size, path) in candidates {
+            if total <= self.limit {
+                break;
+            }
+            remove(&path)?;
+            total -= size;
+            freed += size;
+        }
+        Ok(freed)
+    }
+}
+
+// the size of everything under `path` and when a file in it was last written
+fn usage(path: &Path) -> io::Result<(u64, SystemTime)> {
+    let metadata = fs::symlink_metadata(path)?;
+    if !metadata.is_dir() {
+        return Ok((metadata.len(), metadata.modified()?));
+    }
+    let mut size = 0;
+    let mut newest = None;
+    for entry in fs::read_dir(path)? {
+        let (entry_size, modified) = usage(&entry?.path())?;
+        size += entry_size;
+        newest = newest.max(Some(modified));
+    }
+    // an empty directory is as old as itself
+    Ok((size, newest.map_or_else(|| metadata.modified(), Ok)?))
+}
+
+fn remove(path: &Path) -> io::Result<()> {
+    if fs::symlink_metadata(path)?.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+// where a download is written until it is complete, so a kept directory never holds half a file
+pub fn partial_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".part");
+    path.with_file_name(name)
+}
+
+// the working directory of video `id` for the current run
+pub fn job_dir(app: &tauri::AppHandle, id: i64) -> Result<JobDir, String> {
+    app.state::<CacheManager>().open(id)
+}
+
+// set up the cache at startup, dropping what earlier runs left behind
+pub fn init(
+    app: &tauri::AppHandle,
+    settings: Option<&AppSettings>,
+) -> Result<CacheManager, String> {
+    let cache_dir = app.path().cache_dir().map_err(|e| e.to_string())?;
+    let newscenter = cache_dir.join("newscenter");
+    // the shared files of older versions, every run wrote over them
+    let _ = fs::remove_dir_all(cache_dir.join("chunk"));
+    if let Ok(entries) = fs::read_dir(&newscenter) {
+        for path in entries.flatten().map(|entry| entry.path()) {
+            let name = path.file_name().and_then(|name| name.to_str());
+            if path.is_file() && name.is_some_and(|name| name.starts_with("temp")) {
+                let _ = fs::remove_file(&path);
+            }
+            // an anki export cut short by a crash
+            if path.is_dir() && name.is_some_and(|name| name.starts_with("anki-")) {
+                let _ = fs::remove_dir_all(&path);
+            }
+        }
+    }
+
+    let manager = CacheManager::new(newscenter.join("jobs"), settings);
+    manager.clean(STALE_AFTER).map_err(|e| e.to_string())?;
+    Ok(manager)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use tauri::async_runtime::block_on;
+
+    fn temp_root(name: &str) -> PathBuf {
+        let root =
+            std::env::temp_dir().join(format!("videomaestro-cache-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        root
+    }
+
+    fn write(path: &Path, size: usize, age: Duration) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, vec![0u8; size]).unwrap();
+        File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(SystemTime::now() - age)
+            .unwrap();
+    }
+
+    #[test]
+    fn runs_get_their_own_directory() {
+        let root = temp_root("open");
+        let manager = CacheManager::with_limit(root.clone(), 1024);
+        let cancel = CancellationToken::new();
+
+        let first = manager.open(1).unwrap();
+        let second = manager.open(2).unwrap();
+        assert_ne!(first.path(), second.path());
+        assert!(manager.open(1).is_err());
+
+        write(&first.path().join("audio.webm"), 10, Duration::ZERO);
+        let path = first.path().to_path_buf();
+        block_on(first.close(&Ok(()), &cancel));
+        assert!(!path.exists());
+
+        // a failed run leaves its download for the retry
+        write(&second.path().join("audio.webm"), 10, Duration::ZERO);
+        let path = second.path().to_path_buf();
+        block_on(second.close(&Err::<(), _>("quota".to_string()), &cancel));
+        assert!(path.join("audio.webm").exists());
+        let again = manager.open(2).unwrap();
+        cancel.cancel();
+        block_on(again.close(&Err::<(), _>("cancelled".to_string()), &cancel));
+        assert!(!path.exists());
+
+        assert_eq!(
+            partial_path(&path.join("audio.webm")),
+            path.join("audio.webm.part")
+        );
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn clean_drops_stale_then_oldest() {
+        let root = temp_root("clean");
+        let day = Duration::from_secs(24 * 60 * 60);
+        write(&root.join("1").join("audio.webm"), 100, day * 30);
+        write(
+            &root.join("2").join("chunks").join("chunk_0.webm"),
+            100,
+            day * 2,
+        );
+        write(&root.join("3").join("audio.webm"), 100, day);
+        write(&root.join("4").join("audio.webm"), 100, day * 3);
+        write(&root.join("temp.webm"), 50, Duration::ZERO);
+
+        let manager = CacheManager::with_limit(root.clone(), 200);
+        // a run is going on for video 4, its files stay whatever their age
+        let running = manager.open(4).unwrap();
+        assert_eq!(manager.clean(day * 7).unwrap(), 250);
+        assert!(!root.join("1").exists());
+        assert!(!root.join("2").exists());
+        assert!(root.join("3").join("audio.webm").exists());
+        assert!(root.join("4").join("audio.webm").exists());
+        assert!(!root.join("temp.webm").exists());
+
+        block_on(running.close(&Ok(()), &CancellationToken::new()));
+        assert!(!root.join("4").exists());
+        assert_eq!(manager.clean(day * 7).unwrap(), 0);
+
+        // a run dropped without closing gives its directory up, the files wait for a clean
+        let dropped = manager.open(3).unwrap();
+        drop(dropped);
+        assert!(root.join("3").join("audio.webm").exists());
+        assert!(manager.open(3).is_ok());
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,95 @@
+use tauri::{Manager, State};
+use tube_rs::{Comment, YoutubeAudio};
+
+use super::db::{self, DataBase};
+use super::events::{self, Phase, Sender};
+use super::setting;
+use super::whisper;
+
+fn get_comment_prompt(language: &str) -> String {
+    format!(
+        r#"
+        You are an assistant specializing in analyzing how an audience reacts to a video. Below are viewer comments (in the format `[Likes] Author: Text`, replies are indented under the comment they answer). Summarize what the viewers say, not what the video says.
+        Guiding principles:
+        - Weight comments by their likes, highly liked comments represent the wider audience.
+        - Group similar opinions together instead of listing comments one by one.
+        - Point out disagreements, corrections of the video and recurring questions.
+        - Use emojis where appropriate.
+        The final output should be formatted as follows:
+        ## Overall Sentiment:
+        One short paragraph describing the general mood of the audience.
+        ## Main Themes:
+        Number. option<emoji> Theme - What viewers say about it
+        ...
+        ## Questions & Corrections:
+        - Question or correction raised by viewers
+        ...
+        Write the whole answer in {}.
+        Here are the comments:
+        "#,
+        whisper::language_name(language)
+    )
+}
+
+fn transform_comments_to_string(comments: &[Comment]) -> String {
+    let mut content = String::new();
+    for comment in comments {
+        let indent = if comment.parent_id.is_some() {
+            "    "
+        } else {
+            ""
+        };
+        content.push_str(&format!(
+            "{indent}[{}] {}: {}\n",
+            comment.likes,
+            comment.author,
+            comment.text.replace('\n', " ")
+        ));
+    }
+    content
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn run_comment_summary(
+    app: tauri::AppHandle,
+    db: State<'_, DataBase>,
+    video_id: i64, // id in database
+    language: String,
+    max_comments: usize,
+    with_replies: bool,
+) -> Result<String, String> {
+    let youtube_id = db::get_youtube_id_with_id(db, video_id)?;
+    let youtube_audio = YoutubeAudio::new(
+        setting::get_proxy(&app).as_deref(),
+        setting::get_tube_api_url(&app),
+    );
+    let comments = youtube_audio
+        .get_comments(
+            &format!("https://www.youtube.com/watch?v={youtube_id}"),
+            max_comments,
+            with_replies,
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+    if comments.is_empty() {
+        return Err("no comments found for this video".to_string());
+    }
+    db::save_comments(app.state(), video_id, &comments)?;
+
+    let sender = Sender::new(
+        &app,
+        events::COMMENT_SUMMARY,
+        Some(video_id),
+        Phase::Summarizing,
+    );
+    let cancel = sender.cancel_token();
+    sender
+        .run(whisper::chat_completion_stream(
+            &app,
+            &sender,
+            get_comment_prompt(&language),
+            transform_comments_to_string(&comments),
+            &cancel,
+        ))
+        .await
+}
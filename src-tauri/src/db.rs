@@ -1,13 +1,24 @@
-use crate::{utils, whisper::Segment};
-use rusqlite::{params, Connection, Result};
+use crate::flashcards::Flashcard;
+use crate::fulltext::{self, SearchHit};
+use crate::jobs::{self, Job, JobKind, JobStatus};
+use crate::library::{self, Collection, Tag};
+use crate::listing::{self, VideoPage, VideoQuery};
+use crate::migrations;
+use crate::status::{self, Stage, StatusEntry};
+use crate::utils;
+use crate::whisper::{Segment, TokenUsage};
+use rusqlite::{params, Connection, OptionalExtension, Result};
 use serde::{Deserialize, Serialize};
 use std::sync::Mutex;
 use tauri::{AppHandle, Manager, State};
 use thiserror::Error;
-use tube_rs::AudioData;
+use tube_rs::{AudioData, ChatMessage, Comment, HeatMarker};
 
 pub struct DataBase(Mutex<Connection>);
 
+// the video_id of a row added from a url before its metadata is fetched
+pub const PENDING_PREFIX: &str = "pending:";
+
 #[derive(Error, Debug)]
 pub enum DataBaseError {
     #[error("Faile to get app dir: {0}")]
@@ -20,50 +31,17 @@ pub enum DataBaseError {
     CreateFile(#[from] std::io::Error),
 }
 
-//id, video_id, title, duration, upload_date, transcripts, summary, keywords, timestamp, thumbnail_url
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Audio {
-    id: i64,
-    video_id: String,
-    title: String,
-    duration: u64,
-    upload_date: u64,
-    transcripts: Option<String>,
-    summary: Option<String>,
-    keywords: String,
-    timestamp: i64,
-    thumbnail_url: String,
-}
-
 pub fn init_db(app_handle: &AppHandle) -> Result<DataBase, DataBaseError> {
     let app_dir = app_handle.path().app_data_dir()?;
     std::fs::create_dir_all(&app_dir)?;
 
     let db_path = app_dir.join("test.db");
-    let connection = Connection::open(db_path)?;
-
-    connection.execute(
-        "CREATE TABLE IF NOT EXISTS audio (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            video_id TEXT NOT NULL UNIQUE,
-            title TEXT NOT NULL,
-            duration INTEGER NOT NULL,
-            upload_date INTEGER NOT NULL,
-            keywords TEXT,
-            description TEXT,
-            caption_lang TEXT,
-            caption_url TEXT,
-            audio_url TEXT NOT NULL,
-            audio_filesize INTEGER NOT NULL,
-            mime_type TEXT NOT NULL,
-            thumbnail_url TEXT NOT NULL,
-            transcripts TEXT,
-            summary TEXT,
-            timestamp INTEGER DEFAULT (strftime('%s', 'now'))
-        )",
-        [],
-    )?;
+    let mut connection = Connection::open(&db_path)?;
+    // sqlite leaves them off unless asked, deleting a video relies on their cascades
+    connection.pragma_update(None, "foreign_keys", true)?;
+    migrations::migrate(&mut connection, &db_path)?;
+    status::fail_interrupted(&connection)?;
+    jobs::resume(&connection)?;
 
     Ok(DataBase(Mutex::new(connection)))
 }
@@ -77,8 +55,9 @@ pub fn create_video(db: State<DataBase>, audio_data: AudioData) -> Result<i64, S
     db.execute(
         "INSERT INTO audio (
             video_id, title, duration, upload_date, description,
-            caption_lang, caption_url, audio_url, audio_filesize, thumbnail_url, keywords, mime_type
-        ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            caption_lang, caption_url, audio_url, audio_filesize, thumbnail_url, keywords, mime_type,
+            channel
+        ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
         params![
             audio_data.video_id,
             audio_data.title,
@@ -92,58 +71,295 @@ pub fn create_video(db: State<DataBase>, audio_data: AudioData) -> Result<i64, S
             audio_data.thumbnail_url,
             keywords,
             audio_data.mime_type,
+            audio_data.channel,
         ],
     )
     .map_err(|e| e.to_string())?;
     let db_id = db.last_insert_rowid();
+    status::record(&db, db_id, Stage::Queued, None).map_err(|e| e.to_string())?;
     Ok(db_id)
 }
 
-fn transform_transripts_str(
-    description: Option<String>,
-    transcripts: Option<String>,
-) -> Option<String> {
-    let description = description.unwrap_or("".to_string());
-    match transcripts {
-        Some(data) => {
-            let segments: Vec<Segment> = serde_json::from_str(&data).unwrap();
-            let chunks = utils::transform_segments_to_chunks(&description, segments);
-            Some(chunks.join("\n\n"))
-        }
-        None => None,
+// a row for a url whose metadata is not fetched yet, named by the url until `fill_video`
+pub fn create_pending_video(db: State<DataBase>, url: &str) -> Result<i64, String> {
+    let db = db.0.lock().map_err(|e| e.to_string())?;
+    db.execute(
+        "INSERT INTO audio (
+            video_id, title, duration, upload_date, audio_url, audio_filesize,
+            mime_type, thumbnail_url
+        ) VALUES (?1, ?2, 0, 0, ?2, 0, '', '')",
+        params![format!("{PENDING_PREFIX}{url}"), url],
+    )
+    .map_err(|e| e.to_string())?;
+    let db_id = db.last_insert_rowid();
+    status::record(&db, db_id, Stage::Queued, None).map_err(|e| e.to_string())?;
+    Ok(db_id)
+}
+
+// store the fetched metadata of a pending row; false when the video is in the library already
+pub fn fill_video(db: State<DataBase>, id: i64, audio_data: AudioData) -> Result<bool, String> {
+    let db = db.0.lock().map_err(|e| e.to_string())?;
+    let exists = db
+        .query_row(
+            "SELECT 1 FROM audio WHERE video_id = ?1 AND id != ?2",
+            params![audio_data.video_id, id],
+            |_| Ok(()),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+        .is_some();
+    if exists {
+        return Ok(false);
+    }
+    let keywords = match audio_data.keywords {
+        Some(array) => array.join(" "),
+        None => "".to_string(),
+    };
+    db.execute(
+        "UPDATE audio SET
+            video_id = ?2, title = ?3, duration = ?4, upload_date = ?5, description = ?6,
+            caption_lang = ?7, caption_url = ?8, audio_url = ?9, audio_filesize = ?10,
+            thumbnail_url = ?11, keywords = ?12, mime_type = ?13, channel = ?14
+        WHERE id = ?1",
+        params![
+            id,
+            audio_data.video_id,
+            audio_data.title,
+            audio_data.duration,
+            audio_data.timestamp,
+            audio_data.description,
+            audio_data.caption_lang,
+            audio_data.caption_url,
+            audio_data.audio_url,
+            audio_data.audio_filesize,
+            audio_data.thumbnail_url,
+            keywords,
+            audio_data.mime_type,
+            audio_data.channel,
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(true)
+}
+
+// move a video to another stage of the run going on
+pub fn set_status(
+    db: State<DataBase>,
+    id: i64,
+    stage: Stage,
+    error: Option<&str>,
+) -> Result<(), String> {
+    let mut db = db.0.lock().map_err(|e| e.to_string())?;
+    let tx = db.transaction().map_err(|e| e.to_string())?;
+    if !status::record(&tx, id, stage, error).map_err(|e| e.to_string())? {
+        return Err(format!("cannot move this video to {}", stage.as_str()));
     }
+    tx.commit().map_err(|e| e.to_string())
 }
 
+// start a run on a video, refused while another one is going on; returns the stage it was at
+pub fn begin_status(db: State<DataBase>, id: i64, stage: Stage) -> Result<Stage, String> {
+    let mut db = db.0.lock().map_err(|e| e.to_string())?;
+    let tx = db.transaction().map_err(|e| e.to_string())?;
+    let previous = status::current(&tx, id)
+        .map_err(|e| e.to_string())?
+        .unwrap_or(Stage::Queued);
+    if let Some(running) = status::begin(&tx, id, stage).map_err(|e| e.to_string())? {
+        return Err(format!(
+            "this video is already {}",
+            running.as_str().replace('_', " ")
+        ));
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(previous)
+}
+
+// the stage of the latest run on a video, None before its first one
+pub fn get_status(db: State<DataBase>, id: i64) -> Result<Option<Stage>, String> {
+    let db = db.0.lock().map_err(|e| e.to_string())?;
+    status::current(&db, id).map_err(|e| e.to_string())
+}
+
+// the stage a failed video stopped at, None when it did not fail
+pub fn get_failed_stage(db: State<DataBase>, id: i64) -> Result<Option<Stage>, String> {
+    let db = db.0.lock().map_err(|e| e.to_string())?;
+    status::failed_stage(&db, id).map_err(|e| e.to_string())
+}
+
+pub fn get_failed_jobs(db: State<DataBase>, video_id: i64) -> Result<Vec<JobKind>, String> {
+    let db = db.0.lock().map_err(|e| e.to_string())?;
+    jobs::failed_kinds(&db, video_id).map_err(|e| e.to_string())
+}
+
+pub fn enqueue_job(db: State<DataBase>, kind: &JobKind) -> Result<i64, String> {
+    let db = db.0.lock().map_err(|e| e.to_string())?;
+    jobs::enqueue(&db, kind, jobs::now()).map_err(|e| e.to_string())
+}
+
+pub fn claim_jobs(db: State<DataBase>) -> Result<Vec<Job>, String> {
+    let db = db.0.lock().map_err(|e| e.to_string())?;
+    jobs::claim_due(&db, jobs::now()).map_err(|e| e.to_string())
+}
+
+pub fn next_job_due(db: State<DataBase>) -> Result<Option<i64>, String> {
+    let db = db.0.lock().map_err(|e| e.to_string())?;
+    jobs::next_due(&db).map_err(|e| e.to_string())
+}
+
+pub fn set_job_kind(db: State<DataBase>, id: i64, kind: &JobKind) -> Result<(), String> {
+    let db = db.0.lock().map_err(|e| e.to_string())?;
+    jobs::set_kind(&db, id, kind).map_err(|e| e.to_string())
+}
+
+pub fn finish_job(db: State<DataBase>, id: i64, status: JobStatus) -> Result<(), String> {
+    let db = db.0.lock().map_err(|e| e.to_string())?;
+    jobs::finish(&db, id, status).map_err(|e| e.to_string())
+}
+
+pub fn cancel_pending_job(db: State<DataBase>, id: i64) -> Result<bool, String> {
+    let db = db.0.lock().map_err(|e| e.to_string())?;
+    jobs::cancel_pending(&db, id).map_err(|e| e.to_string())
+}
+
+// returns pending when another attempt is scheduled
+pub fn fail_job(
+    db: State<DataBase>,
+    id: i64,
+    error: &str,
+    retryable: bool,
+) -> Result<JobStatus, String> {
+    let db = db.0.lock().map_err(|e| e.to_string())?;
+    jobs::fail(&db, id, error, retryable, jobs::now()).map_err(|e| e.to_string())
+}
+
+pub fn get_jobs(db: State<DataBase>) -> Result<Vec<Job>, String> {
+    let db = db.0.lock().map_err(|e| e.to_string())?;
+    jobs::list(&db).map_err(|e| e.to_string())
+}
+
+// every stage a video went through, oldest first
+#[tauri::command(rename_all = "snake_case")]
+pub fn get_status_history(db: State<DataBase>, video_id: i64) -> Result<Vec<StatusEntry>, String> {
+    let db = db.0.lock().map_err(|e| e.to_string())?;
+    status::history(&db, video_id).map_err(|e| e.to_string())
+}
+
+// one page of the library for the video list, see `VideoQuery` for sorting and filtering
 #[tauri::command]
-pub fn get_videos(db: State<DataBase>) -> Result<Vec<Audio>, String> {
+pub fn get_videos(db: State<DataBase>, query: Option<VideoQuery>) -> Result<VideoPage, String> {
     let db = db.0.lock().map_err(|e| e.to_string())?;
+    listing::list_videos(&db, &query.unwrap_or_default()).map_err(|e| e.to_string())
+}
 
-    let mut stmt = db
-        .prepare("SELECT id, video_id, title, duration, upload_date, transcripts, summary, keywords, timestamp, thumbnail_url, description from audio ORDER BY id DESC")
-        .map_err(|e| e.to_string())?;
+// ranked matches in titles, keywords, descriptions, summaries and transcripts
+#[tauri::command(rename_all = "snake_case")]
+pub fn search_videos(
+    db: State<DataBase>,
+    query: String,
+    limit: Option<usize>,
+) -> Result<Vec<SearchHit>, String> {
+    let db = db.0.lock().map_err(|e| e.to_string())?;
+    fulltext::search(&db, &query, limit.unwrap_or(50)).map_err(|e| e.to_string())
+}
+
+// everything stored for one video, used by the exports
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VideoRecord {
+    pub id: i64,
+    pub video_id: String,
+    pub title: String,
+    pub channel: Option<String>,
+    pub duration: u64,
+    pub upload_date: u64,
+    pub keywords: Option<String>,
+    pub description: Option<String>,
+    pub audio_url: String,
+    pub thumbnail_url: String,
+    pub segments: Vec<Segment>,
+    pub notes: Vec<Note>,
+    pub summary: Option<String>,
+    pub timestamp: i64,
+}
+
+impl VideoRecord {
+    // youtube ids are stored bare, every other source carries a `name:` prefix
+    pub fn is_youtube(&self) -> bool {
+        !self.video_id.contains(':')
+    }
 
-    let video_iter = stmt
-        .query_map([], |row| {
-            Ok(Audio {
+    pub fn url(&self) -> String {
+        if self.is_youtube() {
+            format!("https://www.youtube.com/watch?v={}", self.video_id)
+        } else {
+            self.audio_url.clone()
+        }
+    }
+
+    // a link that opens the video at `seconds`, only youtube can seek by url
+    pub fn time_url(&self, seconds: u64) -> Option<String> {
+        self.is_youtube()
+            .then(|| format!("{}&t={seconds}", self.url()))
+    }
+
+    // what was said between `start` and `end`, for showing a note with its context
+    pub fn excerpt(&self, start: f64, end: f64) -> String {
+        self.segments
+            .iter()
+            .filter(|segment| segment.end > start && segment.start < end)
+            .map(|segment| segment.text.trim())
+            .filter(|text| !text.is_empty())
+            .collect::<Vec<&str>>()
+            .join(" ")
+    }
+}
+
+pub fn get_video_with_id(db: State<DataBase>, id: i64) -> Result<VideoRecord, String> {
+    let db = db.0.lock().map_err(|e| e.to_string())?;
+    let segments = read_segments(&db, id, None, None).map_err(|e| e.to_string())?;
+    let notes = read_notes(&db, id).map_err(|e| e.to_string())?;
+    db.query_row(
+        "SELECT id, video_id, title, channel, duration, upload_date, keywords, description,
+            audio_url, thumbnail_url, summary, timestamp
+        FROM audio WHERE id=?1",
+        params![id],
+        |row| {
+            Ok(VideoRecord {
                 id: row.get(0)?,
                 video_id: row.get(1)?,
                 title: row.get(2)?,
-                duration: row.get(3)?,
-                upload_date: row.get(4)?,
-                transcripts: transform_transripts_str(row.get(10).ok(), row.get(5).ok()),
-                summary: row.get(6).ok(),
-                keywords: row.get(7)?,
-                timestamp: row.get(8)?,
+                channel: row.get(3)?,
+                duration: row.get(4)?,
+                upload_date: row.get(5)?,
+                keywords: row.get(6)?,
+                description: row.get(7)?,
+                audio_url: row.get(8)?,
                 thumbnail_url: row.get(9)?,
+                segments,
+                notes,
+                summary: row.get(10)?,
+                timestamp: row.get(11)?,
             })
-        })
-        .map_err(|e| e.to_string())?;
+        },
+    )
+    .map_err(|e| e.to_string())
+}
 
-    let mut videos = Vec::new();
-    for video in video_iter {
-        videos.push(video.map_err(|e| e.to_string())?)
-    }
-    Ok(videos)
+// everything the detail view shows, `transcripts` is the text split at the chapters
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VideoDetail {
+    #[serde(flatten)]
+    pub video: VideoRecord,
+    pub transcripts: Option<String>,
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub fn get_video_detail(db: State<DataBase>, video_id: i64) -> Result<VideoDetail, String> {
+    let video = get_video_with_id(db, video_id)?;
+    let transcripts = (!video.segments.is_empty()).then(|| {
+        let description = video.description.as_deref().unwrap_or_default();
+        utils::transform_segments_to_chunks(description, video.segments.clone()).join("\n\n")
+    });
+    Ok(VideoDetail { video, transcripts })
 }
 
 pub fn get_caption_with_id(
@@ -172,35 +388,722 @@ pub fn get_audio_url_with_id(
     .map_err(|e| e.to_string())
 }
 
-pub fn get_subtitle_with_id(db: State<DataBase>, id: i64) -> Result<(String, String), String> {
+// (transcripts, description) of a video, fails when it has not been transcribed yet
+pub fn get_subtitle_with_id(
+    db: State<DataBase>,
+    id: i64,
+) -> Result<(Vec<Segment>, String), String> {
+    let db = db.0.lock().map_err(|e| e.to_string())?;
+    let description: Option<String> = db
+        .query_row(
+            "Select description from audio Where id=?1",
+            params![id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    let segments = read_segments(&db, id, None, None).map_err(|e| e.to_string())?;
+    if segments.is_empty() {
+        return Err("no transcripts for this video".to_string());
+    }
+    Ok((segments, description.unwrap_or_default()))
+}
+
+// the transcripts of a video replace the ones stored before; `source` records where they came
+// from: captions, whisper or subtitle_file
+pub fn save_segments(
+    db: State<DataBase>,
+    id: i64,
+    segments: &[Segment],
+    source: &str,
+) -> Result<(), String> {
+    let mut db = db.0.lock().map_err(|e| e.to_string())?;
+    let tx = db.transaction().map_err(|e| e.to_string())?;
+    tx.execute("DELETE FROM segments WHERE audio_id=?1", params![id])
+        .map_err(|e| e.to_string())?;
+    {
+        let mut stmt = tx
+            .prepare(
+                "INSERT INTO segments (
+                    audio_id, segment_index, start_time, end_time, text, speaker, source
+                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            )
+            .map_err(|e| e.to_string())?;
+        for (index, segment) in segments.iter().enumerate() {
+            stmt.execute(params![
+                id,
+                index,
+                segment.start,
+                segment.end,
+                segment.text,
+                segment.speaker,
+                source
+            ])
+            .map_err(|e| e.to_string())?;
+        }
+    }
+    tx.commit().map_err(|e| e.to_string())
+}
+
+// segments overlapping `start..end` in seconds, either bound may be left open
+fn read_segments(
+    db: &Connection,
+    id: i64,
+    start: Option<f64>,
+    end: Option<f64>,
+) -> Result<Vec<Segment>> {
+    let mut stmt = db.prepare(
+        "SELECT start_time, end_time, text, speaker FROM segments
+        WHERE audio_id=?1 AND (?2 IS NULL OR end_time > ?2) AND (?3 IS NULL OR start_time < ?3)
+        ORDER BY segment_index",
+    )?;
+    let segment_iter = stmt.query_map(params![id, start, end], |row| {
+        Ok(Segment {
+            start: row.get(0)?,
+            end: row.get(1)?,
+            text: row.get(2)?,
+            speaker: row.get(3)?,
+        })
+    })?;
+    segment_iter.collect()
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub fn get_segments(
+    db: State<DataBase>,
+    video_id: i64,
+    start: Option<f64>,
+    end: Option<f64>,
+) -> Result<Vec<Segment>, String> {
+    let db = db.0.lock().map_err(|e| e.to_string())?;
+    read_segments(&db, video_id, start, end).map_err(|e| e.to_string())
+}
+
+pub fn find_id_with_video_id(db: State<DataBase>, video_id: &str) -> Result<Option<i64>, String> {
+    let db = db.0.lock().map_err(|e| e.to_string())?;
+    db.query_row(
+        "Select id from audio Where video_id=?1",
+        params![video_id],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(|e| e.to_string())
+}
+
+// (title, has transcripts, summary) of a video
+pub fn get_progress_with_id(
+    db: State<DataBase>,
+    id: i64,
+) -> Result<(String, bool, Option<String>), String> {
     let db = db.0.lock().map_err(|e| e.to_string())?;
     db.query_row(
-        "Select transcripts, description from audio Where id=?1",
+        "Select title, EXISTS (SELECT 1 FROM segments WHERE audio_id = audio.id), summary
+        from audio Where id=?1",
         params![id],
-        |row| Ok((row.get(0)?, row.get(1)?)),
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
     )
     .map_err(|e| e.to_string())
 }
 
-pub fn update_video(
+// the bare id of a youtube video, what only youtube has is refused for other sources
+pub fn get_youtube_id_with_id(db: State<DataBase>, id: i64) -> Result<String, String> {
+    let video = get_video_with_id(db, id)?;
+    if !video.is_youtube() {
+        return Err("this is only available for YouTube videos".to_string());
+    }
+    Ok(video.video_id)
+}
+
+pub fn get_video_id_with_id(db: State<DataBase>, id: i64) -> Result<String, String> {
+    let db = db.0.lock().map_err(|e| e.to_string())?;
+    db.query_row(
+        "Select video_id from audio Where id=?1",
+        params![id],
+        |row| row.get(0),
+    )
+    .map_err(|e| e.to_string())
+}
+
+// replace the stored comments of a video with a freshly fetched set
+pub fn save_comments(db: State<DataBase>, id: i64, comments: &[Comment]) -> Result<(), String> {
+    let mut db = db.0.lock().map_err(|e| e.to_string())?;
+    let tx = db.transaction().map_err(|e| e.to_string())?;
+    tx.execute("DELETE FROM comments WHERE audio_id=?1", params![id])
+        .map_err(|e| e.to_string())?;
+    for comment in comments {
+        tx.execute(
+            "INSERT OR IGNORE INTO comments (
+                audio_id, comment_id, parent_id, author, text, likes, reply_count, published_time
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                id,
+                comment.comment_id,
+                comment.parent_id,
+                comment.author,
+                comment.text,
+                comment.likes,
+                comment.reply_count,
+                comment.published_time,
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    tx.commit().map_err(|e| e.to_string())
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub fn get_comments(db: State<DataBase>, video_id: i64) -> Result<Vec<Comment>, String> {
+    let db = db.0.lock().map_err(|e| e.to_string())?;
+    let mut stmt = db
+        .prepare("SELECT comment_id, parent_id, author, text, likes, reply_count, published_time FROM comments WHERE audio_id=?1 ORDER BY id")
+        .map_err(|e| e.to_string())?;
+
+    let comment_iter = stmt
+        .query_map(params![video_id], |row| {
+            Ok(Comment {
+                comment_id: row.get(0)?,
+                parent_id: row.get(1)?,
+                author: row.get(2)?,
+                text: row.get(3)?,
+                likes: row.get(4)?,
+                reply_count: row.get(5)?,
+                published_time: row.get(6)?,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+
+    let mut comments = Vec::new();
+    for comment in comment_iter {
+        comments.push(comment.map_err(|e| e.to_string())?)
+    }
+    Ok(comments)
+}
+
+// replace the stored chat replay of a video, messages keep their video offset in ms
+pub fn save_live_chat(
+    db: State<DataBase>,
+    id: i64,
+    messages: &[ChatMessage],
+) -> Result<(), String> {
+    let mut db = db.0.lock().map_err(|e| e.to_string())?;
+    let tx = db.transaction().map_err(|e| e.to_string())?;
+    tx.execute("DELETE FROM live_chat WHERE audio_id=?1", params![id])
+        .map_err(|e| e.to_string())?;
+    for message in messages {
+        tx.execute(
+            "INSERT INTO live_chat (audio_id, offset_ms, author, text) VALUES (?1, ?2, ?3, ?4)",
+            params![id, message.offset, message.author, message.text],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    tx.commit().map_err(|e| e.to_string())
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub fn get_live_chat(db: State<DataBase>, video_id: i64) -> Result<Vec<ChatMessage>, String> {
+    let db = db.0.lock().map_err(|e| e.to_string())?;
+    let mut stmt = db
+        .prepare("SELECT offset_ms, author, text FROM live_chat WHERE audio_id=?1 ORDER BY offset_ms, id")
+        .map_err(|e| e.to_string())?;
+
+    let message_iter = stmt
+        .query_map(params![video_id], |row| {
+            Ok(ChatMessage {
+                offset: row.get(0)?,
+                author: row.get(1)?,
+                text: row.get(2)?,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+
+    let mut messages = Vec::new();
+    for message in message_iter {
+        messages.push(message.map_err(|e| e.to_string())?)
+    }
+    Ok(messages)
+}
+
+pub fn save_heatmap(db: State<DataBase>, id: i64, markers: &[HeatMarker]) -> Result<(), String> {
+    let mut db = db.0.lock().map_err(|e| e.to_string())?;
+    let tx = db.transaction().map_err(|e| e.to_string())?;
+    tx.execute("DELETE FROM heatmap WHERE audio_id=?1", params![id])
+        .map_err(|e| e.to_string())?;
+    for marker in markers {
+        tx.execute(
+            "INSERT INTO heatmap (audio_id, start_ms, duration_ms, intensity) VALUES (?1, ?2, ?3, ?4)",
+            params![id, marker.start, marker.duration, marker.intensity],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    tx.commit().map_err(|e| e.to_string())
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub fn get_heatmap(db: State<DataBase>, video_id: i64) -> Result<Vec<HeatMarker>, String> {
+    let db = db.0.lock().map_err(|e| e.to_string())?;
+    let mut stmt = db
+        .prepare("SELECT start_ms, duration_ms, intensity FROM heatmap WHERE audio_id=?1 ORDER BY start_ms")
+        .map_err(|e| e.to_string())?;
+
+    let marker_iter = stmt
+        .query_map(params![video_id], |row| {
+            Ok(HeatMarker {
+                start: row.get(0)?,
+                duration: row.get(1)?,
+                intensity: row.get(2)?,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+
+    let mut markers = Vec::new();
+    for marker in marker_iter {
+        markers.push(marker.map_err(|e| e.to_string())?)
+    }
+    Ok(markers)
+}
+
+// translated segments keep the index and timing of the transcripts they were made from
+pub fn save_translation(
     db: State<DataBase>,
     id: i64,
-    column: String,
-    value: String,
+    language: &str,
+    segments: &[Segment],
 ) -> Result<(), String> {
     let db = db.0.lock().map_err(|e| e.to_string())?;
+    let segments = serde_json::to_string(segments).map_err(|e| e.to_string())?;
+    db.execute(
+        "INSERT INTO translations (audio_id, language, segments) VALUES (?1, ?2, ?3)
+        ON CONFLICT(audio_id, language) DO UPDATE SET
+            segments = excluded.segments, timestamp = strftime('%s', 'now')",
+        params![id, language, segments],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
 
+#[tauri::command(rename_all = "snake_case")]
+pub fn get_translation(
+    db: State<DataBase>,
+    video_id: i64,
+    language: String,
+) -> Result<Option<Vec<Segment>>, String> {
+    let db = db.0.lock().map_err(|e| e.to_string())?;
+    let segments: Option<String> = db
+        .query_row(
+            "SELECT segments FROM translations WHERE audio_id=?1 AND language=?2",
+            params![video_id, language],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    match segments {
+        Some(data) => serde_json::from_str(&data).map_err(|e| e.to_string()),
+        None => Ok(None),
+    }
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub fn get_translation_languages(
+    db: State<DataBase>,
+    video_id: i64,
+) -> Result<Vec<String>, String> {
+    let db = db.0.lock().map_err(|e| e.to_string())?;
+    let mut stmt = db
+        .prepare("SELECT language FROM translations WHERE audio_id=?1 ORDER BY language")
+        .map_err(|e| e.to_string())?;
+    let language_iter = stmt
+        .query_map(params![video_id], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+
+    let mut languages = Vec::new();
+    for language in language_iter {
+        languages.push(language.map_err(|e| e.to_string())?)
+    }
+    Ok(languages)
+}
+
+pub fn save_flashcards(db: State<DataBase>, id: i64, cards: &[Flashcard]) -> Result<(), String> {
+    let mut db = db.0.lock().map_err(|e| e.to_string())?;
+    let tx = db.transaction().map_err(|e| e.to_string())?;
+    tx.execute("DELETE FROM flashcards WHERE audio_id=?1", params![id])
+        .map_err(|e| e.to_string())?;
+    for card in cards {
+        tx.execute(
+            "INSERT INTO flashcards (audio_id, question, answer, start) VALUES (?1, ?2, ?3, ?4)",
+            params![id, card.question, card.answer, card.start],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    tx.commit().map_err(|e| e.to_string())
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub fn get_flashcards(db: State<DataBase>, video_id: i64) -> Result<Vec<Flashcard>, String> {
+    let db = db.0.lock().map_err(|e| e.to_string())?;
+    let mut stmt = db
+        .prepare(
+            "SELECT question, answer, start FROM flashcards WHERE audio_id=?1 ORDER BY start, id",
+        )
+        .map_err(|e| e.to_string())?;
+
+    let card_iter = stmt
+        .query_map(params![video_id], |row| {
+            Ok(Flashcard {
+                question: row.get(0)?,
+                answer: row.get(1)?,
+                start: row.get(2)?,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+
+    let mut cards = Vec::new();
+    for card in card_iter {
+        cards.push(card.map_err(|e| e.to_string())?)
+    }
+    Ok(cards)
+}
+
+#[tauri::command]
+pub fn get_tags(db: State<DataBase>) -> Result<Vec<Tag>, String> {
+    let db = db.0.lock().map_err(|e| e.to_string())?;
+    library::list_tags(&db).map_err(|e| e.to_string())
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub fn get_video_tags(db: State<DataBase>, video_id: i64) -> Result<Vec<Tag>, String> {
+    let db = db.0.lock().map_err(|e| e.to_string())?;
+    library::video_tags(&db, video_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn create_tag(db: State<DataBase>, name: String) -> Result<Tag, String> {
+    let db = db.0.lock().map_err(|e| e.to_string())?;
+    library::create_tag(&db, &name).map_err(|e| e.to_string())
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub fn rename_tag(db: State<DataBase>, tag_id: i64, name: String) -> Result<(), String> {
+    let db = db.0.lock().map_err(|e| e.to_string())?;
+    library::rename_tag(&db, tag_id, &name).map_err(|e| e.to_string())
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub fn delete_tag(db: State<DataBase>, tag_id: i64) -> Result<(), String> {
+    let mut db = db.0.lock().map_err(|e| e.to_string())?;
+    let tx = db.transaction().map_err(|e| e.to_string())?;
+    library::delete_tag(&tx, tag_id).map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub fn tag_video(db: State<DataBase>, video_id: i64, tag_id: i64) -> Result<(), String> {
+    let db = db.0.lock().map_err(|e| e.to_string())?;
+    library::tag_video(&db, video_id, tag_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub fn untag_video(db: State<DataBase>, video_id: i64, tag_id: i64) -> Result<(), String> {
+    let db = db.0.lock().map_err(|e| e.to_string())?;
+    library::untag_video(&db, video_id, tag_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn get_collections(db: State<DataBase>) -> Result<Vec<Collection>, String> {
+    let db = db.0.lock().map_err(|e| e.to_string())?;
+    library::list_collections(&db).map_err(|e| e.to_string())
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub fn create_collection(
+    db: State<DataBase>,
+    name: String,
+    parent_id: Option<i64>,
+) -> Result<Collection, String> {
+    let db = db.0.lock().map_err(|e| e.to_string())?;
+    library::create_collection(&db, &name, parent_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub fn rename_collection(
+    db: State<DataBase>,
+    collection_id: i64,
+    name: String,
+) -> Result<(), String> {
+    let db = db.0.lock().map_err(|e| e.to_string())?;
+    library::rename_collection(&db, collection_id, &name).map_err(|e| e.to_string())
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub fn move_collection(
+    db: State<DataBase>,
+    collection_id: i64,
+    parent_id: Option<i64>,
+) -> Result<(), String> {
+    let db = db.0.lock().map_err(|e| e.to_string())?;
+    library::move_collection(&db, collection_id, parent_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub fn delete_collection(db: State<DataBase>, collection_id: i64) -> Result<(), String> {
+    let mut db = db.0.lock().map_err(|e| e.to_string())?;
+    let tx = db.transaction().map_err(|e| e.to_string())?;
+    library::delete_collection(&tx, collection_id).map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub fn add_to_collection(
+    db: State<DataBase>,
+    collection_id: i64,
+    video_id: i64,
+) -> Result<(), String> {
+    let db = db.0.lock().map_err(|e| e.to_string())?;
+    library::add_to_collection(&db, collection_id, video_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub fn remove_from_collection(
+    db: State<DataBase>,
+    collection_id: i64,
+    video_id: i64,
+) -> Result<(), String> {
+    let db = db.0.lock().map_err(|e| e.to_string())?;
+    library::remove_from_collection(&db, collection_id, video_id).map_err(|e| e.to_string())
+}
+
+// a highlighted moment of a video with the user's note on it, times in seconds
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Note {
+    pub id: i64,
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+    pub color: String,
+    pub created_at: i64,
+}
+
+fn read_note(row: &rusqlite::Row) -> Result<Note> {
+    Ok(Note {
+        id: row.get(0)?,
+        start: row.get(1)?,
+        end: row.get(2)?,
+        text: row.get(3)?,
+        color: row.get(4)?,
+        created_at: row.get(5)?,
+    })
+}
+
+fn read_notes(db: &Connection, id: i64) -> Result<Vec<Note>> {
+    let mut stmt = db.prepare(
+        "SELECT id, start_time, end_time, text, color, created_at FROM notes
+        WHERE audio_id=?1 ORDER BY start_time, id",
+    )?;
+    let note_iter = stmt.query_map(params![id], read_note)?;
+    note_iter.collect()
+}
+
+fn check_note(start: f64, end: f64, color: &str) -> Result<(), String> {
+    if !(start >= 0.0 && end >= start) {
+        return Err(format!("invalid time range {start} - {end}"));
+    }
+    if !utils::is_note_color(color) {
+        return Err(format!("invalid color: {color}"));
+    }
+    Ok(())
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub fn get_notes(db: State<DataBase>, video_id: i64) -> Result<Vec<Note>, String> {
+    let db = db.0.lock().map_err(|e| e.to_string())?;
+    read_notes(&db, video_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub fn create_note(
+    db: State<DataBase>,
+    video_id: i64,
+    start: f64,
+    end: f64,
+    text: String,
+    color: Option<String>,
+) -> Result<Note, String> {
+    let color = color.unwrap_or(utils::DEFAULT_NOTE_COLOR.to_string());
+    check_note(start, end, &color)?;
+    let db = db.0.lock().map_err(|e| e.to_string())?;
+    db.query_row(
+        "INSERT INTO notes (audio_id, start_time, end_time, text, color)
+        VALUES (?1, ?2, ?3, ?4, ?5)
+        RETURNING id, start_time, end_time, text, color, created_at",
+        params![video_id, start, end, text, color],
+        read_note,
+    )
+    .map_err(|e| e.to_string())
+}
+
+// fields left out keep their value
+#[tauri::command(rename_all = "snake_case")]
+pub fn update_note(
+    db: State<DataBase>,
+    note_id: i64,
+    start: Option<f64>,
+    end: Option<f64>,
+    text: Option<String>,
+    color: Option<String>,
+) -> Result<Note, String> {
+    let db = db.0.lock().map_err(|e| e.to_string())?;
+    let note = db
+        .query_row(
+            "SELECT id, start_time, end_time, text, color, created_at FROM notes WHERE id=?1",
+            params![note_id],
+            read_note,
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or(format!("no note {note_id}"))?;
+    let note = Note {
+        start: start.unwrap_or(note.start),
+        end: end.unwrap_or(note.end),
+        text: text.unwrap_or(note.text),
+        color: color.unwrap_or(note.color),
+        ..note
+    };
+    check_note(note.start, note.end, &note.color)?;
     db.execute(
-        &format!("UPDATE audio SET {} = ?1 Where id=?2", column),
-        params![value, id],
+        "UPDATE notes SET start_time=?2, end_time=?3, text=?4, color=?5 WHERE id=?1",
+        params![note.id, note.start, note.end, note.text, note.color],
     )
     .map_err(|e| e.to_string())?;
+    Ok(note)
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub fn delete_note(db: State<DataBase>, note_id: i64) -> Result<(), String> {
+    let db = db.0.lock().map_err(|e| e.to_string())?;
+    db.execute("DELETE FROM notes WHERE id=?1", params![note_id])
+        .map_err(|e| e.to_string())?;
     Ok(())
 }
 
+// one generated summary, a video keeps every version and shows the active one
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Summary {
+    pub id: i64,
+    pub language: String,
+    pub model: String,
+    pub prompt_template: String,
+    pub content: String,
+    pub prompt_tokens: Option<u64>,
+    pub completion_tokens: Option<u64>,
+    pub active: bool,
+    pub created_at: i64,
+}
+
+// the active summary is mirrored into `audio.summary`, which the list, search and exports read
+fn activate_summary(tx: &Connection, id: i64, summary_id: i64) -> Result<()> {
+    tx.execute(
+        "UPDATE summaries SET active = (id = ?2) WHERE audio_id = ?1",
+        params![id, summary_id],
+    )?;
+    tx.execute(
+        "UPDATE audio SET summary = (SELECT content FROM summaries WHERE id = ?2) WHERE id = ?1",
+        params![id, summary_id],
+    )?;
+    Ok(())
+}
+
+// store a new version and make it the active one; returns its id
+pub fn save_summary(
+    db: State<DataBase>,
+    id: i64,
+    language: &str,
+    model: &str,
+    prompt_template: &str,
+    content: &str,
+    usage: Option<TokenUsage>,
+) -> Result<i64, String> {
+    let mut db = db.0.lock().map_err(|e| e.to_string())?;
+    let tx = db.transaction().map_err(|e| e.to_string())?;
+    tx.execute(
+        "INSERT INTO summaries (
+            audio_id, language, model, prompt_template, content, prompt_tokens, completion_tokens
+        ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            id,
+            language,
+            model,
+            prompt_template,
+            content,
+            usage.map(|usage| usage.prompt_tokens),
+            usage.map(|usage| usage.completion_tokens),
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+    let summary_id = tx.last_insert_rowid();
+    activate_summary(&tx, id, summary_id).map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(summary_id)
+}
+
+// every version of the summary, newest first
+#[tauri::command(rename_all = "snake_case")]
+pub fn get_summaries(db: State<DataBase>, video_id: i64) -> Result<Vec<Summary>, String> {
+    let db = db.0.lock().map_err(|e| e.to_string())?;
+    let mut stmt = db
+        .prepare(
+            "SELECT id, language, model, prompt_template, content, prompt_tokens,
+                completion_tokens, active, created_at
+            FROM summaries WHERE audio_id=?1 ORDER BY created_at DESC, id DESC",
+        )
+        .map_err(|e| e.to_string())?;
+
+    let summary_iter = stmt
+        .query_map(params![video_id], |row| {
+            Ok(Summary {
+                id: row.get(0)?,
+                language: row.get(1)?,
+                model: row.get(2)?,
+                prompt_template: row.get(3)?,
+                content: row.get(4)?,
+                prompt_tokens: row.get(5)?,
+                completion_tokens: row.get(6)?,
+                active: row.get(7)?,
+                created_at: row.get(8)?,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+
+    let mut summaries = Vec::new();
+    for summary in summary_iter {
+        summaries.push(summary.map_err(|e| e.to_string())?)
+    }
+    Ok(summaries)
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub fn set_active_summary(
+    db: State<DataBase>,
+    video_id: i64,
+    summary_id: i64,
+) -> Result<(), String> {
+    let mut db = db.0.lock().map_err(|e| e.to_string())?;
+    let tx = db.transaction().map_err(|e| e.to_string())?;
+    let exists: bool = tx
+        .query_row(
+            "SELECT count(*) > 0 FROM summaries WHERE id=?1 AND audio_id=?2",
+            params![summary_id, video_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    if !exists {
+        return Err(format!("no summary {summary_id} for this video"));
+    }
+    activate_summary(&tx, video_id, summary_id).map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 pub fn delete_video(db: State<DataBase>, id: i64) -> Result<(), String> {
     let db = db.0.lock().map_err(|e| e.to_string())?;
+    // everything stored for the video goes with it through the foreign keys
     db.execute("DELETE From audio WHERE id =?1", params![id])
         .map_err(|e| e.to_string())?;
     Ok(())
@@ -209,6 +1112,7 @@ pub fn delete_video(db: State<DataBase>, id: i64) -> Result<(), String> {
 #[tauri::command]
 pub fn clear_all(db: State<DataBase>) -> Result<(), String> {
     let db = db.0.lock().map_err(|e| e.to_string())?;
+    // tags and collections stay, only what was in them is gone
     db.execute("DELETE FROM audio", [])
         .map_err(|e| e.to_string())?;
     Ok(())
@@ -0,0 +1,248 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+use tauri::{Emitter, Manager};
+use tokio_util::sync::CancellationToken;
+
+use super::jobs;
+
+// events sent to the ui; the version in a channel name changes with the payload,
+// so an older ui never reads events it does not understand
+
+// the transcripts of an import
+pub const TRANSCRIPT: &str = "stream/v1";
+pub const SUMMARY: &str = "summary/v1";
+pub const COMMENT_SUMMARY: &str = "comment_summary/v1";
+pub const SEARCH_SUMMARY: &str = "search_summary/v1";
+pub const TRANSLATION: &str = "translation/v1";
+pub const FLASHCARDS: &str = "flashcards/v1";
+// a video moved to another stage, a `status::StatusEvent`
+pub const STATUS: &str = "status/v1";
+// the queue changed, a `jobs::JobsEvent`
+pub const JOBS: &str = "jobs/v1";
+// a video was added to the library, changed or removed, a `LibraryEvent`
+pub const LIBRARY: &str = "library/v1";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    Captions,
+    Transcribing,
+    Summarizing,
+    Translating,
+    Flashcards,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Payload {
+    Started,
+    // `done` of `total` steps of the phase, chunks of audio or batches of segments
+    Progress { done: usize, total: usize },
+    Delta { text: String },
+    Finished,
+    Failed { error: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct StreamEvent {
+    // none for work started outside the job queue
+    pub job_id: Option<i64>,
+    // none for work on several videos, a comparison of search results
+    pub video_id: Option<i64>,
+    pub phase: Phase,
+    #[serde(flatten)]
+    pub payload: Payload,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Change {
+    Added,
+    Updated,
+    Removed,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct LibraryEvent {
+    pub video_id: i64,
+    pub change: Change,
+}
+
+pub fn library_changed(
+    app: &tauri::AppHandle,
+    video_id: i64,
+    change: Change,
+) -> Result<(), String> {
+    app.emit(LIBRARY, LibraryEvent { video_id, change })
+        .map_err(|e| e.to_string())
+}
+
+// one token per stream a command runs outside the job queue, by channel and video,
+// so `cancel_stream` can stop it
+#[derive(Default)]
+pub struct Streams(Mutex<HashMap<(String, Option<i64>), CancellationToken>>);
+
+// sends the events of one phase of work on a channel
+#[derive(Clone)]
+pub struct Sender {
+    app: tauri::AppHandle,
+    channel: &'static str,
+    job_id: Option<i64>,
+    video_id: Option<i64>,
+    phase: Phase,
+}
+
+impl Sender {
+    pub fn new(
+        app: &tauri::AppHandle,
+        channel: &'static str,
+        video_id: Option<i64>,
+        phase: Phase,
+    ) -> Sender {
+        Sender {
+            app: app.clone(),
+            channel,
+            job_id: jobs::current_job(),
+            video_id,
+            phase,
+        }
+    }
+
+    fn send(&self, payload: Payload) -> Result<(), String> {
+        let event = StreamEvent {
+            job_id: self.job_id,
+            video_id: self.video_id,
+            phase: self.phase,
+            payload,
+        };
+        self.app
+            .emit(self.channel, event)
+            .map_err(|e| e.to_string())
+    }
+
+    pub fn delta(&self, text: &str) -> Result<(), String> {
+        self.send(Payload::Delta {
+            text: text.to_string(),
+        })
+    }
+
+    pub fn progress(&self, done: usize, total: usize) -> Result<(), String> {
+        self.send(Payload::Progress { done, total })
+    }
+
+    // the token `cancel_stream` fires for this channel and video until `run` returns
+    pub fn cancel_token(&self) -> CancellationToken {
+        let cancel = CancellationToken::new();
+        if let Ok(mut streams) = self.app.state::<Streams>().0.lock() {
+            streams.insert((self.channel.to_string(), self.video_id), cancel.clone());
+        }
+        cancel
+    }
+
+    // wrap `run` in started and finished, or failed with its error
+    pub async fn run<T>(&self, run: impl Future<Output = Result<T, String>>) -> Result<T, String> {
+        self.send(Payload::Started)?;
+        let result = run.await;
+        if let Ok(mut streams) = self.app.state::<Streams>().0.lock() {
+            streams.remove(&(self.channel.to_string(), self.video_id));
+        }
+        match result {
+            Ok(value) => {
+                self.send(Payload::Finished)?;
+                Ok(value)
+            }
+            Err(error) => {
+                let _ = self.send(Payload::Failed {
+                    error: error.clone(),
+                });
+                Err(error)
+            }
+        }
+    }
+}
+
+// stop a summary, translation or comparison started outside the job queue
+#[tauri::command(rename_all = "snake_case")]
+pub fn cancel_stream(
+    app: tauri::AppHandle,
+    channel: String,
+    video_id: Option<i64>,
+) -> Result<(), String> {
+    let streams = app.state::<Streams>();
+    let streams = streams.0.lock().map_err(|e| e.to_string())?;
+    match streams.get(&(channel, video_id)) {
+        Some(cancel) => {
+            cancel.cancel();
+            Ok(())
+        }
+        None => Err("this stream is not running".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::status::{Stage, StatusEvent};
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn events_are_tagged() {
+        // text that looks like the markers of the old channels is only text
+        let event = StreamEvent {
+            job_id: Some(3),
+            video_id: Some(1),
+            phase: Phase::Summarizing,
+            payload: Payload::Delta {
+                text: "[end]".to_string(),
+            },
+        };
+        let value = serde_json::to_value(&event).unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "job_id": 3,
+                "video_id": 1,
+                "phase": "summarizing",
+                "type": "delta",
+                "text": "[end]",
+            })
+        );
+        assert_eq!(serde_json::from_value::<StreamEvent>(value).unwrap(), event);
+
+        let value = json!({
+            "job_id": null,
+            "video_id": null,
+            "phase": "transcribing",
+            "type": "progress",
+            "done": 2,
+            "total": 5,
+        });
+        assert_eq!(
+            serde_json::from_value::<StreamEvent>(value)
+                .unwrap()
+                .payload,
+            Payload::Progress { done: 2, total: 5 }
+        );
+
+        let event = StatusEvent {
+            video_id: 4,
+            stage: Stage::FetchingMetadata,
+            error: None,
+        };
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            json!({ "video_id": 4, "stage": "fetching_metadata", "error": null })
+        );
+
+        let event = LibraryEvent {
+            video_id: 4,
+            change: Change::Removed,
+        };
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            json!({ "video_id": 4, "change": "removed" })
+        );
+    }
+}
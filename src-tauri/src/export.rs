@@ -0,0 +1,450 @@
+use serde::{Deserialize, Serialize};
+use tauri::Manager;
+
+use super::db::{self, Note};
+use super::whisper::Segment;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Srt,
+    Vtt,
+    Ass,
+    Ttml,
+    Txt,
+    Json,
+}
+
+const ASS_HEADER: &str = "[Script Info]\nScriptType: v4.00+\n\n[V4+ Styles]\n\
+    Format: Name, Fontname, Fontsize, PrimaryColour, OutlineColour, BackColour, Bold, Italic, \
+    BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding\n\
+    Style: Default,Arial,20,&H00FFFFFF,&H00000000,&H00000000,0,0,1,2,0,2,10,10,10,1\n\n\
+    [Events]\n\
+    Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n";
+
+const TTML_HEADER: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+    <tt xmlns=\"http://www.w3.org/ns/ttml\">\n<body>\n<div>\n";
+
+const TTML_FOOTER: &str = "</div>\n</body>\n</tt>\n";
+
+// only used for the subtitle formats, txt and json keep the segments as they are
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct ExportOptions {
+    // characters per line, a cue holds at most two lines
+    pub max_line_length: usize,
+    // seconds
+    pub max_cue_duration: f64,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        Self {
+            max_line_length: 42,
+            max_cue_duration: 7.0,
+        }
+    }
+}
+
+// "01:02:03,456" for srt or "01:02:03.456" for vtt
+fn format_timestamp(seconds: f64, separator: char) -> String {
+    let millis = (seconds.max(0.0) * 1000.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02}{}{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        separator,
+        millis % 1000
+    )
+}
+
+// "1:02:03.45", ass counts in centiseconds
+fn format_ass_timestamp(seconds: f64) -> String {
+    let centis = (seconds.max(0.0) * 100.0).round() as u64;
+    format!(
+        "{}:{:02}:{:02}.{:02}",
+        centis / 360_000,
+        centis / 6000 % 60,
+        centis / 100 % 60,
+        centis % 100
+    )
+}
+
+fn escape_markup(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn wrap_lines(text: &str, max_line_length: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for word in text.split_whitespace() {
+        match lines.last_mut() {
+            Some(line) if line.chars().count() + 1 + word.chars().count() <= max_line_length => {
+                line.push(' ');
+                line.push_str(word);
+            }
+            _ => lines.push(word.to_string()),
+        }
+    }
+    lines
+}
+
+// split segments into cues that fit in two lines and last no longer than `max_cue_duration`,
+// the time of a segment is shared between its cues by their number of characters
+fn split_cues(segments: &[Segment], options: &ExportOptions) -> Vec<Segment> {
+    let max_chars = options.max_line_length.max(1) * 2;
+    let mut cues = Vec::new();
+    for segment in segments {
+        let text = segment
+            .text
+            .split_whitespace()
+            .collect::<Vec<&str>>()
+            .join(" ");
+        let total_chars = text.chars().count();
+        if total_chars == 0 {
+            continue;
+        }
+        let seconds_per_char = (segment.end - segment.start).max(0.0) / total_chars as f64;
+
+        let mut start = segment.start;
+        let mut current = String::new();
+        for word in text.split(' ') {
+            let next_chars = current.chars().count() + 1 + word.chars().count();
+            let too_long = next_chars > max_chars
+                || (next_chars + 1) as f64 * seconds_per_char > options.max_cue_duration;
+            if !current.is_empty() && too_long {
+                // the separating space belongs to the cue it ends
+                let end = start + (current.chars().count() + 1) as f64 * seconds_per_char;
+                cues.push(Segment {
+                    start,
+                    end,
+                    text: std::mem::take(&mut current),
+                    speaker: segment.speaker.clone(),
+                });
+                start = end;
+            }
+            if !current.is_empty() {
+                current.push(' ');
+            }
+            current.push_str(word);
+        }
+        cues.push(Segment {
+            start,
+            end: segment.end,
+            text: current,
+            speaker: segment.speaker.clone(),
+        });
+    }
+    cues
+}
+
+// append one cue, the lines are wrapped already
+fn push_cue(
+    content: &mut String,
+    format: ExportFormat,
+    index: usize,
+    cue: &Segment,
+    lines: &[String],
+) {
+    match format {
+        ExportFormat::Srt => content.push_str(&format!(
+            "{}\n{} --> {}\n{}\n\n",
+            index + 1,
+            format_timestamp(cue.start, ','),
+            format_timestamp(cue.end, ','),
+            lines.join("\n")
+        )),
+        ExportFormat::Ass => {
+            // the name is a field of its own, a comma in it would shift the text
+            let name = cue.speaker.as_deref().unwrap_or_default().replace(',', " ");
+            content.push_str(&format!(
+                "Dialogue: 0,{},{},Default,{name},0,0,0,,{}\n",
+                format_ass_timestamp(cue.start),
+                format_ass_timestamp(cue.end),
+                lines.join("\\N")
+            ));
+        }
+        // ttml names speakers through agents declared in the head, they are left out
+        ExportFormat::Ttml => content.push_str(&format!(
+            "<p begin=\"{}\" end=\"{}\">{}</p>\n",
+            format_timestamp(cue.start, '.'),
+            format_timestamp(cue.end, '.'),
+            lines
+                .iter()
+                .map(|line| escape_markup(line))
+                .collect::<Vec<String>>()
+                .join("<br/>")
+        )),
+        _ => {
+            // cue text is markup in webvtt, the speaker is the voice of the cue
+            let text = escape_markup(&lines.join("\n"));
+            let text = match &cue.speaker {
+                Some(speaker) => format!("<v {}>{text}", speaker.replace('>', "")),
+                None => text,
+            };
+            content.push_str(&format!(
+                "{} --> {}\n{}\n\n",
+                format_timestamp(cue.start, '.'),
+                format_timestamp(cue.end, '.'),
+                text
+            ));
+        }
+    }
+}
+
+pub fn render_segments(
+    segments: &[Segment],
+    format: ExportFormat,
+    options: &ExportOptions,
+) -> Result<String, String> {
+    let mut content = String::new();
+    match format {
+        ExportFormat::Srt | ExportFormat::Vtt | ExportFormat::Ass | ExportFormat::Ttml => {
+            match format {
+                ExportFormat::Vtt => content.push_str("WEBVTT\n\n"),
+                ExportFormat::Ass => content.push_str(ASS_HEADER),
+                ExportFormat::Ttml => content.push_str(TTML_HEADER),
+                _ => {}
+            }
+            for (index, cue) in split_cues(segments, options).iter().enumerate() {
+                let lines = wrap_lines(&cue.text, options.max_line_length);
+                push_cue(&mut content, format, index, cue, &lines);
+            }
+            if format == ExportFormat::Ttml {
+                content.push_str(TTML_FOOTER);
+            }
+        }
+        ExportFormat::Txt => {
+            for segment in segments {
+                let timestamp = format_timestamp(segment.start, '.');
+                content.push_str(&format!("[{}] {}\n", &timestamp[..8], segment.text.trim()));
+            }
+        }
+        ExportFormat::Json => {
+            content = serde_json::to_string_pretty(segments).map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(content)
+}
+
+// stack the translated text under the original one in every cue; cues follow the original
+// segments without splitting, so both languages always stay on the same timing
+pub fn render_bilingual(
+    segments: &[Segment],
+    translated: &[Segment],
+    format: ExportFormat,
+    options: &ExportOptions,
+) -> Result<String, String> {
+    if !matches!(format, ExportFormat::Srt | ExportFormat::Vtt) {
+        return Err("dual-language export supports srt and vtt only".to_string());
+    }
+    if segments.len() != translated.len() {
+        return Err("the translation does not match the transcripts, translate again".to_string());
+    }
+    let mut content = String::new();
+    if format == ExportFormat::Vtt {
+        content.push_str("WEBVTT\n\n");
+    }
+    for (index, (segment, translation)) in segments.iter().zip(translated).enumerate() {
+        let mut lines = wrap_lines(&segment.text, options.max_line_length);
+        lines.extend(wrap_lines(&translation.text, options.max_line_length));
+        push_cue(&mut content, format, index, segment, &lines);
+    }
+    Ok(content)
+}
+
+// notes become cues of their own between the transcripts, so players show them at their moment
+pub fn merge_notes(segments: &[Segment], notes: &[Note]) -> Vec<Segment> {
+    let mut merged = segments.to_vec();
+    merged.extend(notes.iter().map(|note| Segment {
+        start: note.start,
+        // a note on a single moment still stays on screen for a while
+        end: note.end.max(note.start + 2.0),
+        text: format!("[Note] {}", note.text.trim()),
+        speaker: None,
+    }));
+    merged.sort_by(|a, b| a.start.total_cmp(&b.start));
+    merged
+}
+
+// write the stored transcripts of a video to `path` for use in other editors
+#[tauri::command(rename_all = "snake_case")]
+pub fn export_transcript(
+    app: tauri::AppHandle,
+    video_id: i64,
+    format: ExportFormat,
+    path: String,
+    options: Option<ExportOptions>,
+    translation_language: Option<String>,
+    include_notes: Option<bool>,
+) -> Result<(), String> {
+    let (mut segments, _) = db::get_subtitle_with_id(app.state(), video_id)?;
+    let options = options.unwrap_or_default();
+    if include_notes.unwrap_or(false) {
+        // json keeps the plain segments, dual-language cues pair up with them one to one
+        if format == ExportFormat::Json || translation_language.is_some() {
+            return Err("notes can only be added to single-language subtitles and txt".to_string());
+        }
+        segments = merge_notes(&segments, &db::get_notes(app.state(), video_id)?);
+    }
+    let content = match translation_language {
+        Some(language) => {
+            let translated = db::get_translation(app.state(), video_id, language.clone())?
+                .ok_or(format!("no {language} translation for this video"))?;
+            render_bilingual(&segments, &translated, format, &options)?
+        }
+        None => render_segments(&segments, format, &options)?,
+    };
+    std::fs::write(&path, content).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::subtitles::{parse_subtitles, SubtitleFormat};
+
+    fn segments() -> Vec<Segment> {
+        vec![
+            Segment {
+                start: 0.5,
+                end: 2.0,
+                text: "Short line.".to_string(),
+                speaker: None,
+            },
+            Segment {
+                start: 3661.25,
+                end: 3663.0,
+                text: "Another one & done.".to_string(),
+                speaker: None,
+            },
+        ]
+    }
+
+    #[test]
+    fn format_timestamp_works() {
+        assert_eq!(format_timestamp(3661.25, ','), "01:01:01,250");
+        assert_eq!(format_timestamp(0.0005, '.'), "00:00:00.001");
+    }
+
+    #[test]
+    fn wrap_lines_works() {
+        assert_eq!(
+            wrap_lines("the quick brown fox jumps", 10),
+            vec!["the quick", "brown fox", "jumps"]
+        );
+    }
+
+    #[test]
+    fn split_cues_works() {
+        let long = vec![Segment {
+            start: 0.0,
+            end: 20.0,
+            text: "one two three four five six seven eight nine ten".to_string(),
+            speaker: None,
+        }];
+        let options = ExportOptions {
+            max_line_length: 10,
+            max_cue_duration: 30.0,
+        };
+        let cues = split_cues(&long, &options);
+        assert!(cues.iter().all(|cue| cue.text.chars().count() <= 20));
+        assert_eq!(cues.first().unwrap().start, 0.0);
+        assert_eq!(cues.last().unwrap().end, 20.0);
+        let joined: Vec<&str> = cues.iter().map(|cue| cue.text.as_str()).collect();
+        assert_eq!(joined.join(" "), long[0].text);
+
+        let options = ExportOptions {
+            max_line_length: 42,
+            max_cue_duration: 5.0,
+        };
+        let cues = split_cues(&long, &options);
+        assert!(cues.iter().all(|cue| cue.end - cue.start <= 5.0 + 1e-9));
+        assert!(cues.windows(2).all(|pair| pair[0].end == pair[1].start));
+    }
+
+    #[test]
+    fn srt_round_trip() {
+        let content =
+            render_segments(&segments(), ExportFormat::Srt, &ExportOptions::default()).unwrap();
+        assert!(content.starts_with("1\n00:00:00,500 --> 00:00:02,000\nShort line.\n\n2\n"));
+        assert_eq!(parse_subtitles(&content, SubtitleFormat::Srt), segments());
+    }
+
+    #[test]
+    fn vtt_round_trip() {
+        let content =
+            render_segments(&segments(), ExportFormat::Vtt, &ExportOptions::default()).unwrap();
+        assert!(content.starts_with("WEBVTT\n\n00:00:00.500 --> 00:00:02.000\n"));
+        assert_eq!(parse_subtitles(&content, SubtitleFormat::Vtt), segments());
+    }
+
+    #[test]
+    fn bilingual_works() {
+        let translated = vec![
+            Segment {
+                start: 0.5,
+                end: 2.0,
+                text: "Kurze Zeile.".to_string(),
+                speaker: None,
+            },
+            Segment {
+                start: 3661.25,
+                end: 3663.0,
+                text: "Noch eine & fertig.".to_string(),
+                speaker: None,
+            },
+        ];
+        let options = ExportOptions::default();
+        let content =
+            render_bilingual(&segments(), &translated, ExportFormat::Srt, &options).unwrap();
+        assert!(
+            content.starts_with("1\n00:00:00,500 --> 00:00:02,000\nShort line.\nKurze Zeile.\n\n")
+        );
+
+        let content =
+            render_bilingual(&segments(), &translated, ExportFormat::Vtt, &options).unwrap();
+        assert!(content.contains("Another one &amp; done.\nNoch eine &amp; fertig.\n"));
+        let cues = parse_subtitles(&content, SubtitleFormat::Vtt);
+        assert_eq!(cues[1].text, "Another one & done. Noch eine & fertig.");
+
+        assert!(
+            render_bilingual(&segments(), &translated[..1], ExportFormat::Srt, &options).is_err()
+        );
+        assert!(render_bilingual(&segments(), &translated, ExportFormat::Txt, &options).is_err());
+    }
+
+    #[test]
+    fn txt_and_json_work() {
+        let options = ExportOptions::default();
+        let content = render_segments(&segments(), ExportFormat::Txt, &options).unwrap();
+        assert_eq!(
+            content,
+            "[00:00:00] Short line.\n[01:01:01] Another one & done.\n"
+        );
+        let content = render_segments(&segments(), ExportFormat::Json, &options).unwrap();
+        let parsed: Vec<Segment> = serde_json::from_str(&content).unwrap();
+        assert_eq!(parsed, segments());
+    }
+
+    #[test]
+    fn notes_become_cues() {
+        let note = Note {
+            id: 1,
+            start: 5.0,
+            end: 5.0,
+            text: "check this ".to_string(),
+            color: "yellow".to_string(),
+            created_at: 0,
+        };
+        let merged = merge_notes(&segments(), &[note]);
+        let content =
+            render_segments(&merged, ExportFormat::Txt, &ExportOptions::default()).unwrap();
+        assert_eq!(
+            content,
+            "[00:00:00] Short line.\n[00:00:05] [Note] check this\n[01:01:01] Another one & done.\n"
+        );
+        assert_eq!(merged[1].end, 7.0);
+    }
+}
@@ -0,0 +1,472 @@
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::Manager;
+use tube_rs::fnv1a;
+use zip::write::SimpleFileOptions;
+
+use super::db;
+use super::events::{self, Phase, Sender};
+use super::utils;
+use super::whisper::{self, Segment};
+
+const MAX_ATTEMPTS: usize = 3;
+// fixed, so every exported deck shares one note type in anki
+const MODEL_ID: i64 = 1_716_000_000_000;
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Flashcard {
+    pub question: String,
+    pub answer: String,
+    // seconds, start of the segment the answer is said in
+    pub start: f64,
+}
+
+// a card as the model writes it, `time` is copied from the transcript line
+#[derive(Debug, Deserialize)]
+struct GeneratedCard {
+    question: String,
+    answer: String,
+    time: String,
+}
+
+fn get_flashcard_prompt(language: Option<&str>) -> String {
+    let language = match language {
+        Some(language) => format!("in {}", whisper::language_name(language)),
+        None => "in the language of the transcript".to_string(),
+    };
+    format!(
+        r#"
+        You are an experienced teacher writing spaced-repetition flashcards from a video transcript.
+        Guiding principles:
+        - Every transcript line starts with its time as "mm:ss".
+        - Write 10 to 30 cards {language} covering the key facts, definitions and ideas, one idea per card.
+        - A question must make sense without watching the video; keep the answer short.
+        - Set `time` to the time of the line where the answer is said, copied exactly from the transcript.
+        - Answer with a JSON array of objects with `question`, `answer` and `time` only, without any explanation or code fence.
+        "#
+    )
+}
+
+// "mm:ss" or "hh:mm:ss" to seconds
+fn parse_time(time: &str) -> Option<f64> {
+    let mut seconds = 0.0;
+    for part in time.trim().split(':') {
+        seconds = seconds * 60.0 + part.trim().parse::<f64>().ok()?;
+    }
+    Some(seconds)
+}
+
+// drop incomplete cards and move every card to the start of the segment its time falls in
+fn validate_cards(cards: Vec<GeneratedCard>, segments: &[Segment]) -> Vec<Flashcard> {
+    let mut validated: Vec<Flashcard> = cards
+        .into_iter()
+        .filter_map(|card| {
+            let question = card.question.trim();
+            let answer = card.answer.trim();
+            if question.is_empty() || answer.is_empty() {
+                return None;
+            }
+            // the transcript shows whole seconds, a segment at 65.4 is written as 01:05
+            let time = parse_time(&card.time)? + 1.0;
+            let segment = segments
+                .iter()
+                .rev()
+                .find(|segment| segment.start < time)
+                .or(segments.first())?;
+            Some(Flashcard {
+                question: question.to_string(),
+                answer: answer.to_string(),
+                start: segment.start,
+            })
+        })
+        .collect();
+    validated.sort_by(|a, b| a.start.total_cmp(&b.start));
+    validated
+}
+
+fn parse_flashcards(answer: &str, segments: &[Segment]) -> Result<Vec<Flashcard>, String> {
+    let json = utils::extract_json_array(answer).ok_or("no json array in the flashcards")?;
+    let cards: Vec<GeneratedCard> = serde_json::from_str(json).map_err(|e| e.to_string())?;
+    let cards = validate_cards(cards, segments);
+    if cards.is_empty() {
+        return Err("no usable flashcards in the answer".to_string());
+    }
+    Ok(cards)
+}
+
+// ask the llm for question/answer pairs on the transcripts and store them, replacing older ones
+#[tauri::command(rename_all = "snake_case")]
+pub async fn generate_flashcards(
+    app: tauri::AppHandle,
+    video_id: i64,
+    language: Option<String>,
+) -> Result<Vec<Flashcard>, String> {
+    let (segments, _) = db::get_subtitle_with_id(app.state(), video_id)?;
+    let content = utils::transform_segment_to_string(segments.clone());
+
+    let sender = Sender::new(&app, events::FLASHCARDS, Some(video_id), Phase::Flashcards);
+    let cancel = sender.cancel_token();
+    let generate = async {
+        let mut result = Err(String::new());
+        for _ in 0..MAX_ATTEMPTS {
+            let answer = whisper::chat_completion_stream(
+                &app,
+                &sender,
+                get_flashcard_prompt(language.as_deref()),
+                content.clone(),
+                &cancel,
+            )
+            .await?;
+            result = parse_flashcards(&answer, &segments);
+            if result.is_ok() {
+                break;
+            }
+        }
+        result
+    };
+    let cards = sender.run(generate).await?;
+
+    db::save_flashcards(app.state(), video_id, &cards)?;
+    Ok(cards)
+}
+
+// the back-link shown under every answer, youtube videos open at the moment it is said
+fn source_link(video_id: &str, title: &str, start: f64) -> String {
+    let seconds = start as u64;
+    let label = format!(
+        "{} @ {}",
+        utils::escape_html(title),
+        utils::format_clock(seconds)
+    );
+    if video_id.contains(':') {
+        label
+    } else {
+        format!(
+            "<a href=\"https://www.youtube.com/watch?v={video_id}&amp;t={seconds}\">{label}</a>"
+        )
+    }
+}
+
+// anki finds duplicates by the first 8 hex digits of the sha1 of the sort field
+fn field_checksum(field: &str) -> i64 {
+    let digest = sha1_smol::Sha1::from(field).digest().to_string();
+    i64::from_str_radix(&digest[..8], 16).unwrap_or_default()
+}
+
+// the schema of an anki 2.1 collection (version 11), which every anki version can import
+const COLLECTION_SCHEMA: &str = "
+CREATE TABLE col (
+    id integer primary key, crt integer not null, mod integer not null, scm integer not null,
+    ver integer not null, dty integer not null, usn integer not null, ls integer not null,
+    conf text not null, models text not null, decks text not null, dconf text not null,
+    tags text not null
+);
+CREATE TABLE notes (
+    id integer primary key, guid text not null, mid integer not null, mod integer not null,
+    usn integer not null, tags text not null, flds text not null, sfld integer not null,
+    csum integer not null, flags integer not null, data text not null
+);
+CREATE TABLE cards (
+    id integer primary key, nid integer not null, did integer not null, ord integer not null,
+    mod integer not null, usn integer not null, type integer not null, queue integer not null,
+    due integer not null, ivl integer not null, factor integer not null, reps integer not null,
+    lapses integer not null, left integer not null, odue integer not null, odid integer not null,
+    flags integer not null, data text not null
+);
+CREATE TABLE revlog (
+    id integer primary key, cid integer not null, usn integer not null, ease integer not null,
+    ivl integer not null, lastIvl integer not null, factor integer not null, time integer not null,
+    type integer not null
+);
+CREATE TABLE graves (usn integer not null, oid integer not null, type integer not null);
+CREATE INDEX ix_notes_usn on notes (usn);
+CREATE INDEX ix_cards_usn on cards (usn);
+CREATE INDEX ix_revlog_usn on revlog (usn);
+CREATE INDEX ix_cards_nid on cards (nid);
+CREATE INDEX ix_cards_sched on cards (did, queue, due);
+CREATE INDEX ix_revlog_cid on revlog (cid);
+CREATE INDEX ix_notes_csum on notes (csum);
+";
+
+fn deck_json(id: i64, name: &str, now: i64) -> Value {
+    json!({
+        "id": id, "name": name, "desc": "", "mod": now, "usn": -1, "dyn": 0, "conf": 1,
+        "collapsed": false, "browserCollapsed": false, "extendNew": 0, "extendRev": 0,
+        "newToday": [0, 0], "revToday": [0, 0], "lrnToday": [0, 0], "timeToday": [0, 0]
+    })
+}
+
+fn model_json(deck_id: i64, now: i64) -> Value {
+    let fields: Vec<Value> = ["Question", "Answer", "Source"]
+        .iter()
+        .enumerate()
+        .map(|(ord, name)| {
+            json!({
+                "name": name, "ord": ord, "sticky": false, "rtl": false,
+                "font": "Arial", "size": 20, "media": []
+            })
+        })
+        .collect();
+    json!({
+        "id": MODEL_ID, "name": "Video Maestro", "type": 0, "mod": now, "usn": -1,
+        "sortf": 0, "did": deck_id, "flds": fields,
+        "tmpls": [{
+            "name": "Card 1", "ord": 0, "qfmt": "{{Question}}",
+            "afmt": "{{FrontSide}}<hr id=answer>{{Answer}}<div class=source>{{Source}}</div>",
+            "bqfmt": "", "bafmt": "", "did": null
+        }],
+        "css": ".card { font-family: arial; font-size: 20px; text-align: center; color: black; background-color: white; }\n.source { margin-top: 1em; font-size: 14px; }",
+        "latexPre": "\\documentclass[12pt]{article}\n\\special{papersize=3in,5in}\n\\usepackage[utf8]{inputenc}\n\\usepackage{amssymb,amsmath}\n\\pagestyle{empty}\n\\setlength{\\parindent}{0in}\n\\begin{document}\n",
+        "latexPost": "\\end{document}", "latexsvg": false,
+        "tags": [], "vers": [], "req": [[0, "any", [0]]]
+    })
+}
+
+fn write_collection(
+    connection: &Connection,
+    video_id: &str,
+    title: &str,
+    cards: &[Flashcard],
+) -> rusqlite::Result<()> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or_default();
+    // derived from the video, importing a new export again updates the same deck
+    let deck_id = (fnv1a(video_id) >> 12) as i64;
+
+    let mut models = Map::new();
+    models.insert(MODEL_ID.to_string(), model_json(deck_id, now));
+    let mut decks = Map::new();
+    decks.insert("1".to_string(), deck_json(1, "Default", now));
+    decks.insert(
+        deck_id.to_string(),
+        deck_json(deck_id, &format!("Video Maestro::{title}"), now),
+    );
+    let conf = json!({
+        "activeDecks": [1], "curDeck": 1, "newSpread": 0, "collapseTime": 1200, "timeLim": 0,
+        "estTimes": true, "dueCounts": true, "curModel": null, "nextPos": cards.len() + 1,
+        "sortType": "noteFld", "sortBackwards": false, "addToCur": true
+    });
+    let dconf = json!({"1": {
+        "id": 1, "name": "Default", "mod": 0, "usn": 0, "maxTaken": 60, "autoplay": true,
+        "timer": 0, "replayq": true, "dyn": false,
+        "new": {"bury": true, "delays": [1.0, 10.0], "initialFactor": 2500, "ints": [1, 4, 7],
+            "order": 1, "perDay": 20, "separate": true},
+        "lapse": {"delays": [10.0], "leechAction": 0, "leechFails": 8, "minInt": 1, "mult": 0.0},
+        "rev": {"bury": true, "ease4": 1.3, "fuzz": 0.05, "ivlFct": 1.0, "maxIvl": 36500,
+            "minSpace": 1, "perDay": 100}
+    }});
+
+    connection.execute_batch(COLLECTION_SCHEMA)?;
+    connection.execute(
+        "INSERT INTO col VALUES (1, ?1, ?2, ?2, 11, 0, 0, 0, ?3, ?4, ?5, ?6, '{}')",
+        params![
+            now,
+            now * 1000,
+            conf.to_string(),
+            Value::Object(models).to_string(),
+            Value::Object(decks).to_string(),
+            dconf.to_string()
+        ],
+    )?;
+    for (index, card) in cards.iter().enumerate() {
+        let note_id = now * 1000 + index as i64;
+        // a stable guid lets anki update a card instead of adding a duplicate
+        let guid = format!(
+            "{:016x}",
+            fnv1a(format!("{video_id}\u{1f}{}", card.question))
+        );
+        let question = utils::escape_html(&card.question);
+        let fields = [
+            question.as_str(),
+            &utils::escape_html(&card.answer),
+            &source_link(video_id, title, card.start),
+        ]
+        .join("\u{1f}");
+        connection.execute(
+            "INSERT INTO notes VALUES (?1, ?2, ?3, ?4, -1, ' videomaestro ', ?5, ?6, ?7, 0, '')",
+            params![
+                note_id,
+                guid,
+                MODEL_ID,
+                now,
+                fields,
+                question,
+                field_checksum(&card.question)
+            ],
+        )?;
+        // new cards, `due` is the position in the new queue
+        connection.execute(
+            "INSERT INTO cards VALUES (?1, ?1, ?2, 0, ?3, -1, 0, 0, ?4, 0, 0, 0, 0, 0, 0, 0, 0, '')",
+            params![note_id, deck_id, now, index as i64 + 1],
+        )?;
+    }
+    Ok(())
+}
+
+// an .apkg is a zip of the collection database and a json map of media files
+pub fn write_apkg(
+    work_dir: &Path,
+    output: &Path,
+    video_id: &str,
+    title: &str,
+    cards: &[Flashcard],
+) -> Result<(), String> {
+    let collection_path = work_dir.join("collection.anki2");
+    if collection_path.exists() {
+        fs::remove_file(&collection_path).map_err(|e| e.to_string())?;
+    }
+    {
+        let connection = Connection::open(&collection_path).map_err(|e| e.to_string())?;
+        write_collection(&connection, video_id, title, cards).map_err(|e| e.to_string())?;
+    }
+    let collection = fs::read(&collection_path).map_err(|e| e.to_string())?;
+    fs::remove_file(&collection_path).map_err(|e| e.to_string())?;
+
+    let mut zip = zip::ZipWriter::new(File::create(output).map_err(|e| e.to_string())?);
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    zip.start_file("collection.anki2", options)
+        .map_err(|e| e.to_string())?;
+    zip.write_all(&collection).map_err(|e| e.to_string())?;
+    zip.start_file("media", options)
+        .map_err(|e| e.to_string())?;
+    zip.write_all(b"{}").map_err(|e| e.to_string())?;
+    zip.finish().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+// write the stored flashcards of a video to `path` as an anki deck; returns the number of cards
+#[tauri::command(rename_all = "snake_case")]
+pub async fn export_anki(
+    app: tauri::AppHandle,
+    video_id: i64,
+    path: String,
+) -> Result<usize, String> {
+    let cards = db::get_flashcards(app.state(), video_id)?;
+    if cards.is_empty() {
+        return Err("no flashcards for this video, generate them first".to_string());
+    }
+    let video = db::get_video_with_id(app.state(), video_id)?;
+    // a directory per export, so two exports never write the same collection
+    let started = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| e.to_string())?
+        .as_nanos();
+    let work_dir = app
+        .path()
+        .cache_dir()
+        .map_err(|e| e.to_string())?
+        .join("newscenter")
+        .join(format!("anki-{video_id}-{started}"));
+    fs::create_dir_all(&work_dir).map_err(|e| e.to_string())?;
+    let count = cards.len();
+    let blocking_dir = work_dir.clone();
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        write_apkg(
+            &blocking_dir,
+            Path::new(&path),
+            &video.video_id,
+            &video.title,
+            &cards,
+        )
+    })
+    .await
+    .map_err(|e| e.to_string());
+    let _ = fs::remove_dir_all(&work_dir);
+    result??;
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    fn segments() -> Vec<Segment> {
+        vec![
+            Segment {
+                start: 0.0,
+                end: 5.0,
+                text: "Welcome".to_string(),
+                speaker: None,
+            },
+            Segment {
+                start: 65.4,
+                end: 70.0,
+                text: "Ownership moves values".to_string(),
+                speaker: None,
+            },
+        ]
+    }
+
+    #[test]
+    fn parse_flashcards_works() {
+        let answer = r#"Here you go:
+        [{"question": "What happens on assignment?", "answer": "The value moves", "time": "01:05"},
+         {"question": " ", "answer": "dropped", "time": "00:01"},
+         {"question": "Greeting?", "answer": "Welcome", "time": "00:02"}]"#;
+        let cards = parse_flashcards(answer, &segments()).unwrap();
+        assert_eq!(cards.len(), 2);
+        assert_eq!(cards[0].start, 0.0);
+        assert_eq!(cards[1].question, "What happens on assignment?");
+        assert_eq!(cards[1].start, 65.4);
+        assert!(parse_flashcards("[]", &segments()).is_err());
+        assert_eq!(parse_time("1:01:05"), Some(3665.0));
+    }
+
+    #[test]
+    fn write_apkg_works() {
+        let work_dir =
+            std::env::temp_dir().join(format!("videomaestro-anki-{}", std::process::id()));
+        fs::create_dir_all(&work_dir).unwrap();
+        let output = work_dir.join("deck.apkg");
+        let cards = vec![Flashcard {
+            question: "What is <ownership>?".to_string(),
+            answer: "Rules & moves".to_string(),
+            start: 65.4,
+        }];
+        write_apkg(&work_dir, &output, "FdeioVndUhs", "Rust", &cards).unwrap();
+
+        let mut archive = zip::ZipArchive::new(File::open(&output).unwrap()).unwrap();
+        let mut media = String::new();
+        archive
+            .by_name("media")
+            .unwrap()
+            .read_to_string(&mut media)
+            .unwrap();
+        assert_eq!(media, "{}");
+        let mut collection = Vec::new();
+        archive
+            .by_name("collection.anki2")
+            .unwrap()
+            .read_to_end(&mut collection)
+            .unwrap();
+        let collection_path = work_dir.join("read.anki2");
+        fs::write(&collection_path, collection).unwrap();
+
+        let connection = Connection::open(&collection_path).unwrap();
+        let fields: String = connection
+            .query_row("SELECT flds FROM notes", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(
+            fields,
+            "What is &lt;ownership&gt;?\u{1f}Rules &amp; moves\u{1f}<a href=\"https://www.youtube.com/watch?v=FdeioVndUhs&amp;t=65\">Rust @ 01:05</a>"
+        );
+        let (deck_id, decks): (i64, String) = connection
+            .query_row(
+                "SELECT cards.did, col.decks FROM cards, col WHERE cards.nid = (SELECT id FROM notes)",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        let decks: Value = serde_json::from_str(&decks).unwrap();
+        assert_eq!(decks[deck_id.to_string()]["name"], "Video Maestro::Rust");
+        fs::remove_dir_all(&work_dir).unwrap();
+    }
+}
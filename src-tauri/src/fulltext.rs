@@ -0,0 +1,529 @@
+use rusqlite::{params, params_from_iter, Connection, Result};
+use serde::{Deserialize, Serialize};
+
+use super::utils;
+
+// snippet() marks matches with these private use characters, they become <mark> after escaping
+const MATCH_START: char = '\u{e000}';
+const MATCH_END: char = '\u{e001}';
+// transcript hits shown per video, a word said fifty times should not fill the whole result
+const HITS_PER_VIDEO: i64 = 3;
+// the index cannot look up shorter words, most words of chinese are two characters
+const MIN_MATCH_CHARS: usize = 3;
+// characters around a match in a snippet, trigram tokens are single characters
+const SNIPPET_CHARS: usize = 64;
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HitKind {
+    // title, keywords, description or summary
+    Video,
+    Transcript,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchHit {
+    pub id: i64,
+    pub video_id: String,
+    pub title: String,
+    pub thumbnail_url: String,
+    pub kind: HitKind,
+    // html escaped, the matched words are wrapped in <mark>
+    pub snippet: String,
+    // seconds, start of the matched segment for transcript hits
+    pub start: Option<f64>,
+    // bm25, lower is better
+    pub rank: f64,
+}
+
+// one row per video for the metadata and one row per transcript segment, both kept in sync
+// with `audio` by triggers; segments are read from the transcripts json with json_each
+pub fn create_index(connection: &Connection) -> Result<()> {
+    let exists: bool = connection.query_row(
+        "SELECT count(*) > 0 FROM sqlite_master WHERE name = 'video_search'",
+        [],
+        |row| row.get(0),
+    )?;
+
+    connection.execute_batch(
+        "CREATE VIRTUAL TABLE IF NOT EXISTS video_search USING fts5(
+            title, keywords, description, summary,
+            tokenize = 'unicode61 remove_diacritics 2'
+        );
+        CREATE VIRTUAL TABLE IF NOT EXISTS segment_search USING fts5(
+            text, audio_id UNINDEXED, start UNINDEXED,
+            tokenize = 'unicode61 remove_diacritics 2'
+        );
+
+        CREATE TRIGGER IF NOT EXISTS audio_search_insert AFTER INSERT ON audio BEGIN
+            INSERT INTO video_search (rowid, title, keywords, description, summary)
+            VALUES (new.id, new.title, new.keywords, new.description, new.summary);
+            INSERT INTO segment_search (text, audio_id, start)
+            SELECT trim(json_extract(value, '$.text')), new.id, json_extract(value, '$.start')
+            FROM json_each(CASE WHEN json_valid(new.transcripts) THEN new.transcripts ELSE '[]' END);
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS audio_search_update
+        AFTER UPDATE OF title, keywords, description, summary ON audio BEGIN
+            DELETE FROM video_search WHERE rowid = old.id;
+            INSERT INTO video_search (rowid, title, keywords, description, summary)
+            VALUES (new.id, new.title, new.keywords, new.description, new.summary);
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS audio_search_transcripts
+        AFTER UPDATE OF transcripts ON audio BEGIN
+            DELETE FROM segment_search WHERE audio_id = old.id;
+            INSERT INTO segment_search (text, audio_id, start)
+            SELECT trim(json_extract(value, '$.text')), new.id, json_extract(value, '$.start')
+            FROM json_each(CASE WHEN json_valid(new.transcripts) THEN new.transcripts ELSE '[]' END);
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS audio_search_delete AFTER DELETE ON audio BEGIN
+            DELETE FROM video_search WHERE rowid = old.id;
+            DELETE FROM segment_search WHERE audio_id = old.id;
+        END;",
+    )?;
+
+    // videos stored before the index existed
+    if !exists {
+        connection.execute_batch(
+            "INSERT INTO video_search (rowid, title, keywords, description, summary)
+            SELECT id, title, keywords, description, summary FROM audio;
+            INSERT INTO segment_search (text, audio_id, start)
+            SELECT trim(json_extract(value, '$.text')), audio.id, json_extract(value, '$.start')
+            FROM audio,
+                json_each(CASE WHEN json_valid(audio.transcripts) THEN audio.transcripts ELSE '[]' END);",
+        )?;
+    }
+    Ok(())
+}
+
+// once transcripts live in the segments table the segment index follows that table instead,
+// with the segment id as rowid
+pub fn index_segments(connection: &Connection) -> Result<()> {
+    connection.execute_batch(
+        "DROP TRIGGER IF EXISTS audio_search_insert;
+        DROP TRIGGER IF EXISTS audio_search_transcripts;
+        DROP TRIGGER IF EXISTS audio_search_delete;
+
+        CREATE TRIGGER audio_search_insert AFTER INSERT ON audio BEGIN
+            INSERT INTO video_search (rowid, title, keywords, description, summary)
+            VALUES (new.id, new.title, new.keywords, new.description, new.summary);
+        END;
+
+        CREATE TRIGGER audio_search_delete AFTER DELETE ON audio BEGIN
+            DELETE FROM video_search WHERE rowid = old.id;
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS segment_search_insert AFTER INSERT ON segments BEGIN
+            INSERT INTO segment_search (rowid, text, audio_id, start)
+            VALUES (new.id, trim(new.text), new.audio_id, new.start_time);
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS segment_search_update AFTER UPDATE ON segments BEGIN
+            DELETE FROM segment_search WHERE rowid = old.id;
+            INSERT INTO segment_search (rowid, text, audio_id, start)
+            VALUES (new.id, trim(new.text), new.audio_id, new.start_time);
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS segment_search_delete AFTER DELETE ON segments BEGIN
+            DELETE FROM segment_search WHERE rowid = old.id;
+        END;
+
+        DELETE FROM segment_search;
+        INSERT INTO segment_search (rowid, text, audio_id, start)
+        SELECT id, trim(text), audio_id, start_time FROM segments;",
+    )
+}
+
+// unicode61 only splits on spaces and punctuation, so a word of chinese or japanese text, written
+// without spaces, is never a token of its own; trigrams find any part of the text in any script
+pub fn tokenize_trigrams(connection: &Connection) -> Result<()> {
+    connection.execute_batch(
+        "DROP TABLE IF EXISTS video_search;
+        DROP TABLE IF EXISTS segment_search;
+        CREATE VIRTUAL TABLE video_search USING fts5(
+            title, keywords, description, summary,
+            tokenize = 'trigram remove_diacritics 1'
+        );
+        CREATE VIRTUAL TABLE segment_search USING fts5(
+            text, audio_id UNINDEXED, start UNINDEXED,
+            tokenize = 'trigram remove_diacritics 1'
+        );
+
+        INSERT INTO video_search (rowid, title, keywords, description, summary)
+        SELECT id, title, keywords, description, summary FROM audio;
+        INSERT INTO segment_search (rowid, text, audio_id, start)
+        SELECT id, trim(text), audio_id, start_time FROM segments;",
+    )
+}
+
+// every word of the input as a quoted fts5 string, so user input is never read as query syntax;
+// trigrams match inside words, so a word still being typed finds results already
+fn match_query(terms: &[&str]) -> String {
+    terms
+        .iter()
+        .map(|term| format!("\"{}\"", term.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// a LIKE pattern for `term` anywhere in the text, its own wildcards escaped with a backslash
+fn like_pattern(term: &str) -> String {
+    let term = term
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{term}%")
+}
+
+// what snippet() gives for a match the index did not find: the text around the first term,
+// with every term in it marked; terms are compared ignoring ascii case, as LIKE does
+fn mark_terms(text: &str, terms: &[&str]) -> Option<String> {
+    let chars: Vec<char> = text.chars().collect();
+    let terms: Vec<Vec<char>> = terms.iter().map(|term| term.chars().collect()).collect();
+    let match_at = |index: usize| {
+        terms
+            .iter()
+            .filter(|term| {
+                chars[index..].len() >= term.len()
+                    && chars[index..index + term.len()]
+                        .iter()
+                        .zip(term.iter())
+                        .all(|(a, b)| a.eq_ignore_ascii_case(b))
+            })
+            .map(|term| term.len())
+            .max()
+    };
+    let first = (0..chars.len()).find(|index| match_at(*index).is_some())?;
+    let start = first.saturating_sub(SNIPPET_CHARS / 4);
+    let end = (start + SNIPPET_CHARS).min(chars.len());
+
+    let mut snippet = String::new();
+    if start > 0 {
+        snippet.push('…');
+    }
+    let mut index = start;
+    while index < end {
+        match match_at(index) {
+            Some(len) => {
+                snippet.push(MATCH_START);
+                snippet.extend(&chars[index..(index + len).min(end)]);
+                snippet.push(MATCH_END);
+                index += len;
+            }
+            None => {
+                snippet.push(chars[index]);
+                index += 1;
+            }
+        }
+    }
+    if end < chars.len() {
+        snippet.push('…');
+    }
+    Some(snippet)
+}
+
+fn format_snippet(snippet: &str) -> String {
+    utils::escape_html(snippet)
+        .replace(MATCH_START, "<mark>")
+        .replace(MATCH_END, "</mark>")
+}
+
+pub fn search(connection: &Connection, query: &str, limit: usize) -> Result<Vec<SearchHit>> {
+    let terms: Vec<&str> = query.split_whitespace().collect();
+    if terms.is_empty() {
+        return Ok(Vec::new());
+    }
+    if terms
+        .iter()
+        .any(|term| term.chars().count() < MIN_MATCH_CHARS)
+    {
+        return search_like(connection, &terms, limit);
+    }
+    let query = match_query(&terms);
+    let limit = limit as i64;
+    let mut hits = Vec::new();
+
+    // title matches weigh the most, then keywords, summary and description
+    let mut stmt = connection.prepare(
+        "SELECT audio.id, audio.video_id, audio.title, audio.thumbnail_url,
+            snippet(video_search, -1, ?3, ?4, '…', ?5),
+            bm25(video_search, 10.0, 5.0, 1.0, 2.0) AS score
+        FROM video_search JOIN audio ON audio.id = video_search.rowid
+        WHERE video_search MATCH ?1
+        ORDER BY score LIMIT ?2",
+    )?;
+    let rows = stmt.query_map(
+        params![
+            query,
+            limit,
+            MATCH_START.to_string(),
+            MATCH_END.to_string(),
+            SNIPPET_CHARS
+        ],
+        |row| {
+            Ok(SearchHit {
+                id: row.get(0)?,
+                video_id: row.get(1)?,
+                title: row.get(2)?,
+                thumbnail_url: row.get(3)?,
+                kind: HitKind::Video,
+                snippet: format_snippet(&row.get::<_, String>(4)?),
+                start: None,
+                rank: row.get(5)?,
+            })
+        },
+    )?;
+    for row in rows {
+        hits.push(row?);
+    }
+
+    let mut stmt = connection.prepare(
+        "SELECT audio.id, audio.video_id, audio.title, audio.thumbnail_url,
+            hits.snippet, hits.start, hits.score
+        FROM (
+            SELECT audio_id, start, snippet, score,
+                row_number() OVER (PARTITION BY audio_id ORDER BY score) AS position
+            FROM (
+                SELECT audio_id, start, snippet(segment_search, 0, ?3, ?4, '…', ?6) AS snippet,
+                    rank AS score
+                FROM segment_search WHERE segment_search MATCH ?1
+            )
+        ) hits JOIN audio ON audio.id = hits.audio_id
+        WHERE hits.position <= ?5
+        ORDER BY hits.score LIMIT ?2",
+    )?;
+    let rows = stmt.query_map(
+        params![
+            query,
+            limit,
+            MATCH_START.to_string(),
+            MATCH_END.to_string(),
+            HITS_PER_VIDEO,
+            SNIPPET_CHARS
+        ],
+        |row| {
+            Ok(SearchHit {
+                id: row.get(0)?,
+                video_id: row.get(1)?,
+                title: row.get(2)?,
+                thumbnail_url: row.get(3)?,
+                kind: HitKind::Transcript,
+                snippet: format_snippet(&row.get::<_, String>(4)?),
+                start: row.get(5)?,
+                rank: row.get(6)?,
+            })
+        },
+    )?;
+    for row in rows {
+        hits.push(row?);
+    }
+
+    hits.sort_by(|a, b| a.rank.total_cmp(&b.rank));
+    hits.truncate(limit as usize);
+    Ok(hits)
+}
+
+// a query with a word too short for the index scans the tables with LIKE instead, every word
+// must be found; the hits are not ranked, the newest videos come first
+fn search_like(connection: &Connection, terms: &[&str], limit: usize) -> Result<Vec<SearchHit>> {
+    let patterns: Vec<String> = terms.iter().map(|term| like_pattern(term)).collect();
+    let matches_all = |column: &str| {
+        (1..=patterns.len())
+            .map(|index| format!("{column} LIKE ?{index} ESCAPE '\\'"))
+            .collect::<Vec<_>>()
+            .join(" AND ")
+    };
+    let mut hits = Vec::new();
+
+    let mut stmt = connection.prepare(&format!(
+        "SELECT audio.id, audio.video_id, audio.title, audio.thumbnail_url,
+            video_search.title, video_search.keywords, video_search.description,
+            video_search.summary
+        FROM video_search JOIN audio ON audio.id = video_search.rowid
+        WHERE {}
+        ORDER BY audio.id DESC LIMIT {limit}",
+        matches_all(
+            "(coalesce(video_search.title, '') || ' ' || coalesce(video_search.keywords, '')
+                || ' ' || coalesce(video_search.description, '') || ' '
+                || coalesce(video_search.summary, ''))"
+        )
+    ))?;
+    let rows = stmt.query_map(params_from_iter(&patterns), |row| {
+        let columns = (4..8)
+            .map(|index| row.get::<_, Option<String>>(index))
+            .collect::<Result<Vec<_>>>()?;
+        // the first column with a match, as snippet() picks one
+        let snippet = columns
+            .iter()
+            .flatten()
+            .find_map(|column| mark_terms(column, terms))
+            .unwrap_or_default();
+        Ok(SearchHit {
+            id: row.get(0)?,
+            video_id: row.get(1)?,
+            title: row.get(2)?,
+            thumbnail_url: row.get(3)?,
+            kind: HitKind::Video,
+            snippet: format_snippet(&snippet),
+            start: None,
+            rank: 0.0,
+        })
+    })?;
+    for row in rows {
+        hits.push(row?);
+    }
+
+    let mut stmt = connection.prepare(&format!(
+        "SELECT audio.id, audio.video_id, audio.title, audio.thumbnail_url, hits.text, hits.start
+        FROM (
+            SELECT audio_id, start, text,
+                row_number() OVER (PARTITION BY audio_id ORDER BY start) AS position
+            FROM segment_search WHERE {}
+        ) hits JOIN audio ON audio.id = hits.audio_id
+        WHERE hits.position <= {HITS_PER_VIDEO}
+        ORDER BY audio.id DESC, hits.start LIMIT {limit}",
+        matches_all("text")
+    ))?;
+    let rows = stmt.query_map(params_from_iter(&patterns), |row| {
+        let text: String = row.get(4)?;
+        Ok(SearchHit {
+            id: row.get(0)?,
+            video_id: row.get(1)?,
+            title: row.get(2)?,
+            thumbnail_url: row.get(3)?,
+            kind: HitKind::Transcript,
+            snippet: format_snippet(&mark_terms(&text, terms).unwrap_or(text)),
+            start: row.get(5)?,
+            rank: 0.0,
+        })
+    })?;
+    for row in rows {
+        hits.push(row?);
+    }
+
+    hits.truncate(limit);
+    Ok(hits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::migrations;
+    use super::*;
+    use std::path::Path;
+
+    fn connection() -> Connection {
+        let mut connection = Connection::open_in_memory().unwrap();
+        connection
+            .pragma_update(None, "foreign_keys", true)
+            .unwrap();
+        migrations::migrate(&mut connection, Path::new(":memory:")).unwrap();
+        connection
+            .execute(
+                "INSERT INTO audio (
+                    video_id, title, duration, upload_date, keywords, description, audio_url,
+                    audio_filesize, mime_type, thumbnail_url
+                ) VALUES ('abc', 'Training language models', 0, 0, 'llm', 'a talk', '', 0, '', '')",
+                [],
+            )
+            .unwrap();
+        connection
+    }
+
+    fn insert_segment(connection: &Connection, index: i64, start: f64, text: &str) {
+        connection
+            .execute(
+                "INSERT INTO segments (audio_id, segment_index, start_time, end_time, text, source)
+                SELECT id, ?1, ?2, ?2 + 4.0, ?3, 'whisper' FROM audio WHERE video_id = 'abc'",
+                params![index, start, text],
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn match_query_works() {
+        let terms: Vec<&str> = " rlhf \"reward model ".split_whitespace().collect();
+        assert_eq!(match_query(&terms), "\"rlhf\" \"\"\"reward\" \"model\"");
+        assert_eq!(like_pattern("50%_a\\"), "%50\\%\\_a\\\\%");
+    }
+
+    #[test]
+    fn index_follows_the_tables() {
+        let connection = connection();
+        let hits = search(&connection, "training", 10).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].kind, HitKind::Video);
+        assert_eq!(hits[0].snippet, "<mark>Training</mark> language models");
+
+        insert_segment(&connection, 0, 0.0, " Welcome <everyone>");
+        insert_segment(&connection, 1, 61.5, " then RLHF tunes the model");
+        connection
+            .execute(
+                "UPDATE audio SET summary = 'Covers RLHF in depth' WHERE video_id = 'abc'",
+                [],
+            )
+            .unwrap();
+
+        let hits = search(&connection, "rlh", 10).unwrap();
+        assert_eq!(hits.len(), 2);
+        let transcript = hits
+            .iter()
+            .find(|hit| hit.kind == HitKind::Transcript)
+            .unwrap();
+        assert_eq!(transcript.start, Some(61.5));
+        // trigrams match what was typed, inside the word
+        assert_eq!(transcript.snippet, "then <mark>RLH</mark>F tunes the model");
+        assert_eq!(
+            search(&connection, "everyone", 10).unwrap()[0].snippet,
+            "Welcome &lt;<mark>everyone</mark>&gt;"
+        );
+
+        connection
+            .execute("DELETE FROM segments WHERE segment_index = 1", [])
+            .unwrap();
+        assert_eq!(search(&connection, "tunes", 10).unwrap().len(), 0);
+
+        connection
+            .execute("DELETE FROM audio WHERE video_id = 'abc'", [])
+            .unwrap();
+        assert!(search(&connection, "rlhf", 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn chinese_text_is_found() {
+        let connection = connection();
+        connection
+            .execute(
+                "UPDATE audio SET title = '大语言模型入门' WHERE video_id = 'abc'",
+                [],
+            )
+            .unwrap();
+        insert_segment(&connection, 0, 0.0, "大家好");
+        insert_segment(&connection, 1, 8.0, "我们今天讨论大语言模型的训练");
+
+        // words of two characters are too short for the index
+        let hits = search(&connection, "模型", 10).unwrap();
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].kind, HitKind::Video);
+        assert_eq!(hits[0].snippet, "大语言<mark>模型</mark>入门");
+        assert_eq!(hits[1].start, Some(8.0));
+        assert_eq!(hits[1].snippet, "我们今天讨论大语言<mark>模型</mark>的训练");
+
+        let hits = search(&connection, "语言模型", 10).unwrap();
+        assert_eq!(hits.len(), 2);
+        let transcript = hits
+            .iter()
+            .find(|hit| hit.kind == HitKind::Transcript)
+            .unwrap();
+        assert_eq!(
+            transcript.snippet,
+            "我们今天讨论大<mark>语言模型</mark>的训练"
+        );
+
+        // every word must be found
+        assert_eq!(search(&connection, "模型 训练", 10).unwrap().len(), 1);
+        assert!(search(&connection, "模型 推理", 10).unwrap().is_empty());
+        assert!(search(&connection, "%", 10).unwrap().is_empty());
+    }
+}
@@ -1,10 +1,27 @@
 use serde::Deserialize;
 
+use super::whisper::TokenUsage;
+
 #[derive(Deserialize)]
 struct GeminiResponse {
     candidates: Vec<GeminiCandidate>,
 }
 
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiUsageResponse {
+    usage_metadata: Option<GeminiUsage>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiUsage {
+    #[serde(default)]
+    prompt_token_count: u64,
+    #[serde(default)]
+    candidates_token_count: u64,
+}
+
 #[derive(Deserialize)]
 #[allow(non_snake_case)]
 struct GeminiCandidate {
@@ -36,3 +53,11 @@ pub fn parse_gemini(chunk: &str) -> Result<String, String> {
         Err("No candidates found in Gemini response".to_string())
     }
 }
+
+pub fn parse_gemini_usage(chunk: &str) -> Option<TokenUsage> {
+    let response: GeminiUsageResponse = serde_json::from_str(chunk).ok()?;
+    response.usage_metadata.map(|usage| TokenUsage {
+        prompt_tokens: usage.prompt_token_count,
+        completion_tokens: usage.candidates_token_count,
+    })
+}
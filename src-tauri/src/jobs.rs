@@ -0,0 +1,719 @@
+use regex::Regex;
+use rusqlite::{params, Connection, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::{Emitter, Manager};
+use tokio::sync::{oneshot, Notify, OwnedSemaphorePermit, Semaphore};
+use tokio_util::sync::CancellationToken;
+
+use super::db;
+use super::events;
+use super::setting::AppSettings;
+use super::whisper::{self, SummaryMode};
+
+// background work kept in the database, so what was queued or running when the app closed
+// is picked up again at the next start
+
+// a failed job runs this many times in total before it is given up
+const MAX_ATTEMPTS: i64 = 4;
+const BACKOFF_BASE: i64 = 10;
+const BACKOFF_MAX: i64 = 10 * 60;
+// how long the dispatcher sleeps when nothing is scheduled
+const IDLE_WAIT: u64 = 60;
+// the error of a run stopped by `cancel_job`
+pub const CANCELLED: &str = "cancelled";
+
+tokio::task_local! {
+    // the job the current task runs, events sent while running it carry its id
+    static JOB_ID: i64;
+}
+
+pub fn current_job() -> Option<i64> {
+    JOB_ID.try_with(|id| *id).ok()
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum JobKind {
+    // fetch the transcripts of a video, `video_id` is none until it is created from the url
+    Import {
+        url: String,
+        video_id: Option<i64>,
+    },
+    Summarize {
+        video_id: i64,
+        language: String,
+        include_chat: bool,
+        mode: SummaryMode,
+    },
+}
+
+impl JobKind {
+    fn video_id(&self) -> Option<i64> {
+        match self {
+            JobKind::Import { video_id, .. } => *video_id,
+            JobKind::Summarize { video_id, .. } => Some(*video_id),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Pending,
+    Running,
+    Done,
+    Failed,
+    Cancelled,
+}
+
+impl JobStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            JobStatus::Pending => "pending",
+            JobStatus::Running => "running",
+            JobStatus::Done => "done",
+            JobStatus::Failed => "failed",
+            JobStatus::Cancelled => "cancelled",
+        }
+    }
+
+    fn parse(value: &str) -> Option<JobStatus> {
+        [
+            JobStatus::Pending,
+            JobStatus::Running,
+            JobStatus::Done,
+            JobStatus::Failed,
+            JobStatus::Cancelled,
+        ]
+        .into_iter()
+        .find(|status| status.as_str() == value)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Job {
+    pub id: i64,
+    pub kind: JobKind,
+    pub status: JobStatus,
+    pub attempts: i64,
+    pub max_attempts: i64,
+    // unix seconds, a pending job does not start before it
+    pub run_after: i64,
+    pub error: Option<String>,
+    pub created_at: i64,
+}
+
+pub fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+// seconds to wait before the next attempt, doubling from 10 seconds up to 10 minutes
+pub fn backoff(attempts: i64) -> i64 {
+    let exponent = (attempts - 1).clamp(0, 16) as u32;
+    (BACKOFF_BASE * 2_i64.pow(exponent)).min(BACKOFF_MAX)
+}
+
+fn read_job(row: &rusqlite::Row) -> Result<Job> {
+    let invalid = |index, value: String| {
+        rusqlite::Error::InvalidColumnType(index, value, rusqlite::types::Type::Text)
+    };
+    let payload: String = row.get(1)?;
+    let status: String = row.get(2)?;
+    Ok(Job {
+        id: row.get(0)?,
+        kind: serde_json::from_str(&payload).map_err(|_| invalid(1, payload.clone()))?,
+        status: JobStatus::parse(&status).ok_or_else(|| invalid(2, status.clone()))?,
+        attempts: row.get(3)?,
+        max_attempts: row.get(4)?,
+        run_after: row.get(5)?,
+        error: row.get(6)?,
+        created_at: row.get(7)?,
+    })
+}
+
+const JOB_COLUMNS: &str =
+    "id, payload, status, attempts, max_attempts, run_after, error, created_at";
+
+pub fn enqueue(connection: &Connection, kind: &JobKind, now: i64) -> Result<i64> {
+    let payload = serde_json::to_string(kind)
+        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(e.into()))?;
+    connection.execute(
+        "INSERT INTO jobs (payload, audio_id, max_attempts, run_after) VALUES (?1, ?2, ?3, ?4)",
+        params![payload, kind.video_id(), MAX_ATTEMPTS, now],
+    )?;
+    Ok(connection.last_insert_rowid())
+}
+
+// mark every pending job that is due as running and return them, oldest first
+pub fn claim_due(connection: &Connection, now: i64) -> Result<Vec<Job>> {
+    let mut stmt = connection.prepare(&format!(
+        "UPDATE jobs SET status = 'running', attempts = attempts + 1,
+            updated_at = strftime('%s', 'now')
+        WHERE status = 'pending' AND run_after <= ?1
+        RETURNING {JOB_COLUMNS}"
+    ))?;
+    let mut jobs = stmt
+        .query_map(params![now], read_job)?
+        .collect::<Result<Vec<Job>>>()?;
+    jobs.sort_by_key(|job| job.id);
+    Ok(jobs)
+}
+
+// when the next pending job is due, none when there is nothing left to run
+pub fn next_due(connection: &Connection) -> Result<Option<i64>> {
+    connection.query_row(
+        "SELECT min(run_after) FROM jobs WHERE status = 'pending'",
+        [],
+        |row| row.get(0),
+    )
+}
+
+// a job that created its video keeps the id, a retry must not create it again
+pub fn set_kind(connection: &Connection, id: i64, kind: &JobKind) -> Result<()> {
+    let payload = serde_json::to_string(kind)
+        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(e.into()))?;
+    connection.execute(
+        "UPDATE jobs SET payload = ?2, audio_id = ?3 WHERE id = ?1",
+        params![id, payload, kind.video_id()],
+    )?;
+    Ok(())
+}
+
+// what the failed jobs of a video were asked to do, the latest first, so a retry runs it again
+pub fn failed_kinds(connection: &Connection, video_id: i64) -> Result<Vec<JobKind>> {
+    let mut stmt = connection.prepare(&format!(
+        "SELECT {JOB_COLUMNS} FROM jobs WHERE audio_id = ?1 AND status = 'failed' ORDER BY id DESC"
+    ))?;
+    let jobs = stmt.query_map(params![video_id], read_job)?;
+    jobs.map(|job| job.map(|job| job.kind)).collect()
+}
+
+// a job that ran to the end, `status` is done or cancelled
+pub fn finish(connection: &Connection, id: i64, status: JobStatus) -> Result<()> {
+    connection.execute(
+        "UPDATE jobs SET status = ?2, error = NULL, updated_at = strftime('%s', 'now')
+        WHERE id = ?1",
+        params![id, status.as_str()],
+    )?;
+    Ok(())
+}
+
+// a job that has not started yet is dropped from the queue; returns false when it already has
+pub fn cancel_pending(connection: &Connection, id: i64) -> Result<bool> {
+    let changed = connection.execute(
+        "UPDATE jobs SET status = 'cancelled', updated_at = strftime('%s', 'now')
+        WHERE id = ?1 AND status = 'pending'",
+        params![id],
+    )?;
+    Ok(changed > 0)
+}
+
+// errors that may go away on their own: the network, a rate limit or a server error;
+// anything else, a missing setting or an unsupported url, would fail the same way again
+pub fn is_retryable(error: &str) -> bool {
+    const TRANSIENT: [&str; 9] = [
+        "error sending request",
+        "timed out",
+        "connection",
+        "dns error",
+        "error decoding response body",
+        "unexpected eof",
+        "too many requests",
+        "rate limit",
+        "temporarily unavailable",
+    ];
+    let status = Regex::new(r"\b(429|5\d\d)\b").unwrap();
+    let error = error.to_lowercase();
+    TRANSIENT.iter().any(|text| error.contains(text)) || status.is_match(&error)
+}
+
+// schedule the next attempt after a backoff, or give up once the attempts are used up
+// or the error is one another attempt would not get past
+pub fn fail(
+    connection: &Connection,
+    id: i64,
+    error: &str,
+    retryable: bool,
+    now: i64,
+) -> Result<JobStatus> {
+    let (attempts, max_attempts): (i64, i64) = connection.query_row(
+        "SELECT attempts, max_attempts FROM jobs WHERE id = ?1",
+        params![id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    let (status, run_after) = if retryable && attempts < max_attempts {
+        (JobStatus::Pending, now + backoff(attempts))
+    } else {
+        (JobStatus::Failed, now)
+    };
+    connection.execute(
+        "UPDATE jobs SET status = ?2, run_after = ?3, error = ?4,
+            updated_at = strftime('%s', 'now')
+        WHERE id = ?1",
+        params![id, status.as_str(), run_after, error],
+    )?;
+    Ok(status)
+}
+
+// at startup: jobs cut off while running go back to the queue, an interrupted attempt does not
+// count; finished and cancelled jobs are dropped
+pub fn resume(connection: &Connection) -> Result<usize> {
+    connection.execute("DELETE FROM jobs WHERE status IN ('done', 'cancelled')", [])?;
+    connection.execute(
+        "UPDATE jobs SET status = 'pending', attempts = max(attempts - 1, 0)
+        WHERE status = 'running'",
+        [],
+    )
+}
+
+// unfinished and failed jobs, newest first
+pub fn list(connection: &Connection) -> Result<Vec<Job>> {
+    let mut stmt = connection.prepare(&format!(
+        "SELECT {JOB_COLUMNS} FROM jobs WHERE status != 'done' ORDER BY id DESC LIMIT 200"
+    ))?;
+    let job_iter = stmt.query_map([], read_job)?;
+    job_iter.collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pool {
+    Downloads,
+    Transcriptions,
+    Llm,
+}
+
+type Waiters = HashMap<i64, Vec<oneshot::Sender<Result<(), String>>>>;
+
+// each pool limits how many jobs do that kind of work at once; a job only holds a permit
+// while it downloads, transcribes or waits on the llm
+pub struct JobQueue {
+    downloads: Arc<Semaphore>,
+    transcriptions: Arc<Semaphore>,
+    llm: Arc<Semaphore>,
+    paused: AtomicBool,
+    wake: Notify,
+    // callers waiting for a job to finish for good
+    waiters: Mutex<Waiters>,
+    // one per running job, taken together with the claim so a cancel never falls in between
+    tokens: Mutex<HashMap<i64, CancellationToken>>,
+}
+
+impl JobQueue {
+    pub fn new(settings: Option<&AppSettings>) -> JobQueue {
+        let size = |value: Option<usize>, default: usize| value.unwrap_or(default).clamp(1, 16);
+        let settings = settings.cloned().unwrap_or_default();
+        JobQueue {
+            downloads: Arc::new(Semaphore::new(size(settings.download_workers, 2))),
+            transcriptions: Arc::new(Semaphore::new(size(settings.transcription_workers, 1))),
+            llm: Arc::new(Semaphore::new(size(settings.llm_workers, 2))),
+            paused: AtomicBool::new(false),
+            wake: Notify::new(),
+            waiters: Mutex::new(HashMap::new()),
+            tokens: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn pool(&self, pool: Pool) -> Arc<Semaphore> {
+        match pool {
+            Pool::Downloads => self.downloads.clone(),
+            Pool::Transcriptions => self.transcriptions.clone(),
+            Pool::Llm => self.llm.clone(),
+        }
+    }
+
+    fn resolve(&self, id: i64, result: Result<(), String>) {
+        let senders = match self.waiters.lock() {
+            Ok(mut waiters) => waiters.remove(&id).unwrap_or_default(),
+            Err(_) => return,
+        };
+        for sender in senders {
+            let _ = sender.send(result.clone());
+        }
+    }
+}
+
+// await `run` unless `cancel` fires first, the run is dropped then and the error is `CANCELLED`
+pub async fn until_cancelled<T, E: ToString>(
+    cancel: &CancellationToken,
+    run: impl Future<Output = Result<T, E>>,
+) -> Result<T, String> {
+    tokio::select! {
+        _ = cancel.cancelled() => Err(CANCELLED.to_string()),
+        result = run => result.map_err(|e| e.to_string()),
+    }
+}
+
+// wait for a free slot in `pool`, the slot is given back when the permit is dropped
+pub async fn permit(app: &tauri::AppHandle, pool: Pool) -> Result<OwnedSemaphorePermit, String> {
+    let semaphore = app.state::<JobQueue>().pool(pool);
+    semaphore.acquire_owned().await.map_err(|e| e.to_string())
+}
+
+// the event sent to the ui whenever the queue changes, with every job in it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JobsEvent {
+    pub paused: bool,
+    pub jobs: Vec<Job>,
+}
+
+fn emit_jobs(app: &tauri::AppHandle) {
+    let jobs = match db::get_jobs(app.state()) {
+        Ok(jobs) => jobs,
+        Err(e) => {
+            eprintln!("failed to list jobs: {e}");
+            return;
+        }
+    };
+    let event = JobsEvent {
+        paused: jobs_paused(app.clone()),
+        jobs,
+    };
+    let _ = app.emit(events::JOBS, event);
+}
+
+pub fn add(app: &tauri::AppHandle, kind: &JobKind) -> Result<i64, String> {
+    let id = db::enqueue_job(app.state(), kind)?;
+    app.state::<JobQueue>().wake.notify_one();
+    emit_jobs(app);
+    Ok(id)
+}
+
+// queue a job and wait until it is done or has failed its last attempt
+pub async fn run(app: &tauri::AppHandle, kind: JobKind) -> Result<(), String> {
+    let (sender, receiver) = oneshot::channel();
+    let id = db::enqueue_job(app.state(), &kind)?;
+    {
+        let queue = app.state::<JobQueue>();
+        let mut waiters = queue.waiters.lock().map_err(|e| e.to_string())?;
+        waiters.entry(id).or_default().push(sender);
+    }
+    app.state::<JobQueue>().wake.notify_one();
+    emit_jobs(app);
+    receiver
+        .await
+        .map_err(|_| "the job queue stopped".to_string())?
+}
+
+async fn execute(
+    app: &tauri::AppHandle,
+    job: &Job,
+    cancel: &CancellationToken,
+) -> Result<(), String> {
+    match &job.kind {
+        JobKind::Import { url, video_id } => {
+            let id = match video_id {
+                Some(id) => *id,
+                None => {
+                    let id = crate::add_video(app, url)?;
+                    let kind = JobKind::Import {
+                        url: url.clone(),
+                        video_id: Some(id),
+                    };
+                    db::set_job_kind(app.state(), job.id, &kind)?;
+                    id
+                }
+            };
+            crate::import_video(app, id, cancel).await?;
+        }
+        JobKind::Summarize {
+            video_id,
+            language,
+            include_chat,
+            mode,
+        } => {
+            whisper::summarize_video(app, *video_id, language, *include_chat, *mode, cancel)
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+async fn work(app: tauri::AppHandle, job: Job, cancel: CancellationToken) {
+    let result = JOB_ID.scope(job.id, execute(&app, &job, &cancel)).await;
+    let queue = app.state::<JobQueue>();
+    if let Ok(mut tokens) = queue.tokens.lock() {
+        tokens.remove(&job.id);
+    }
+    match result {
+        Ok(()) => {
+            if let Err(e) = db::finish_job(app.state(), job.id, JobStatus::Done) {
+                eprintln!("failed to finish job {}: {e}", job.id);
+            }
+            queue.resolve(job.id, Ok(()));
+        }
+        // a cancelled job is not retried
+        Err(error) if cancel.is_cancelled() => {
+            if let Err(e) = db::finish_job(app.state(), job.id, JobStatus::Cancelled) {
+                eprintln!("failed to cancel job {}: {e}", job.id);
+            }
+            queue.resolve(job.id, Err(error));
+        }
+        Err(error) => match db::fail_job(app.state(), job.id, &error, is_retryable(&error)) {
+            Ok(JobStatus::Pending) => {}
+            Ok(_) => queue.resolve(job.id, Err(error)),
+            Err(e) => {
+                eprintln!("failed to reschedule job {}: {e}", job.id);
+                queue.resolve(job.id, Err(error));
+            }
+        },
+    }
+    // a retry may now be the next job due
+    queue.wake.notify_one();
+    emit_jobs(&app);
+}
+
+fn claim(app: &tauri::AppHandle) -> Result<Vec<(Job, CancellationToken)>, String> {
+    let queue = app.state::<JobQueue>();
+    let mut tokens = queue.tokens.lock().map_err(|e| e.to_string())?;
+    let jobs = db::claim_jobs(app.state())?;
+    Ok(jobs
+        .into_iter()
+        .map(|job| {
+            let cancel = CancellationToken::new();
+            tokens.insert(job.id, cancel.clone());
+            (job, cancel)
+        })
+        .collect())
+}
+
+// start every job that is due, then sleep until the next one is or a job is added
+pub async fn dispatch(app: tauri::AppHandle) {
+    let queue = app.state::<JobQueue>();
+    loop {
+        let mut wait = IDLE_WAIT;
+        if !queue.paused.load(Ordering::SeqCst) {
+            match claim(&app) {
+                Ok(jobs) => {
+                    if !jobs.is_empty() {
+                        emit_jobs(&app);
+                    }
+                    for (job, cancel) in jobs {
+                        tauri::async_runtime::spawn(work(app.clone(), job, cancel));
+                    }
+                }
+                Err(e) => eprintln!("failed to claim jobs: {e}"),
+            }
+            if let Ok(Some(run_after)) = db::next_job_due(app.state()) {
+                wait = (run_after - now()).clamp(1, IDLE_WAIT as i64) as u64;
+            }
+        }
+        tokio::select! {
+            _ = queue.wake.notified() => {}
+            _ = tokio::time::sleep(Duration::from_secs(wait)) => {}
+        }
+    }
+}
+
+// stop a running job where it is, or take a queued one off the queue
+#[tauri::command(rename_all = "snake_case")]
+pub fn cancel_job(app: tauri::AppHandle, job_id: i64) -> Result<(), String> {
+    let queue = app.state::<JobQueue>();
+    {
+        let tokens = queue.tokens.lock().map_err(|e| e.to_string())?;
+        if let Some(cancel) = tokens.get(&job_id) {
+            cancel.cancel();
+            return Ok(());
+        }
+        if !db::cancel_pending_job(app.state(), job_id)? {
+            return Err("this job is not queued or running".to_string());
+        }
+    }
+    queue.resolve(job_id, Err(CANCELLED.to_string()));
+    emit_jobs(&app);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_jobs(app: tauri::AppHandle) -> Result<Vec<Job>, String> {
+    db::get_jobs(app.state())
+}
+
+// running jobs finish, nothing new starts until the queue is resumed
+#[tauri::command]
+pub fn pause_jobs(app: tauri::AppHandle) -> Result<(), String> {
+    app.state::<JobQueue>().paused.store(true, Ordering::SeqCst);
+    emit_jobs(&app);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn resume_jobs(app: tauri::AppHandle) -> Result<(), String> {
+    let queue = app.state::<JobQueue>();
+    queue.paused.store(false, Ordering::SeqCst);
+    queue.wake.notify_one();
+    emit_jobs(&app);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn jobs_paused(app: tauri::AppHandle) -> bool {
+    app.state::<JobQueue>().paused.load(Ordering::SeqCst)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::migrations;
+    use super::*;
+    use std::path::Path;
+
+    fn connection() -> Connection {
+        let mut connection = Connection::open_in_memory().unwrap();
+        connection
+            .pragma_update(None, "foreign_keys", true)
+            .unwrap();
+        migrations::migrate(&mut connection, Path::new(":memory:")).unwrap();
+        connection
+            .execute(
+                "INSERT INTO audio (
+                    video_id, title, duration, upload_date, audio_url, audio_filesize,
+                    mime_type, thumbnail_url
+                ) VALUES ('abc', 'abc', 0, 0, '', 0, '', '')",
+                [],
+            )
+            .unwrap();
+        connection
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        assert_eq!(backoff(1), 10);
+        assert_eq!(backoff(2), 20);
+        assert_eq!(backoff(4), 80);
+        assert_eq!(backoff(20), BACKOFF_MAX);
+    }
+
+    #[test]
+    fn failed_jobs_are_retried_then_given_up() {
+        let connection = connection();
+        let kind = JobKind::Import {
+            url: "https://www.youtube.com/watch?v=abc".to_string(),
+            video_id: None,
+        };
+        let id = enqueue(&connection, &kind, 100).unwrap();
+        assert!(claim_due(&connection, 99).unwrap().is_empty());
+        assert_eq!(next_due(&connection).unwrap(), Some(100));
+
+        let jobs = claim_due(&connection, 100).unwrap();
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0].kind, kind);
+        assert_eq!(jobs[0].attempts, 1);
+        // a claimed job is not handed out twice
+        assert!(claim_due(&connection, 100).unwrap().is_empty());
+
+        let kind = JobKind::Import {
+            url: "https://www.youtube.com/watch?v=abc".to_string(),
+            video_id: Some(1),
+        };
+        set_kind(&connection, id, &kind).unwrap();
+        assert_eq!(
+            fail(&connection, id, "timed out", true, 100).unwrap(),
+            JobStatus::Pending
+        );
+        assert_eq!(next_due(&connection).unwrap(), Some(110));
+        for attempt in 2..=MAX_ATTEMPTS {
+            let now = next_due(&connection).unwrap().unwrap();
+            let job = claim_due(&connection, now).unwrap().remove(0);
+            assert_eq!(job.attempts, attempt);
+            assert_eq!(job.kind, kind);
+            fail(&connection, id, "timed out", true, now).unwrap();
+        }
+        let job = list(&connection).unwrap().remove(0);
+        assert_eq!(job.status, JobStatus::Failed);
+        assert_eq!(job.error.as_deref(), Some("timed out"));
+        assert_eq!(next_due(&connection).unwrap(), None);
+    }
+
+    #[test]
+    fn permanent_errors_fail_at_once() {
+        assert!(is_retryable(
+            "error sending request for url (https://api.openai.com)"
+        ));
+        assert!(is_retryable("API error 429 Too Many Requests - slow down"));
+        assert!(is_retryable("API error 503 Service Unavailable - "));
+        assert!(!is_retryable("no api settings found"));
+        assert!(!is_retryable("no extractor supports this url"));
+        assert!(!is_retryable("API error 401 Unauthorized - invalid key"));
+
+        let connection = connection();
+        let kind = JobKind::Import {
+            url: "https://example.com".to_string(),
+            video_id: None,
+        };
+        let id = enqueue(&connection, &kind, 0).unwrap();
+        claim_due(&connection, 0).unwrap();
+        assert_eq!(
+            fail(&connection, id, "no api settings found", false, 0).unwrap(),
+            JobStatus::Failed
+        );
+        assert_eq!(next_due(&connection).unwrap(), None);
+    }
+
+    #[test]
+    fn failed_jobs_keep_their_parameters() {
+        let connection = connection();
+        let summarize = JobKind::Summarize {
+            video_id: 1,
+            language: "de".to_string(),
+            include_chat: true,
+            mode: SummaryMode::Highlights,
+        };
+        let import = JobKind::Import {
+            url: String::new(),
+            video_id: Some(1),
+        };
+        let first = enqueue(&connection, &summarize, 0).unwrap();
+        let second = enqueue(&connection, &import, 0).unwrap();
+        let done = enqueue(&connection, &summarize, 0).unwrap();
+        claim_due(&connection, 0).unwrap();
+        fail(&connection, first, "API error 401 Unauthorized", false, 0).unwrap();
+        fail(&connection, second, "no extractor found", false, 0).unwrap();
+        finish(&connection, done, JobStatus::Done).unwrap();
+
+        assert_eq!(
+            failed_kinds(&connection, 1).unwrap(),
+            vec![import, summarize]
+        );
+        assert!(failed_kinds(&connection, 2).unwrap().is_empty());
+    }
+
+    #[test]
+    fn running_jobs_resume_at_startup() {
+        let connection = connection();
+        let summarize = JobKind::Summarize {
+            video_id: 1,
+            language: "auto".to_string(),
+            include_chat: false,
+            mode: SummaryMode::Highlights,
+        };
+        let done = enqueue(&connection, &summarize, 0).unwrap();
+        let running = enqueue(&connection, &summarize, 0).unwrap();
+        claim_due(&connection, 0).unwrap();
+        finish(&connection, done, JobStatus::Done).unwrap();
+        let cancelled = enqueue(&connection, &summarize, 0).unwrap();
+        assert!(cancel_pending(&connection, cancelled).unwrap());
+        assert!(!cancel_pending(&connection, running).unwrap());
+
+        assert_eq!(resume(&connection).unwrap(), 1);
+        let count: i64 = connection
+            .query_row(
+                "SELECT count(*) FROM jobs WHERE id IN (?1, ?2)",
+                [done, cancelled],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(count, 0);
+        let jobs = list(&connection).unwrap();
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0].id, running);
+        assert_eq!(jobs[0].status, JobStatus::Pending);
+        assert_eq!(jobs[0].attempts, 0);
+    }
+}
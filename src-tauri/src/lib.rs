@@ -1,85 +1,283 @@
 pub mod webvtt;
 use dotenv::dotenv;
-use tauri::{Emitter, Manager};
+use tauri::Manager;
+mod cache;
+mod comments;
 mod db;
+mod events;
+mod export;
+mod flashcards;
+mod fulltext;
 mod gemini;
+mod jobs;
+mod library;
+mod listing;
+mod local_file;
+mod markdown;
+mod migrations;
+mod podcast;
+mod report;
+mod search;
 mod setting;
+mod status;
+mod subtitles;
+mod translate;
 mod utils;
 mod whisper;
-use tube_rs::YoutubeAudio;
+use events::{Change, Phase};
+use jobs::{JobKind, Pool};
+use status::Stage;
+use std::path::Path;
+use tokio_util::sync::CancellationToken;
+use tube_rs::{CaptionTrack, ExtractorRegistry, YoutubeAudio};
+use whisper::Segment;
 
+const ALREADY_IN_LIBRARY: &str = "this video is already in the library";
+
+// queue the import and wait for it; `input_id` is -1 for a url that is not in the library yet
 #[tauri::command(rename_all = "snake_case")]
 async fn run_yt(app: tauri::AppHandle, url: &str, input_id: i64) -> Result<(), String> {
-    let mut _id = input_id;
-    let youtube_audio = YoutubeAudio::new(
-        setting::get_proxy(&app).as_deref(),
-        setting::get_tube_api_url(&app),
+    let video_id = (input_id != -1).then_some(input_id);
+    jobs::run(
+        &app,
+        JobKind::Import {
+            url: url.to_string(),
+            video_id,
+        },
+    )
+    .await
+}
+
+// add a url to the library before its metadata is fetched; returns the id in database
+fn add_video(app: &tauri::AppHandle, url: &str) -> Result<i64, String> {
+    let id = db::create_pending_video(app.state(), url)?;
+    events::library_changed(app, id, Change::Added)?;
+    Ok(id)
+}
+
+// store the transcripts of video `input_id`, fetching its metadata first when it was added from a url
+async fn import_video(
+    app: &tauri::AppHandle,
+    _id: i64,
+    cancel: &CancellationToken,
+) -> Result<i64, String> {
+    let video_id = db::get_video_id_with_id(app.state(), _id)?;
+    // podcast episodes are imported from a feed rather than a page url
+    if video_id.starts_with("podcast:") {
+        podcast::transcribe_episode(app, _id, cancel).await?;
+        return Ok(_id);
+    }
+    if video_id.starts_with("local:") {
+        local_file::transcribe_local_file(app, _id, cancel).await?;
+        return Ok(_id);
+    }
+    let registry = ExtractorRegistry::new(
+        setting::get_proxy(app).as_deref(),
+        setting::get_tube_api_url(app),
     );
-    if _id == -1 {
-        let audio_data = match youtube_audio.get_video_info(url).await {
-            Some(data) => data,
-            None => return Err("failed to parse audio info".to_string()),
-        };
-        _id = db::create_video(app.state(), audio_data)?;
-        app.emit("state", "update video")
-            .map_err(|e| e.to_string())?;
+    let pending_url = video_id.strip_prefix(db::PENDING_PREFIX);
+    let extractor = match pending_url {
+        Some(url) => registry
+            .find(url)
+            .ok_or("no extractor supports this url".to_string())?,
+        None => registry
+            .find_for_video_id(&video_id)
+            .ok_or(format!("no extractor found for {video_id}"))?,
+    };
+    let first = match pending_url {
+        Some(_) => Stage::FetchingMetadata,
+        None => Stage::Downloading,
     };
-    if let Ok((Some(lang), Some(url))) = db::get_caption_with_id(app.state(), _id) {
-        let subtitles = youtube_audio
-            .download_caption(&url, &lang)
-            .await
-            .map_err(|e| e.to_string())?;
-        app.emit("stream", "[start]".to_string())
-            .map_err(|e| e.to_string())?;
-        for subtitle in &subtitles {
-            app.emit("stream", subtitle.text.clone())
-                .map_err(|e| e.to_string())?
+    let result = status::track(app, _id, first, cancel, async {
+        if let Some(url) = pending_url {
+            let audio_data = jobs::until_cancelled(cancel, async {
+                extractor
+                    .fetch_metadata(url)
+                    .await
+                    .map_err(|e| e.to_string())
+            })
+            .await?;
+            if !db::fill_video(app.state(), _id, audio_data)? {
+                return Err(ALREADY_IN_LIBRARY.to_string());
+            }
+            events::library_changed(app, _id, Change::Updated)?;
+            status::update(app, _id, Stage::Downloading)?;
         }
-        app.emit("stream", "[end]".to_string())
-            .map_err(|e| e.to_string())?;
 
-        let segments = utils::transform_subtitles_to_segments(subtitles);
-        let transcripts = serde_json::to_string(&segments).unwrap();
-        db::update_video(app.state(), _id, "transcripts".to_string(), transcripts)?;
-        return Ok(());
+        let permit = jobs::until_cancelled(cancel, jobs::permit(app, Pool::Downloads)).await?;
+        if let Ok((Some(lang), Some(url))) = db::get_caption_with_id(app.state(), _id) {
+            let subtitles =
+                jobs::until_cancelled(cancel, extractor.fetch_caption(&CaptionTrack { lang, url }))
+                    .await?;
+            drop(permit);
+            let segments = utils::transform_subtitles_to_segments(subtitles);
+            return save_subtitles(app, _id, segments).await;
+        };
+
+        let (audio_url, audio_filesize, mime_type, duration) =
+            db::get_audio_url_with_id(app.state(), _id)?;
+        let dir = cache::job_dir(app, _id)?;
+        let audio_path = dir
+            .path()
+            .join(format!("audio.{}", utils::audio_extension(&mime_type)));
+        let result = async {
+            // a failed run left its download for the retry
+            if !audio_path.exists() {
+                let partial = cache::partial_path(&audio_path);
+                let download = extractor.download_audio(&audio_url, audio_filesize, &partial);
+                jobs::until_cancelled(cancel, download).await?;
+                std::fs::rename(&partial, &audio_path).map_err(|e| e.to_string())?;
+            }
+            drop(permit);
+            transcribe_audio_file(app, _id, &audio_path, duration, dir.path(), cancel).await
+        }
+        .await;
+        dir.close(&result, cancel).await;
+        result
+    })
+    .await;
+    // the url led to a video the library has, the row added for it goes away
+    if let Err(error) = &result {
+        if error == ALREADY_IN_LIBRARY {
+            db::delete_video(app.state(), _id)?;
+            events::library_changed(app, _id, Change::Removed)?;
+        }
+    }
+    result.map(|_| _id)
+}
+
+// queue a failed video again from the stage it stopped at
+#[tauri::command(rename_all = "snake_case")]
+async fn retry_video(app: tauri::AppHandle, video_id: i64) -> Result<(), String> {
+    let stage = db::get_failed_stage(app.state(), video_id)?
+        .ok_or("this video has not failed".to_string())?;
+    // the failed job runs again as it was asked for, with its language and mode
+    let summarizing = stage == Stage::Summarizing;
+    let failed = db::get_failed_jobs(app.state(), video_id)?
+        .into_iter()
+        .find(|kind| matches!(kind, JobKind::Summarize { .. }) == summarizing);
+    let kind = match failed {
+        Some(kind) => kind,
+        // the run did not go through the queue, as the search summary runs its imports itself
+        None if summarizing => {
+            // the language of the last summary, if there was one
+            let language = db::get_summaries(app.state(), video_id)?
+                .into_iter()
+                .next()
+                .map_or("auto".to_string(), |summary| summary.language);
+            JobKind::Summarize {
+                video_id,
+                language,
+                include_chat: false,
+                mode: whisper::SummaryMode::Standard,
+            }
+        }
+        None => JobKind::Import {
+            url: String::new(),
+            video_id: Some(video_id),
+        },
     };
+    jobs::add(&app, &kind)?;
+    Ok(())
+}
 
-    let (audio_url, audio_filesize, mime_type, duration) =
-        db::get_audio_url_with_id(app.state(), _id)?;
-    let cache_dir = app.path().cache_dir().unwrap();
-    let file_path = if mime_type.contains("webm") {
-        "temp.webm"
-    } else {
-        "temp.m4a"
+// stream existing captions to the ui and store them as the transcripts
+async fn save_subtitles(
+    app: &tauri::AppHandle,
+    id: i64,
+    segments: Vec<Segment>,
+) -> Result<(), String> {
+    status::update(app, id, Stage::Transcribing)?;
+    let sender = events::Sender::new(app, events::TRANSCRIPT, Some(id), Phase::Captions);
+    let stream = async {
+        segments
+            .iter()
+            .try_for_each(|segment| sender.delta(&segment.text))
     };
-    let mut temp_path = cache_dir.join("newscenter").join(file_path);
-    youtube_audio
-        .download_audio(&audio_url, audio_filesize, &temp_path)
-        .await
-        .map_err(|e| e.to_string())?;
+    sender.run(stream).await?;
+
+    db::save_segments(app.state(), id, &segments, "captions")
+}
 
+// split a downloaded audio file when it is over the whisper upload limit, then transcribe it;
+// the chunks are written to `work_dir`, the directory of the run
+async fn transcribe_audio_file(
+    app: &tauri::AppHandle,
+    id: i64,
+    audio_path: &Path,
+    duration: u64,
+    work_dir: &Path,
+    cancel: &CancellationToken,
+) -> Result<(), String> {
+    // other platforms may not report the size up front, trust the downloaded file
+    let audio_filesize = std::fs::metadata(audio_path)
+        .map(|metadata| metadata.len())
+        .map_err(|e| e.to_string())?;
+    let _permit = jobs::until_cancelled(cancel, jobs::permit(app, Pool::Transcriptions)).await?;
+    let mut temp_path = audio_path.to_path_buf();
+    let mut chunk_duration = 0;
     if audio_filesize > 22 * 1024 * 1024 {
-        let output_dir = cache_dir.join("chunk");
-        let bytes_per_second = audio_filesize as f64 / duration as f64;
-        let chunk_duration = ((20 * 1024 * 1024) as f64 / bytes_per_second) as i64;
+        status::update(app, id, Stage::Splitting)?;
+        let output_dir = work_dir.join("chunks");
+        chunk_duration = if duration > 0 {
+            let bytes_per_second = audio_filesize as f64 / duration as f64;
+            ((20 * 1024 * 1024) as f64 / bytes_per_second) as i64
+        } else {
+            10 * 60
+        };
 
         let auido_splitter = ffmpeg_audio::AudioSplitter::new(chunk_duration);
-        auido_splitter
-            .split(&temp_path, &output_dir)
-            .map_err(|e| e.to_string())?;
+        let split = auido_splitter.split_until(audio_path, &output_dir, || cancel.is_cancelled());
+        if let Err(error) = split {
+            let _ = std::fs::remove_dir_all(&output_dir);
+            return Err(match cancel.is_cancelled() {
+                true => jobs::CANCELLED.to_string(),
+                false => error.to_string(),
+            });
+        }
 
         temp_path = output_dir;
     };
 
-    app.emit("stream", "[start]".to_string())
-        .map_err(|e| e.to_string())?;
-    let segments = whisper::trancript(&app, &temp_path).await?;
-    app.emit("stream", "[end]".to_string())
-        .map_err(|e| e.to_string())?;
-    let transcripts = serde_json::to_string(&segments).unwrap();
-    db::update_video(app.state(), _id, "transcripts".to_string(), transcripts)?;
+    status::update(app, id, Stage::Transcribing)?;
+    let sender = events::Sender::new(app, events::TRANSCRIPT, Some(id), Phase::Transcribing);
+    let segments = sender
+        .run(whisper::trancript(
+            app,
+            &sender,
+            &temp_path,
+            chunk_duration,
+            cancel,
+        ))
+        .await;
+    if segments.is_err() && temp_path.is_dir() {
+        // a retry splits the kept download again
+        let _ = std::fs::remove_dir_all(&temp_path);
+    }
+    let segments = segments?;
+    db::save_segments(app.state(), id, &segments, "whisper")
+}
 
-    Ok(())
+#[tauri::command(rename_all = "snake_case")]
+async fn fetch_live_chat(
+    app: tauri::AppHandle,
+    video_id: i64,
+    max_messages: Option<usize>,
+) -> Result<usize, String> {
+    let youtube_id = db::get_youtube_id_with_id(app.state(), video_id)?;
+    let youtube_audio = YoutubeAudio::new(
+        setting::get_proxy(&app).as_deref(),
+        setting::get_tube_api_url(&app),
+    );
+    let messages = youtube_audio
+        .get_live_chat_replay(
+            &format!("https://www.youtube.com/watch?v={youtube_id}"),
+            max_messages.unwrap_or(20_000),
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+    db::save_live_chat(app.state(), video_id, &messages)?;
+    Ok(messages.len())
 }
 
 #[tauri::command]
@@ -101,14 +299,70 @@ pub fn run() {
             setting::get_config_path(app.handle());
             let database = db::init_db(app.handle())?;
             app.manage(database);
+            let settings = setting::get_settings(app.handle());
+            app.manage(cache::init(app.handle(), settings.as_ref())?);
+            app.manage(jobs::JobQueue::new(settings.as_ref()));
+            app.manage(events::Streams::default());
+            tauri::async_runtime::spawn(jobs::dispatch(app.handle().clone()));
             Ok(())
         })
         .plugin(tauri_plugin_shell::init())
         .invoke_handler(tauri::generate_handler![
             run_yt,
+            retry_video,
+            jobs::get_jobs,
+            jobs::cancel_job,
+            events::cancel_stream,
+            jobs::pause_jobs,
+            jobs::resume_jobs,
+            jobs::jobs_paused,
             fetch_image,
+            fetch_live_chat,
             whisper::run_summary,
+            comments::run_comment_summary,
+            search::search_youtube,
+            search::summarize_search_results,
+            podcast::list_podcast_episodes,
+            podcast::import_podcast_episode,
+            local_file::import_local_file,
+            subtitles::import_subtitle_file,
+            export::export_transcript,
+            translate::translate_transcript,
+            markdown::export_markdown,
+            flashcards::generate_flashcards,
+            flashcards::export_anki,
+            report::export_report,
             db::get_videos,
+            db::get_video_detail,
+            db::search_videos,
+            db::get_segments,
+            db::get_status_history,
+            db::get_summaries,
+            db::set_active_summary,
+            db::get_notes,
+            db::create_note,
+            db::update_note,
+            db::delete_note,
+            db::get_tags,
+            db::get_video_tags,
+            db::create_tag,
+            db::rename_tag,
+            db::delete_tag,
+            db::tag_video,
+            db::untag_video,
+            db::get_collections,
+            db::create_collection,
+            db::rename_collection,
+            db::move_collection,
+            db::delete_collection,
+            db::add_to_collection,
+            db::remove_from_collection,
+            db::get_comments,
+            db::get_live_chat,
+            db::get_heatmap,
+            db::get_translation,
+            db::get_translation_languages,
+            db::get_flashcards,
             db::delete_video,
             db::clear_all,
             setting::load_settings,
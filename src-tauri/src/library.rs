@@ -0,0 +1,412 @@
+use rusqlite::{params, Connection, OptionalExtension, Params};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+// the user's own organization of the library: flat tags and collections nested in a tree
+
+#[derive(Error, Debug)]
+pub enum LibraryError {
+    #[error("Database error: {0}")]
+    Database(#[from] rusqlite::Error),
+
+    #[error("{0} not found")]
+    NotFound(&'static str),
+
+    #[error("a collection cannot be moved into itself or one of its children")]
+    Cycle,
+
+    #[error("name cannot be empty")]
+    EmptyName,
+}
+
+type Result<T> = std::result::Result<T, LibraryError>;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Tag {
+    pub id: i64,
+    pub name: String,
+    pub video_count: i64,
+}
+
+// the ui builds the tree from `parent_id`, top level collections have none
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Collection {
+    pub id: i64,
+    pub name: String,
+    pub parent_id: Option<i64>,
+    // videos directly in this collection, not counting its children
+    pub video_count: i64,
+}
+
+// `audio.id IN (...)` for get_videos: all of the tags in the `:tags` json array, and in the
+// `:collection` collection or any collection nested in it; a null parameter does not filter
+pub const VIDEO_FILTER: &str = "(:tags IS NULL OR audio.id IN (
+        SELECT audio_id FROM video_tags
+        WHERE tag_id IN (SELECT value FROM json_each(:tags))
+        GROUP BY audio_id HAVING count(DISTINCT tag_id) = json_array_length(:tags)
+    ))
+    AND (:collection IS NULL OR audio.id IN (
+        WITH RECURSIVE subtree(id) AS (
+            SELECT :collection
+            UNION SELECT collections.id FROM collections JOIN subtree ON parent_id = subtree.id
+        )
+        SELECT audio_id FROM collection_videos WHERE collection_id IN subtree
+    ))";
+
+// the value bound to `:tags`, no tags means no filter
+pub fn tags_param(tag_ids: &[i64]) -> Option<String> {
+    let mut tag_ids = tag_ids.to_vec();
+    tag_ids.sort_unstable();
+    tag_ids.dedup();
+    (!tag_ids.is_empty()).then(|| serde_json::to_string(&tag_ids).unwrap_or_default())
+}
+
+fn clean_name(name: &str) -> Result<&str> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(LibraryError::EmptyName);
+    }
+    Ok(name)
+}
+
+fn check_updated(changed: usize, what: &'static str) -> Result<()> {
+    if changed == 0 {
+        return Err(LibraryError::NotFound(what));
+    }
+    Ok(())
+}
+
+// tag names are unique ignoring case, creating an existing one returns it
+pub fn create_tag(connection: &Connection, name: &str) -> Result<Tag> {
+    let name = clean_name(name)?;
+    connection.execute(
+        "INSERT INTO tags (name) VALUES (?1) ON CONFLICT(name) DO NOTHING",
+        params![name],
+    )?;
+    let id: i64 = connection.query_row(
+        "SELECT id FROM tags WHERE name = ?1",
+        params![name],
+        |row| row.get(0),
+    )?;
+    list_tags(connection)?
+        .into_iter()
+        .find(|tag| tag.id == id)
+        .ok_or(LibraryError::NotFound("tag"))
+}
+
+pub fn rename_tag(connection: &Connection, id: i64, name: &str) -> Result<()> {
+    let changed = connection.execute(
+        "UPDATE tags SET name = ?2 WHERE id = ?1",
+        params![id, clean_name(name)?],
+    )?;
+    check_updated(changed, "tag")
+}
+
+pub fn delete_tag(connection: &Connection, id: i64) -> Result<()> {
+    // the video_tags rows go with it, the foreign key cascades
+    let changed = connection.execute("DELETE FROM tags WHERE id = ?1", params![id])?;
+    check_updated(changed, "tag")
+}
+
+fn query_tags(connection: &Connection, sql: &str, params: impl Params) -> Result<Vec<Tag>> {
+    let mut stmt = connection.prepare(sql)?;
+    let tag_iter = stmt.query_map(params, |row| {
+        Ok(Tag {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            video_count: row.get(2)?,
+        })
+    })?;
+    Ok(tag_iter.collect::<rusqlite::Result<_>>()?)
+}
+
+pub fn list_tags(connection: &Connection) -> Result<Vec<Tag>> {
+    query_tags(
+        connection,
+        "SELECT tags.id, tags.name, count(video_tags.audio_id) FROM tags
+        LEFT JOIN video_tags ON video_tags.tag_id = tags.id
+        GROUP BY tags.id ORDER BY tags.name",
+        [],
+    )
+}
+
+pub fn video_tags(connection: &Connection, video_id: i64) -> Result<Vec<Tag>> {
+    query_tags(
+        connection,
+        "SELECT tags.id, tags.name,
+            (SELECT count(*) FROM video_tags AS counted WHERE counted.tag_id = tags.id)
+        FROM tags JOIN video_tags ON video_tags.tag_id = tags.id
+        WHERE video_tags.audio_id = ?1
+        ORDER BY tags.name",
+        params![video_id],
+    )
+}
+
+pub fn tag_video(connection: &Connection, video_id: i64, tag_id: i64) -> Result<()> {
+    video_exists(connection, video_id)?;
+    connection.execute(
+        "INSERT OR IGNORE INTO video_tags (audio_id, tag_id) VALUES (?1, ?2)",
+        params![video_id, tag_id],
+    )?;
+    Ok(())
+}
+
+pub fn untag_video(connection: &Connection, video_id: i64, tag_id: i64) -> Result<()> {
+    connection.execute(
+        "DELETE FROM video_tags WHERE audio_id = ?1 AND tag_id = ?2",
+        params![video_id, tag_id],
+    )?;
+    Ok(())
+}
+
+// `parent_id` and the ids of all collections nested in it, the parent first
+fn subtree(connection: &Connection, parent_id: i64) -> Result<Vec<i64>> {
+    let mut stmt = connection.prepare(
+        "WITH RECURSIVE subtree(id) AS (
+            SELECT ?1
+            UNION SELECT collections.id FROM collections JOIN subtree ON parent_id = subtree.id
+        )
+        SELECT id FROM subtree",
+    )?;
+    let id_iter = stmt.query_map(params![parent_id], |row| row.get(0))?;
+    Ok(id_iter.collect::<rusqlite::Result<_>>()?)
+}
+
+// the foreign keys refuse a missing video as well, this says what is missing
+fn video_exists(connection: &Connection, id: i64) -> Result<()> {
+    connection
+        .query_row("SELECT id FROM audio WHERE id = ?1", params![id], |row| {
+            row.get::<_, i64>(0)
+        })
+        .optional()?
+        .map(|_| ())
+        .ok_or(LibraryError::NotFound("video"))
+}
+
+fn collection_exists(connection: &Connection, id: i64) -> Result<()> {
+    connection
+        .query_row(
+            "SELECT id FROM collections WHERE id = ?1",
+            params![id],
+            |row| row.get::<_, i64>(0),
+        )
+        .optional()?
+        .map(|_| ())
+        .ok_or(LibraryError::NotFound("collection"))
+}
+
+pub fn create_collection(
+    connection: &Connection,
+    name: &str,
+    parent_id: Option<i64>,
+) -> Result<Collection> {
+    let name = clean_name(name)?;
+    if let Some(parent_id) = parent_id {
+        collection_exists(connection, parent_id)?;
+    }
+    connection.execute(
+        "INSERT INTO collections (name, parent_id) VALUES (?1, ?2)",
+        params![name, parent_id],
+    )?;
+    Ok(Collection {
+        id: connection.last_insert_rowid(),
+        name: name.to_string(),
+        parent_id,
+        video_count: 0,
+    })
+}
+
+pub fn rename_collection(connection: &Connection, id: i64, name: &str) -> Result<()> {
+    let changed = connection.execute(
+        "UPDATE collections SET name = ?2 WHERE id = ?1",
+        params![id, clean_name(name)?],
+    )?;
+    check_updated(changed, "collection")
+}
+
+// move a collection with everything in it under `parent_id`, or to the top level
+pub fn move_collection(connection: &Connection, id: i64, parent_id: Option<i64>) -> Result<()> {
+    collection_exists(connection, id)?;
+    if let Some(parent_id) = parent_id {
+        collection_exists(connection, parent_id)?;
+        if subtree(connection, id)?.contains(&parent_id) {
+            return Err(LibraryError::Cycle);
+        }
+    }
+    connection.execute(
+        "UPDATE collections SET parent_id = ?2 WHERE id = ?1",
+        params![id, parent_id],
+    )?;
+    Ok(())
+}
+
+// removes the nested collections too, the videos in them stay in the library
+pub fn delete_collection(connection: &Connection, id: i64) -> Result<()> {
+    // sub-collections and their collection_videos rows follow through the cascades
+    let changed = connection.execute("DELETE FROM collections WHERE id = ?1", params![id])?;
+    check_updated(changed, "collection")
+}
+
+pub fn list_collections(connection: &Connection) -> Result<Vec<Collection>> {
+    let mut stmt = connection.prepare(
+        "SELECT collections.id, collections.name, collections.parent_id,
+            count(collection_videos.audio_id)
+        FROM collections
+        LEFT JOIN collection_videos ON collection_videos.collection_id = collections.id
+        GROUP BY collections.id ORDER BY collections.name",
+    )?;
+    let collection_iter = stmt.query_map([], |row| {
+        Ok(Collection {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            parent_id: row.get(2)?,
+            video_count: row.get(3)?,
+        })
+    })?;
+    Ok(collection_iter.collect::<rusqlite::Result<_>>()?)
+}
+
+pub fn add_to_collection(connection: &Connection, collection_id: i64, video_id: i64) -> Result<()> {
+    collection_exists(connection, collection_id)?;
+    video_exists(connection, video_id)?;
+    connection.execute(
+        "INSERT OR IGNORE INTO collection_videos (collection_id, audio_id) VALUES (?1, ?2)",
+        params![collection_id, video_id],
+    )?;
+    Ok(())
+}
+
+pub fn remove_from_collection(
+    connection: &Connection,
+    collection_id: i64,
+    video_id: i64,
+) -> Result<()> {
+    connection.execute(
+        "DELETE FROM collection_videos WHERE collection_id = ?1 AND audio_id = ?2",
+        params![collection_id, video_id],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::migrations;
+    use super::*;
+    use rusqlite::named_params;
+    use std::path::Path;
+
+    fn connection() -> Connection {
+        let mut connection = Connection::open_in_memory().unwrap();
+        connection
+            .pragma_update(None, "foreign_keys", true)
+            .unwrap();
+        migrations::migrate(&mut connection, Path::new(":memory:")).unwrap();
+        for video_id in ["a", "b", "c"] {
+            connection
+                .execute(
+                    "INSERT INTO audio (
+                        video_id, title, duration, upload_date, audio_url, audio_filesize,
+                        mime_type, thumbnail_url
+                    ) VALUES (?1, ?1, 0, 0, '', 0, '', '')",
+                    params![video_id],
+                )
+                .unwrap();
+        }
+        connection
+    }
+
+    fn filtered(connection: &Connection, tags: &[i64], collection: Option<i64>) -> Vec<String> {
+        let mut stmt = connection
+            .prepare(&format!(
+                "SELECT video_id FROM audio WHERE {VIDEO_FILTER} ORDER BY video_id"
+            ))
+            .unwrap();
+        stmt.query_map(
+            named_params! {":tags": tags_param(tags), ":collection": collection},
+            |row| row.get(0),
+        )
+        .unwrap()
+        .collect::<rusqlite::Result<_>>()
+        .unwrap()
+    }
+
+    #[test]
+    fn tags_filter_videos() {
+        let connection = connection();
+        let rust = create_tag(&connection, " Rust ").unwrap();
+        assert_eq!(create_tag(&connection, "rust").unwrap().id, rust.id);
+        let talk = create_tag(&connection, "talk").unwrap();
+        tag_video(&connection, 1, rust.id).unwrap();
+        tag_video(&connection, 1, talk.id).unwrap();
+        tag_video(&connection, 2, rust.id).unwrap();
+        tag_video(&connection, 2, rust.id).unwrap();
+
+        assert_eq!(filtered(&connection, &[], None), ["a", "b", "c"]);
+        assert_eq!(filtered(&connection, &[rust.id], None), ["a", "b"]);
+        assert_eq!(filtered(&connection, &[rust.id, talk.id], None), ["a"]);
+        assert_eq!(list_tags(&connection).unwrap()[0].video_count, 2);
+
+        rename_tag(&connection, talk.id, "conference").unwrap();
+        assert!(matches!(
+            rename_tag(&connection, talk.id, "  "),
+            Err(LibraryError::EmptyName)
+        ));
+        let names: Vec<String> = video_tags(&connection, 1)
+            .unwrap()
+            .into_iter()
+            .map(|tag| tag.name)
+            .collect();
+        assert_eq!(names, ["conference", "Rust"]);
+
+        assert!(matches!(
+            tag_video(&connection, 99, talk.id),
+            Err(LibraryError::NotFound("video"))
+        ));
+        // the foreign keys clear the tags of a deleted video
+        connection
+            .execute("DELETE FROM audio WHERE id = 1", [])
+            .unwrap();
+        let counts: Vec<i64> = list_tags(&connection)
+            .unwrap()
+            .into_iter()
+            .map(|tag| tag.video_count)
+            .collect();
+        assert_eq!(counts, [0, 1]);
+
+        delete_tag(&connection, rust.id).unwrap();
+        assert_eq!(video_tags(&connection, 2).unwrap(), []);
+    }
+
+    #[test]
+    fn collections_nest() {
+        let connection = connection();
+        let courses = create_collection(&connection, "Courses", None).unwrap();
+        let rust = create_collection(&connection, "Rust", Some(courses.id)).unwrap();
+        let async_rust = create_collection(&connection, "Async", Some(rust.id)).unwrap();
+        add_to_collection(&connection, courses.id, 1).unwrap();
+        add_to_collection(&connection, async_rust.id, 3).unwrap();
+
+        // a collection shows the videos of the ones nested in it
+        assert_eq!(filtered(&connection, &[], Some(courses.id)), ["a", "c"]);
+        assert_eq!(filtered(&connection, &[], Some(rust.id)), ["c"]);
+
+        assert!(matches!(
+            move_collection(&connection, courses.id, Some(async_rust.id)),
+            Err(LibraryError::Cycle)
+        ));
+        move_collection(&connection, async_rust.id, None).unwrap();
+        assert_eq!(filtered(&connection, &[], Some(courses.id)), ["a"]);
+
+        delete_collection(&connection, courses.id).unwrap();
+        let left: Vec<String> = list_collections(&connection)
+            .unwrap()
+            .into_iter()
+            .map(|collection| collection.name)
+            .collect();
+        assert_eq!(left, ["Async"]);
+        assert!(matches!(
+            add_to_collection(&connection, rust.id, 1),
+            Err(LibraryError::NotFound("collection"))
+        ));
+    }
+}
@@ -0,0 +1,369 @@
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{Connection, ToSql};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+
+use super::library;
+use super::status::Stage;
+
+// pages of the library for the video list, without transcript and summary bodies
+
+const DEFAULT_PAGE_SIZE: usize = 50;
+const MAX_PAGE_SIZE: usize = 500;
+
+#[derive(Error, Debug)]
+pub enum ListingError {
+    #[error("Database error: {0}")]
+    Database(#[from] rusqlite::Error),
+
+    #[error("invalid cursor: {0}")]
+    InvalidCursor(String),
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VideoSort {
+    #[default]
+    DateAdded,
+    UploadDate,
+    Duration,
+    Title,
+}
+
+impl VideoSort {
+    fn expression(self) -> &'static str {
+        match self {
+            VideoSort::DateAdded => "coalesce(audio.timestamp, 0)",
+            VideoSort::UploadDate => "audio.upload_date",
+            VideoSort::Duration => "audio.duration",
+            VideoSort::Title => "audio.title COLLATE NOCASE",
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    Ascending,
+    #[default]
+    Descending,
+}
+
+// every field is optional for the frontend, `{}` is the newest videos first
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct VideoQuery {
+    pub sort: VideoSort,
+    pub order: SortOrder,
+    pub page_size: Option<usize>,
+    // `next_cursor` of the previous page; takes precedence over `offset`
+    pub cursor: Option<String>,
+    pub offset: Option<usize>,
+    // stage of processing, `failed` lists what can be retried
+    pub status: Option<Stage>,
+    // language of the captions
+    pub language: Option<String>,
+    pub has_summary: Option<bool>,
+    pub tag_ids: Vec<i64>,
+    pub collection_id: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VideoListItem {
+    pub id: i64,
+    pub video_id: String,
+    pub title: String,
+    pub channel: Option<String>,
+    pub duration: u64,
+    pub upload_date: u64,
+    pub keywords: Option<String>,
+    pub timestamp: i64,
+    pub thumbnail_url: String,
+    pub caption_lang: Option<String>,
+    pub has_transcripts: bool,
+    pub has_summary: bool,
+    pub status: Stage,
+    // what the last run failed on
+    pub status_error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VideoPage {
+    pub items: Vec<VideoListItem>,
+    // none on the last page
+    pub next_cursor: Option<String>,
+    // videos matching the filters on all pages
+    pub total: usize,
+}
+
+// a cursor is the sort value and id of the last video on a page, as a json array
+fn encode_cursor(sort_value: SqlValue, id: i64) -> String {
+    let value = match sort_value {
+        SqlValue::Integer(value) => Value::from(value),
+        SqlValue::Real(value) => Value::from(value),
+        SqlValue::Text(value) => Value::from(value),
+        _ => Value::Null,
+    };
+    Value::Array(vec![value, Value::from(id)]).to_string()
+}
+
+fn decode_cursor(cursor: &str) -> Result<(SqlValue, i64), ListingError> {
+    let invalid = || ListingError::InvalidCursor(cursor.to_string());
+    let parsed: Vec<Value> = serde_json::from_str(cursor).map_err(|_| invalid())?;
+    let [value, id] = parsed.as_slice() else {
+        return Err(invalid());
+    };
+    let id = id.as_i64().ok_or_else(invalid)?;
+    let value = match value {
+        Value::Number(number) => match number.as_i64() {
+            Some(value) => SqlValue::Integer(value),
+            None => SqlValue::Real(number.as_f64().ok_or_else(invalid)?),
+        },
+        Value::String(value) => SqlValue::Text(value.clone()),
+        _ => return Err(invalid()),
+    };
+    Ok((value, id))
+}
+
+pub fn list_videos(connection: &Connection, query: &VideoQuery) -> Result<VideoPage, ListingError> {
+    let page_size = query
+        .page_size
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+    let sort = query.sort.expression();
+    let (direction, comparison) = match query.order {
+        SortOrder::Ascending => ("ASC", ">"),
+        SortOrder::Descending => ("DESC", "<"),
+    };
+
+    let filter = format!(
+        "{}
+        AND (:language IS NULL OR audio.caption_lang = :language)
+        AND (:has_summary IS NULL OR (coalesce(audio.summary, '') != '') = :has_summary)
+        AND (:status IS NULL OR audio.status = :status)",
+        library::VIDEO_FILTER
+    );
+    let tags = library::tags_param(&query.tag_ids);
+    let status = query.status.map(Stage::as_str);
+    let mut params: Vec<(&str, &dyn ToSql)> = vec![
+        (":tags", &tags),
+        (":collection", &query.collection_id),
+        (":language", &query.language),
+        (":has_summary", &query.has_summary),
+        (":status", &status),
+    ];
+
+    let total: usize = connection.query_row(
+        &format!("SELECT count(*) FROM audio WHERE {filter}"),
+        params.as_slice(),
+        |row| row.get(0),
+    )?;
+
+    let cursor = query.cursor.as_deref().map(decode_cursor).transpose()?;
+    let mut sql = format!(
+        "SELECT audio.id, audio.video_id, audio.title, audio.channel, audio.duration,
+            audio.upload_date, audio.keywords, coalesce(audio.timestamp, 0),
+            audio.thumbnail_url, audio.caption_lang,
+            EXISTS (SELECT 1 FROM segments WHERE segments.audio_id = audio.id),
+            coalesce(audio.summary, '') != '', audio.status, audio.status_error, {sort}
+        FROM audio WHERE {filter}"
+    );
+    if cursor.is_some() {
+        sql.push_str(&format!(
+            " AND ({sort}, audio.id) {comparison} (:cursor_value, :cursor_id)"
+        ));
+    }
+    sql.push_str(&format!(
+        " ORDER BY {sort} {direction}, audio.id {direction} LIMIT :limit OFFSET :offset"
+    ));
+
+    // one more than asked for tells whether there is a next page
+    let limit = page_size as i64 + 1;
+    let offset = match cursor {
+        Some(_) => 0,
+        None => query.offset.unwrap_or(0) as i64,
+    };
+    params.push((":limit", &limit));
+    params.push((":offset", &offset));
+    if let Some((value, id)) = &cursor {
+        params.push((":cursor_value", value));
+        params.push((":cursor_id", id));
+    }
+
+    let mut stmt = connection.prepare(&sql)?;
+    let rows = stmt.query_map(params.as_slice(), |row| {
+        let status: String = row.get(12)?;
+        Ok((
+            VideoListItem {
+                id: row.get(0)?,
+                video_id: row.get(1)?,
+                title: row.get(2)?,
+                channel: row.get(3)?,
+                duration: row.get(4)?,
+                upload_date: row.get(5)?,
+                keywords: row.get(6)?,
+                timestamp: row.get(7)?,
+                thumbnail_url: row.get(8)?,
+                caption_lang: row.get(9)?,
+                has_transcripts: row.get(10)?,
+                has_summary: row.get(11)?,
+                status: Stage::parse(&status).unwrap_or(Stage::Queued),
+                status_error: row.get(13)?,
+            },
+            row.get::<_, SqlValue>(14)?,
+        ))
+    })?;
+
+    let mut rows = rows.collect::<rusqlite::Result<Vec<_>>>()?;
+    let next_cursor = if rows.len() > page_size {
+        rows.truncate(page_size);
+        rows.last()
+            .map(|(item, sort_value)| encode_cursor(sort_value.clone(), item.id))
+    } else {
+        None
+    };
+
+    Ok(VideoPage {
+        items: rows.into_iter().map(|(item, _)| item).collect(),
+        next_cursor,
+        total,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::migrations;
+    use super::*;
+    use rusqlite::params;
+    use std::path::Path;
+
+    fn connection() -> Connection {
+        let mut connection = Connection::open_in_memory().unwrap();
+        connection
+            .pragma_update(None, "foreign_keys", true)
+            .unwrap();
+        migrations::migrate(&mut connection, Path::new(":memory:")).unwrap();
+        let videos = [
+            ("a", "banana", 300, Some("en"), None),
+            ("b", "Apple", 100, None, Some("a summary")),
+            ("c", "cherry", 200, Some("en"), None),
+            ("d", "apple pie", 100, Some("de"), Some("")),
+        ];
+        for (index, (video_id, title, duration, language, summary)) in videos.iter().enumerate() {
+            connection
+                .execute(
+                    "INSERT INTO audio (
+                        video_id, title, duration, upload_date, audio_url, audio_filesize,
+                        mime_type, thumbnail_url, caption_lang, summary, timestamp
+                    ) VALUES (?1, ?2, ?3, ?4, '', 0, '', '', ?5, ?6, ?4)",
+                    params![video_id, title, duration, index as i64, language, summary],
+                )
+                .unwrap();
+        }
+        connection
+            .execute(
+                "INSERT INTO segments (audio_id, segment_index, start_time, end_time, text, source)
+                VALUES (3, 0, 0.0, 1.0, 'hello', 'whisper')",
+                [],
+            )
+            .unwrap();
+        connection
+    }
+
+    fn ids(page: &VideoPage) -> Vec<&str> {
+        page.items
+            .iter()
+            .map(|item| item.video_id.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn cursor_walks_all_pages() {
+        let connection = connection();
+        let mut query = VideoQuery {
+            sort: VideoSort::Duration,
+            page_size: Some(2),
+            ..Default::default()
+        };
+        let first = list_videos(&connection, &query).unwrap();
+        assert_eq!(ids(&first), ["a", "c"]);
+        assert_eq!(first.total, 4);
+
+        // ties on duration are broken by id
+        query.cursor = first.next_cursor;
+        let second = list_videos(&connection, &query).unwrap();
+        assert_eq!(ids(&second), ["d", "b"]);
+        assert_eq!(second.next_cursor, None);
+
+        query.cursor = Some("[1]".to_string());
+        assert!(matches!(
+            list_videos(&connection, &query),
+            Err(ListingError::InvalidCursor(_))
+        ));
+    }
+
+    #[test]
+    fn sorts_and_filters() {
+        let connection = connection();
+        let query = VideoQuery {
+            sort: VideoSort::Title,
+            order: SortOrder::Ascending,
+            ..Default::default()
+        };
+        let page = list_videos(&connection, &query).unwrap();
+        assert_eq!(ids(&page), ["b", "d", "a", "c"]);
+
+        let query = VideoQuery {
+            sort: VideoSort::Title,
+            order: SortOrder::Ascending,
+            offset: Some(1),
+            page_size: Some(2),
+            ..Default::default()
+        };
+        let page = list_videos(&connection, &query).unwrap();
+        assert_eq!(ids(&page), ["d", "a"]);
+        // a cursor continues after an offset page too
+        let next = list_videos(
+            &connection,
+            &VideoQuery {
+                cursor: page.next_cursor,
+                ..query
+            },
+        )
+        .unwrap();
+        assert_eq!(ids(&next), ["c"]);
+
+        let query = VideoQuery {
+            language: Some("en".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(ids(&list_videos(&connection, &query).unwrap()), ["c", "a"]);
+
+        let query = VideoQuery {
+            has_summary: Some(false),
+            ..Default::default()
+        };
+        assert_eq!(
+            ids(&list_videos(&connection, &query).unwrap()),
+            ["d", "c", "a"]
+        );
+
+        let query = VideoQuery {
+            status: Some(Stage::Failed),
+            ..Default::default()
+        };
+        assert!(ids(&list_videos(&connection, &query).unwrap()).is_empty());
+        connection
+            .execute(
+                "UPDATE audio SET status = 'failed', status_error = 'quota' WHERE id = 3",
+                [],
+            )
+            .unwrap();
+        let page = list_videos(&connection, &query).unwrap();
+        assert_eq!(ids(&page), ["c"]);
+        assert!(page.items[0].has_transcripts);
+        assert_eq!(page.items[0].status_error.as_deref(), Some("quota"));
+        assert_eq!(page.total, 1);
+    }
+}
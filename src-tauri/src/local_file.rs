@@ -0,0 +1,154 @@
+use std::{
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+use tauri::Manager;
+use tokio_util::sync::CancellationToken;
+use tube_rs::{AudioData, Fnv1a};
+
+use super::events::{self, Change};
+use super::jobs::{self, JobKind};
+use super::status::{self, Stage};
+use super::whisper::SummaryMode;
+use super::{cache, db, transcribe_audio_file};
+
+// fnv-1a over the content, the same recording imported twice maps to one video
+fn content_hash(path: &Path) -> std::io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut buffer = vec![0; 1024 * 1024];
+    let mut hasher = Fnv1a::new();
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.write(&buffer[..read]);
+    }
+    Ok(format!("{:016x}", hasher.finish()))
+}
+
+// mime type of the audio `ffmpeg_audio::extract_audio` will write for a codec
+fn codec_mime_type(codec: &str) -> &str {
+    match codec {
+        "aac" | "alac" => "audio/mp4",
+        "mp3" => "audio/mpeg",
+        "opus" | "vorbis" => "audio/ogg",
+        "flac" => "audio/flac",
+        _ => "audio/wav",
+    }
+}
+
+// import a video or audio file from disk, transcribe and summarize it; returns the id in database
+#[tauri::command(rename_all = "snake_case")]
+pub async fn import_local_file(
+    app: tauri::AppHandle,
+    path: String,
+    language: String,
+    auto: bool,
+) -> Result<i64, String> {
+    let file_path = Path::new(&path);
+    // reading a large recording and its container takes a while, it runs off the async threads
+    let blocking_path = file_path.to_path_buf();
+    let (hash, media_info) = tauri::async_runtime::spawn_blocking(move || {
+        let hash = content_hash(&blocking_path).map_err(|e| e.to_string())?;
+        let media_info = ffmpeg_audio::probe(&blocking_path).map_err(|e| e.to_string())?;
+        Ok::<_, String>((hash, media_info))
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+    let video_id = format!("local:{hash}");
+
+    let id = match db::find_id_with_video_id(app.state(), &video_id)? {
+        Some(id) => id,
+        None => {
+            let audio_codec = media_info
+                .audio_codec
+                .ok_or(format!("no audio stream found in {path}"))?;
+            let metadata = std::fs::metadata(file_path).map_err(|e| e.to_string())?;
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(|duration| duration.as_micros() as u64)
+                .unwrap_or(0);
+            let audio_data = AudioData {
+                video_id,
+                title: file_path
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().to_string())
+                    .unwrap_or(path.clone()),
+                channel: None,
+                duration: media_info.duration.round() as u64,
+                timestamp: modified,
+                keywords: None,
+                description: Some(String::new()),
+                caption_lang: None,
+                caption_url: None,
+                audio_url: path.clone(),
+                audio_filesize: metadata.len(),
+                thumbnail_url: String::new(),
+                mime_type: codec_mime_type(&audio_codec).to_string(),
+            };
+            let id = db::create_video(app.state(), audio_data)?;
+            events::library_changed(&app, id, Change::Added)?;
+            id
+        }
+    };
+
+    // a file already in the library is not transcribed or summarized again
+    let (_, has_transcripts, summary) = db::get_progress_with_id(app.state(), id)?;
+    if summary.is_some() {
+        return Ok(id);
+    }
+    if !has_transcripts {
+        // `import_video` sends a local: id to `transcribe_local_file`, the url is only shown
+        let import = JobKind::Import {
+            url: path.clone(),
+            video_id: Some(id),
+        };
+        jobs::run(&app, import).await?;
+    }
+    let summarize = JobKind::Summarize {
+        video_id: id,
+        language: if auto { "auto".to_string() } else { language },
+        include_chat: false,
+        mode: SummaryMode::Standard,
+    };
+    jobs::run(&app, summarize).await?;
+    Ok(id)
+}
+
+// the stored audio url of a local import is the path of the original file
+pub async fn transcribe_local_file(
+    app: &tauri::AppHandle,
+    id: i64,
+    cancel: &CancellationToken,
+) -> Result<(), String> {
+    let (path, _, _, duration) = db::get_audio_url_with_id(app.state(), id)?;
+    // pulling the audio track out of the file counts as splitting it
+    status::track(app, id, Stage::Splitting, cancel, async {
+        let dir = cache::job_dir(app, id)?;
+        let result = async {
+            // ffmpeg blocks until the whole file is read, it runs off the async threads
+            let input = PathBuf::from(&path);
+            let output = dir.path().to_path_buf();
+            let stop = cancel.clone();
+            let extract = tauri::async_runtime::spawn_blocking(move || {
+                ffmpeg_audio::extract_audio_until(&input, &output, || stop.is_cancelled())
+            })
+            .await
+            .map_err(|e| e.to_string())?;
+            let audio_path = extract.map_err(|error| match cancel.is_cancelled() {
+                true => jobs::CANCELLED.to_string(),
+                false => error.to_string(),
+            })?;
+            transcribe_audio_file(app, id, &audio_path, duration, dir.path(), cancel).await
+        }
+        .await;
+        dir.close(&result, cancel).await;
+        result
+    })
+    .await
+}
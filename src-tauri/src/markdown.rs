@@ -0,0 +1,317 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::Manager;
+
+use super::db::{self, VideoRecord};
+use super::setting;
+use super::utils;
+
+// the part of a note between these markers belongs to the user and survives re-exports
+const NOTES_START: &str = "<!-- videomaestro:notes:start -->";
+const NOTES_END: &str = "<!-- videomaestro:notes:end -->";
+
+fn yaml_string(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// "[01:02](https://www.youtube.com/watch?v=…&t=62)", plain "[01:02]" when the source can not seek
+fn time_link(video: &VideoRecord, seconds: u64) -> String {
+    match video.time_url(seconds) {
+        Some(url) => format!("[{}]({url})", utils::format_clock(seconds)),
+        None => format!("[{}]", utils::format_clock(seconds)),
+    }
+}
+
+// the title, or the video id when nothing is left of it; `with_id` tells apart videos of one title
+fn file_name(video: &VideoRecord, with_id: bool) -> String {
+    let id = video.video_id.replace(':', "-");
+    let name: String = video
+        .title
+        .chars()
+        .filter(|c| {
+            !matches!(
+                c,
+                '\\' | '/' | ':' | '*' | '?' | '"' | '<' | '>' | '|' | '#' | '^' | '[' | ']'
+            )
+        })
+        .take(120)
+        .collect();
+    match name.trim() {
+        "" => format!("{id}.md"),
+        name if with_id => format!("{name} ({id}).md"),
+        name => format!("{name}.md"),
+    }
+}
+
+fn extract_notes(content: &str) -> Option<String> {
+    let start = content.find(NOTES_START)? + NOTES_START.len();
+    let end = content[start..].find(NOTES_END)? + start;
+    Some(content[start..end].trim_matches('\n').to_string())
+}
+
+pub fn render_markdown(video: &VideoRecord, notes: Option<&str>) -> Result<String, String> {
+    let keywords: Vec<String> = video
+        .keywords
+        .as_deref()
+        .unwrap_or_default()
+        .split_whitespace()
+        .map(yaml_string)
+        .collect();
+    let source = video
+        .video_id
+        .split_once(':')
+        .map(|(prefix, _)| prefix)
+        .unwrap_or("youtube");
+
+    let mut content = String::from("---\n");
+    content.push_str(&format!("title: {}\n", yaml_string(&video.title)));
+    content.push_str(&format!(
+        "channel: {}\n",
+        yaml_string(video.channel.as_deref().unwrap_or_default())
+    ));
+    content.push_str(&format!("url: {}\n", yaml_string(&video.url())));
+    content.push_str(&format!("video_id: {}\n", yaml_string(&video.video_id)));
+    content.push_str(&format!(
+        "duration: {}\n",
+        yaml_string(&utils::format_clock(video.duration))
+    ));
+    content.push_str(&format!("keywords: [{}]\n", keywords.join(", ")));
+    content.push_str(&format!("tags: [videomaestro, {source}]\n"));
+    content.push_str("---\n\n");
+
+    content.push_str(&format!("# {}\n\n", video.title));
+    if !video.thumbnail_url.is_empty() {
+        content.push_str(&format!("![thumbnail]({})\n\n", video.thumbnail_url));
+    }
+
+    content.push_str("## Summary\n\n");
+    match video.summary.as_deref() {
+        Some(summary) if !summary.trim().is_empty() => {
+            content.push_str(summary.trim());
+            content.push_str("\n\n");
+        }
+        _ => content.push_str("_No summary yet._\n\n"),
+    }
+
+    let chapters = utils::parse_chapters(video.description.as_deref().unwrap_or_default());
+    if !chapters.is_empty() {
+        content.push_str("## Chapters\n\n");
+        for (seconds, title) in chapters {
+            content.push_str(&format!("- {} {title}\n", time_link(video, seconds)));
+        }
+        content.push('\n');
+    }
+
+    if !video.notes.is_empty() {
+        content.push_str("## Highlights\n\n");
+        for note in &video.notes {
+            content.push_str(&format!(
+                "- {} {}\n",
+                time_link(video, note.start as u64),
+                note.text.trim()
+            ));
+            let excerpt = video.excerpt(note.start, note.end);
+            if !excerpt.is_empty() {
+                content.push_str(&format!("  > {excerpt}\n"));
+            }
+        }
+        content.push('\n');
+    }
+
+    content.push_str("## My Notes\n\n");
+    content.push_str(NOTES_START);
+    content.push('\n');
+    if let Some(notes) = notes.filter(|notes| !notes.is_empty()) {
+        content.push_str(notes);
+        content.push('\n');
+    }
+    content.push_str(NOTES_END);
+    content.push_str("\n\n");
+
+    if !video.segments.is_empty() {
+        // a folded callout, obsidian shows it collapsed until clicked
+        content.push_str("> [!note]- Transcript\n");
+        for segment in video.segments.iter().filter(|s| !s.text.trim().is_empty()) {
+            content.push_str(&format!(
+                "> {} {}\n",
+                time_link(video, segment.start as u64),
+                segment.text.trim()
+            ));
+        }
+    }
+    Ok(content)
+}
+
+// the note of a video is the file with its `video_id:` line, so it keeps its place even if the
+// user renamed it; a new note is named by the title, and by the id too when another video
+// of the same title has that name already
+fn note_path(folder: &Path, video: &VideoRecord) -> PathBuf {
+    let id_line = format!("video_id: {}", yaml_string(&video.video_id));
+    let belongs = |path: &Path| {
+        fs::read_to_string(path).is_ok_and(|content| content.lines().any(|l| l == id_line))
+    };
+    let named = folder.join(file_name(video, false));
+    if belongs(&named) {
+        return named;
+    }
+    let existing = fs::read_dir(folder).ok().and_then(|entries| {
+        entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "md"))
+            .find(|path| belongs(path))
+    });
+    match existing {
+        Some(path) => path,
+        None if !named.exists() => named,
+        None => folder.join(file_name(video, true)),
+    }
+}
+
+// write or update the markdown note of a video in the vault folder; returns the file path
+#[tauri::command(rename_all = "snake_case")]
+pub fn export_markdown(
+    app: tauri::AppHandle,
+    video_id: i64,
+    folder: Option<String>,
+) -> Result<String, String> {
+    let folder = folder
+        .or(setting::get_settings(&app).and_then(|settings| settings.vault_folder))
+        .ok_or("no vault folder configured".to_string())?;
+    let folder = PathBuf::from(folder);
+    fs::create_dir_all(&folder).map_err(|e| e.to_string())?;
+
+    let video = db::get_video_with_id(app.state(), video_id)?;
+    let path = note_path(&folder, &video);
+    let notes = fs::read_to_string(&path)
+        .ok()
+        .and_then(|content| extract_notes(&content));
+    let content = render_markdown(&video, notes.as_deref())?;
+    fs::write(&path, content).map_err(|e| e.to_string())?;
+    Ok(path.to_string_lossy().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Note;
+    use crate::whisper::Segment;
+
+    fn video() -> VideoRecord {
+        VideoRecord {
+            id: 1,
+            video_id: "FdeioVndUhs".to_string(),
+            title: "Rust: \"fast\" & safe?".to_string(),
+            channel: Some("Some Channel".to_string()),
+            duration: 3723,
+            upload_date: 0,
+            keywords: Some("rust memory".to_string()),
+            description: Some("00:00 Intro\n01:05 - Ownership".to_string()),
+            audio_url: String::new(),
+            thumbnail_url: String::new(),
+            segments: vec![Segment {
+                start: 65.4,
+                end: 70.0,
+                text: " Ownership rules ".to_string(),
+                speaker: None,
+            }],
+            notes: Vec::new(),
+            summary: Some("## Key Points\n- ownership".to_string()),
+            timestamp: 0,
+        }
+    }
+
+    #[test]
+    fn render_markdown_works() {
+        let content = render_markdown(&video(), None).unwrap();
+        assert!(content.starts_with("---\ntitle: \"Rust: \\\"fast\\\" & safe?\"\n"));
+        assert!(content.contains("channel: \"Some Channel\"\n"));
+        assert!(content.contains("duration: \"1:02:03\"\n"));
+        assert!(content.contains("keywords: [\"rust\", \"memory\"]\n"));
+        assert!(content.contains("tags: [videomaestro, youtube]\n"));
+        assert!(content
+            .contains("- [01:05](https://www.youtube.com/watch?v=FdeioVndUhs&t=65) Ownership\n"));
+        assert!(content.contains(
+            "> [!note]- Transcript\n> [01:05](https://www.youtube.com/watch?v=FdeioVndUhs&t=65) Ownership rules\n"
+        ));
+    }
+
+    #[test]
+    fn highlights_work() {
+        let mut video = video();
+        video.notes.push(Note {
+            id: 1,
+            start: 66.0,
+            end: 68.0,
+            text: "the key rule ".to_string(),
+            color: "yellow".to_string(),
+            created_at: 0,
+        });
+        let content = render_markdown(&video, None).unwrap();
+        assert!(content.contains(
+            "## Highlights\n\n- [01:06](https://www.youtube.com/watch?v=FdeioVndUhs&t=66) the key rule\n  > Ownership rules\n\n## My Notes"
+        ));
+    }
+
+    #[test]
+    fn notes_survive_re_export() {
+        let first = render_markdown(&video(), None).unwrap();
+        assert_eq!(extract_notes(&first).as_deref(), Some(""));
+
+        let edited = first.replace(
+            &format!("{NOTES_START}\n"),
+            &format!("{NOTES_START}\nmy own thoughts\n\n- [ ] rewatch\n"),
+        );
+        let notes = extract_notes(&edited).unwrap();
+        assert_eq!(notes, "my own thoughts\n\n- [ ] rewatch");
+
+        let second = render_markdown(&video(), Some(&notes)).unwrap();
+        assert_eq!(second, edited);
+    }
+
+    #[test]
+    fn file_name_works() {
+        assert_eq!(file_name(&video(), false), "Rust fast & safe.md");
+        assert_eq!(
+            file_name(&video(), true),
+            "Rust fast & safe (FdeioVndUhs).md"
+        );
+        let mut untitled = video();
+        untitled.title = "???".to_string();
+        untitled.video_id = "local:00ff".to_string();
+        assert_eq!(file_name(&untitled, false), "local-00ff.md");
+    }
+
+    #[test]
+    fn videos_of_one_title_keep_their_own_notes() {
+        let folder =
+            std::env::temp_dir().join(format!("videomaestro-markdown-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        let export = |video: &VideoRecord, notes: &str| {
+            let path = note_path(&folder, video);
+            fs::write(&path, render_markdown(video, Some(notes)).unwrap()).unwrap();
+            path
+        };
+
+        let first = video();
+        let mut reupload = video();
+        reupload.video_id = "reupload1".to_string();
+        let first_path = export(&first, "first notes");
+        let reupload_path = export(&reupload, "reupload notes");
+        assert_eq!(first_path, folder.join("Rust fast & safe.md"));
+        assert_eq!(
+            reupload_path,
+            folder.join("Rust fast & safe (reupload1).md")
+        );
+
+        // each video finds its own note again, also after the user renamed it
+        assert_eq!(note_path(&folder, &first), first_path);
+        let renamed = folder.join("renamed.md");
+        fs::rename(&reupload_path, &renamed).unwrap();
+        assert_eq!(note_path(&folder, &reupload), renamed);
+        let notes = extract_notes(&fs::read_to_string(&renamed).unwrap());
+        assert_eq!(notes.as_deref(), Some("reupload notes"));
+        fs::remove_dir_all(&folder).unwrap();
+    }
+}
@@ -0,0 +1,445 @@
+use rusqlite::{Connection, Result, Transaction};
+use std::path::{Path, PathBuf};
+
+use super::db::DataBaseError;
+use super::fulltext;
+
+type Migration = fn(&Transaction) -> Result<()>;
+
+// the schema version of a database is the number of migrations applied to it, stored in
+// `PRAGMA user_version`; only ever append here, a released migration must never change
+const MIGRATIONS: &[Migration] = &[
+    create_tables,
+    add_audio_channel,
+    create_search_index,
+    create_segments,
+    create_summaries,
+    create_library,
+    create_notes,
+    create_status,
+    create_jobs,
+    tokenize_search_trigrams,
+];
+
+// version 0 is the audio table alone; builds from before the migrations created the other
+// tables on the fly, so a version 0 database may already have some of them
+fn create_tables(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS audio (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            video_id TEXT NOT NULL UNIQUE,
+            title TEXT NOT NULL,
+            duration INTEGER NOT NULL,
+            upload_date INTEGER NOT NULL,
+            keywords TEXT,
+            description TEXT,
+            caption_lang TEXT,
+            caption_url TEXT,
+            audio_url TEXT NOT NULL,
+            audio_filesize INTEGER NOT NULL,
+            mime_type TEXT NOT NULL,
+            thumbnail_url TEXT NOT NULL,
+            transcripts TEXT,
+            summary TEXT,
+            timestamp INTEGER DEFAULT (strftime('%s', 'now'))
+        );
+
+        CREATE TABLE IF NOT EXISTS comments (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            audio_id INTEGER NOT NULL REFERENCES audio(id) ON DELETE CASCADE,
+            comment_id TEXT NOT NULL,
+            parent_id TEXT,
+            author TEXT NOT NULL,
+            text TEXT NOT NULL,
+            likes INTEGER NOT NULL DEFAULT 0,
+            reply_count INTEGER NOT NULL DEFAULT 0,
+            published_time TEXT NOT NULL,
+            UNIQUE(audio_id, comment_id)
+        );
+
+        CREATE TABLE IF NOT EXISTS live_chat (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            audio_id INTEGER NOT NULL REFERENCES audio(id) ON DELETE CASCADE,
+            offset_ms INTEGER NOT NULL,
+            author TEXT NOT NULL,
+            text TEXT NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS heatmap (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            audio_id INTEGER NOT NULL REFERENCES audio(id) ON DELETE CASCADE,
+            start_ms INTEGER NOT NULL,
+            duration_ms INTEGER NOT NULL,
+            intensity REAL NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS translations (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            audio_id INTEGER NOT NULL REFERENCES audio(id) ON DELETE CASCADE,
+            language TEXT NOT NULL,
+            segments TEXT NOT NULL,
+            timestamp INTEGER DEFAULT (strftime('%s', 'now')),
+            UNIQUE(audio_id, language)
+        );
+
+        CREATE TABLE IF NOT EXISTS flashcards (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            audio_id INTEGER NOT NULL REFERENCES audio(id) ON DELETE CASCADE,
+            question TEXT NOT NULL,
+            answer TEXT NOT NULL,
+            start REAL NOT NULL,
+            timestamp INTEGER DEFAULT (strftime('%s', 'now'))
+        );",
+    )
+}
+
+fn add_audio_channel(tx: &Transaction) -> Result<()> {
+    // builds from before the migrations added it already
+    if tx.prepare("SELECT channel FROM audio LIMIT 0").is_err() {
+        tx.execute("ALTER TABLE audio ADD COLUMN channel TEXT", [])?;
+    }
+    Ok(())
+}
+
+fn create_search_index(tx: &Transaction) -> Result<()> {
+    fulltext::create_index(tx)
+}
+
+// transcripts move from a json blob in `audio.transcripts` to one row per segment; blobs that
+// do not parse are left where they are, the backup keeps everything anyway
+fn create_segments(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS segments (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            audio_id INTEGER NOT NULL REFERENCES audio(id) ON DELETE CASCADE,
+            segment_index INTEGER NOT NULL,
+            start_time REAL NOT NULL,
+            end_time REAL NOT NULL,
+            text TEXT NOT NULL,
+            speaker TEXT,
+            source TEXT NOT NULL,
+            UNIQUE(audio_id, segment_index)
+        );
+        CREATE INDEX IF NOT EXISTS segments_time ON segments (audio_id, start_time);
+
+        INSERT INTO segments (audio_id, segment_index, start_time, end_time, text, source)
+        SELECT audio.id, segment.key, json_extract(segment.value, '$.start'),
+            json_extract(segment.value, '$.end'), json_extract(segment.value, '$.text'), 'unknown'
+        FROM audio,
+            json_each(CASE WHEN json_valid(audio.transcripts) THEN audio.transcripts ELSE '[]' END)
+                AS segment
+        WHERE json_type(segment.value, '$.start') IN ('integer', 'real')
+            AND json_type(segment.value, '$.end') IN ('integer', 'real')
+            AND json_type(segment.value, '$.text') = 'text';
+        UPDATE audio SET transcripts = NULL WHERE json_valid(transcripts);",
+    )?;
+    fulltext::index_segments(tx)
+}
+
+// every generated summary is kept; `audio.summary` stays as a copy of the active one
+fn create_summaries(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS summaries (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            audio_id INTEGER NOT NULL REFERENCES audio(id) ON DELETE CASCADE,
+            language TEXT NOT NULL,
+            model TEXT NOT NULL,
+            prompt_template TEXT NOT NULL,
+            content TEXT NOT NULL,
+            prompt_tokens INTEGER,
+            completion_tokens INTEGER,
+            active INTEGER NOT NULL DEFAULT 0,
+            created_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now'))
+        );
+        CREATE INDEX IF NOT EXISTS summaries_version
+            ON summaries (audio_id, language, model, prompt_template, created_at);
+
+        INSERT INTO summaries (audio_id, language, model, prompt_template, content, active, created_at)
+        SELECT id, 'unknown', 'unknown', 'unknown', summary, 1,
+            coalesce(timestamp, strftime('%s', 'now'))
+        FROM audio WHERE summary IS NOT NULL AND summary != '';",
+    )
+}
+
+// user tags and nested collections, both many-to-many with videos
+fn create_library(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS tags (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL UNIQUE COLLATE NOCASE
+        );
+
+        CREATE TABLE IF NOT EXISTS video_tags (
+            audio_id INTEGER NOT NULL REFERENCES audio(id) ON DELETE CASCADE,
+            tag_id INTEGER NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+            PRIMARY KEY (audio_id, tag_id)
+        );
+
+        CREATE TABLE IF NOT EXISTS collections (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            parent_id INTEGER REFERENCES collections(id) ON DELETE CASCADE
+        );
+        CREATE INDEX IF NOT EXISTS collections_parent ON collections (parent_id);
+
+        CREATE TABLE IF NOT EXISTS collection_videos (
+            collection_id INTEGER NOT NULL REFERENCES collections(id) ON DELETE CASCADE,
+            audio_id INTEGER NOT NULL REFERENCES audio(id) ON DELETE CASCADE,
+            PRIMARY KEY (collection_id, audio_id)
+        );",
+    )
+}
+
+fn create_notes(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS notes (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            audio_id INTEGER NOT NULL REFERENCES audio(id) ON DELETE CASCADE,
+            start_time REAL NOT NULL,
+            end_time REAL NOT NULL,
+            text TEXT NOT NULL,
+            color TEXT NOT NULL,
+            created_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now'))
+        );
+        CREATE INDEX IF NOT EXISTS notes_time ON notes (audio_id, start_time);",
+    )
+}
+
+// videos from before the status column either have a transcript or never finished
+fn create_status(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "ALTER TABLE audio ADD COLUMN status TEXT NOT NULL DEFAULT 'queued';
+        ALTER TABLE audio ADD COLUMN status_error TEXT;
+        UPDATE audio SET status = 'done'
+        WHERE EXISTS (SELECT 1 FROM segments WHERE segments.audio_id = audio.id);
+
+        CREATE TABLE IF NOT EXISTS status_history (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            audio_id INTEGER NOT NULL REFERENCES audio(id) ON DELETE CASCADE,
+            stage TEXT NOT NULL,
+            error TEXT,
+            created_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now'))
+        );
+        CREATE INDEX IF NOT EXISTS status_history_audio ON status_history (audio_id);
+        INSERT INTO status_history (audio_id, stage) SELECT id, status FROM audio;",
+    )
+}
+
+fn create_jobs(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS jobs (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            payload TEXT NOT NULL,
+            audio_id INTEGER REFERENCES audio(id) ON DELETE CASCADE,
+            status TEXT NOT NULL DEFAULT 'pending',
+            attempts INTEGER NOT NULL DEFAULT 0,
+            max_attempts INTEGER NOT NULL,
+            run_after INTEGER NOT NULL,
+            error TEXT,
+            created_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
+            updated_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now'))
+        );
+        CREATE INDEX IF NOT EXISTS jobs_due ON jobs (status, run_after);",
+    )
+}
+
+fn tokenize_search_trigrams(tx: &Transaction) -> Result<()> {
+    fulltext::tokenize_trigrams(tx)
+}
+
+fn backup_path(db_path: &Path, version: usize) -> PathBuf {
+    db_path.with_extension(format!("v{version}.backup.db"))
+}
+
+fn run_migrations(
+    connection: &mut Connection,
+    db_path: &Path,
+    migrations: &[Migration],
+) -> Result<usize, DataBaseError> {
+    let version: usize = connection.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    // a database from a newer build is left alone rather than guessed at
+    if version >= migrations.len() {
+        return Ok(version);
+    }
+
+    let empty: bool =
+        connection.query_row("SELECT count(*) = 0 FROM sqlite_master", [], |row| {
+            row.get(0)
+        })?;
+    if !empty {
+        let backup = backup_path(db_path, version);
+        if backup.exists() {
+            std::fs::remove_file(&backup)?;
+        }
+        connection.execute("VACUUM INTO ?1", [backup.to_string_lossy()])?;
+    }
+
+    // all or nothing, a failed migration leaves the database as it was
+    let tx = connection.transaction()?;
+    for migration in &migrations[version..] {
+        migration(&tx)?;
+    }
+    tx.pragma_update(None, "user_version", migrations.len())?;
+    tx.commit()?;
+    Ok(migrations.len())
+}
+
+// bring the database at `db_path` to the latest schema, a copy of it is kept next to it
+// as `<name>.v<version>.backup.db` first; returns the schema version
+pub fn migrate(connection: &mut Connection, db_path: &Path) -> Result<usize, DataBaseError> {
+    run_migrations(connection, db_path, MIGRATIONS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_db(name: &str) -> (PathBuf, Connection) {
+        let dir = std::env::temp_dir().join(format!(
+            "videomaestro-migrations-{name}-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("test.db");
+        let connection = Connection::open(&path).unwrap();
+        (path, connection)
+    }
+
+    fn version(connection: &Connection) -> usize {
+        connection
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn migrates_v0_database() {
+        let (path, mut connection) = temp_db("v0");
+        connection
+            .execute_batch(include_str!("../fixtures/schema_v0.sql"))
+            .unwrap();
+
+        assert_eq!(migrate(&mut connection, &path).unwrap(), MIGRATIONS.len());
+        assert_eq!(version(&connection), MIGRATIONS.len());
+
+        let (title, channel): (String, Option<String>) = connection
+            .query_row(
+                "SELECT title, channel FROM audio WHERE video_id = 'FdeioVndUhs'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(title, "Rust ownership explained");
+        assert_eq!(channel, None);
+        for table in [
+            "comments",
+            "live_chat",
+            "heatmap",
+            "translations",
+            "flashcards",
+        ] {
+            let count: i64 = connection
+                .query_row(&format!("SELECT count(*) FROM {table}"), [], |row| {
+                    row.get(0)
+                })
+                .unwrap();
+            assert_eq!(count, 0, "{table}");
+        }
+        // the transcripts blob became one row per segment
+        let segments: Vec<(i64, f64, String, String)> = connection
+            .prepare(
+                "SELECT segment_index, start_time, text, source FROM segments
+                ORDER BY segment_index",
+            )
+            .unwrap()
+            .query_map([], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+            })
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(
+            segments,
+            vec![
+                (0, 0.0, " Welcome back".to_string(), "unknown".to_string()),
+                (
+                    1,
+                    12.5,
+                    " today we borrow a value".to_string(),
+                    "unknown".to_string()
+                ),
+            ]
+        );
+        let transcripts: Option<String> = connection
+            .query_row(
+                "SELECT transcripts FROM audio WHERE video_id = 'FdeioVndUhs'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(transcripts, None);
+        // the summary became the first, active version
+        let (content, model, active): (String, String, bool) = connection
+            .query_row(
+                "SELECT content, model, active FROM summaries
+                JOIN audio ON audio.id = summaries.audio_id WHERE video_id = 'FdeioVndUhs'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+        assert!(content.starts_with("## Key Points"));
+        assert_eq!(model, "unknown");
+        assert!(active);
+        // a transcribed video counts as done, with that as its first history entry
+        let (status, history): (String, String) = connection
+            .query_row(
+                "SELECT status, (SELECT group_concat(stage) FROM status_history
+                    WHERE audio_id = audio.id)
+                FROM audio WHERE video_id = 'FdeioVndUhs'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!((status.as_str(), history.as_str()), ("done", "done"));
+        // rows from before the migration are in the search index
+        let hits = fulltext::search(&connection, "borrow", 10).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].start, Some(12.5));
+
+        let backup = Connection::open(backup_path(&path, 0)).unwrap();
+        assert_eq!(version(&backup), 0);
+        let count: i64 = backup
+            .query_row("SELECT count(*) FROM audio", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 2);
+
+        // nothing left to do on the next start
+        std::fs::remove_file(backup_path(&path, 0)).unwrap();
+        assert_eq!(migrate(&mut connection, &path).unwrap(), MIGRATIONS.len());
+        assert!(!backup_path(&path, 0).exists());
+    }
+
+    #[test]
+    fn fresh_database_has_no_backup() {
+        let (path, mut connection) = temp_db("fresh");
+        assert_eq!(migrate(&mut connection, &path).unwrap(), MIGRATIONS.len());
+        assert!(!backup_path(&path, 0).exists());
+        assert!(connection.prepare("SELECT channel FROM audio").is_ok());
+    }
+
+    #[test]
+    fn failed_migration_rolls_back() {
+        fn broken(tx: &Transaction) -> Result<()> {
+            tx.execute("ALTER TABLE missing ADD COLUMN nothing TEXT", [])
+                .map(|_| ())
+        }
+        let (path, mut connection) = temp_db("broken");
+        connection
+            .execute_batch(include_str!("../fixtures/schema_v0.sql"))
+            .unwrap();
+
+        assert!(run_migrations(&mut connection, &path, &[create_tables, broken]).is_err());
+        assert_eq!(version(&connection), 0);
+        assert!(connection.prepare("SELECT * FROM comments").is_err());
+    }
+}
@@ -0,0 +1,133 @@
+use podcast_rs::{PodcastClient, PodcastFeed};
+use tauri::Manager;
+use tokio_util::sync::CancellationToken;
+use tube_rs::AudioData;
+
+use super::events::{self, Change};
+use super::jobs::{self, JobKind, Pool};
+use super::status::{self, Stage};
+use super::subtitles::{parse_subtitles, SubtitleFormat};
+use super::{cache, db, save_subtitles, setting, transcribe_audio_file, utils};
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn list_podcast_episodes(
+    app: tauri::AppHandle,
+    feed_url: String,
+) -> Result<PodcastFeed, String> {
+    let client = PodcastClient::new(setting::get_proxy(&app).as_deref());
+    client
+        .fetch_feed(&feed_url)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// store one episode of a feed as a video and transcribe it; returns the id in database
+#[tauri::command(rename_all = "snake_case")]
+pub async fn import_podcast_episode(
+    app: tauri::AppHandle,
+    feed_url: String,
+    guid: String,
+) -> Result<i64, String> {
+    let client = PodcastClient::new(setting::get_proxy(&app).as_deref());
+    let feed = client
+        .fetch_feed(&feed_url)
+        .await
+        .map_err(|e| e.to_string())?;
+    let episode = feed
+        .episodes
+        .iter()
+        .find(|episode| episode.guid == guid)
+        .ok_or(format!("episode {guid} not found in the feed"))?;
+
+    let video_id = format!("podcast:{}", episode.episode_id());
+    let id = match db::find_id_with_video_id(app.state(), &video_id)? {
+        Some(id) => {
+            // an episode imported before is not transcribed again
+            let (_, has_transcripts, _) = db::get_progress_with_id(app.state(), id)?;
+            if has_transcripts {
+                return Ok(id);
+            }
+            id
+        }
+        None => {
+            // only srt and webvtt transcripts carry timestamps, others go through whisper
+            let transcript = episode.timed_transcript();
+            let audio_data = AudioData {
+                video_id,
+                title: episode.title.clone(),
+                channel: Some(feed.title.clone()),
+                duration: episode.duration,
+                timestamp: episode.published,
+                keywords: episode.keywords.clone(),
+                description: Some(episode.description.clone().unwrap_or_default()),
+                caption_lang: transcript.map(|t| {
+                    t.language
+                        .clone()
+                        .or(feed.language.clone())
+                        .unwrap_or("und".to_string())
+                }),
+                caption_url: transcript.map(|t| t.url.clone()),
+                audio_url: episode.enclosure_url.clone(),
+                audio_filesize: episode.enclosure_size,
+                thumbnail_url: episode
+                    .image_url
+                    .clone()
+                    .or(feed.image_url.clone())
+                    .unwrap_or_default(),
+                mime_type: episode.mime_type.clone(),
+            };
+            let id = db::create_video(app.state(), audio_data)?;
+            events::library_changed(&app, id, Change::Added)?;
+            id
+        }
+    };
+    // `import_video` sends a podcast: id to `transcribe_episode`, the url is only shown
+    let import = JobKind::Import {
+        url: episode.enclosure_url.clone(),
+        video_id: Some(id),
+    };
+    jobs::run(&app, import).await?;
+    Ok(id)
+}
+
+// use the transcript published with the episode when there is one, otherwise download the enclosure
+pub async fn transcribe_episode(
+    app: &tauri::AppHandle,
+    id: i64,
+    cancel: &CancellationToken,
+) -> Result<(), String> {
+    status::track(app, id, Stage::Downloading, cancel, async {
+        let permit = jobs::until_cancelled(cancel, jobs::permit(app, Pool::Downloads)).await?;
+        let client = PodcastClient::new(setting::get_proxy(app).as_deref());
+        if let Ok((Some(_), Some(url))) = db::get_caption_with_id(app.state(), id) {
+            let content = jobs::until_cancelled(cancel, client.download_text(&url)).await?;
+            let segments = SubtitleFormat::detect(&content)
+                .map(|format| parse_subtitles(&content, format))
+                .unwrap_or_default();
+            if !segments.is_empty() {
+                drop(permit);
+                return save_subtitles(app, id, segments).await;
+            }
+        }
+
+        let (audio_url, _, mime_type, duration) = db::get_audio_url_with_id(app.state(), id)?;
+        let dir = cache::job_dir(app, id)?;
+        let audio_path = dir
+            .path()
+            .join(format!("audio.{}", utils::audio_extension(&mime_type)));
+        let result = async {
+            if !audio_path.exists() {
+                let partial = cache::partial_path(&audio_path);
+                let download = client.download_enclosure(&audio_url, &partial);
+                jobs::until_cancelled(cancel, download).await?;
+                std::fs::rename(&partial, &audio_path).map_err(|e| e.to_string())?;
+            }
+            drop(permit);
+            transcribe_audio_file(app, id, &audio_path, duration, dir.path(), cancel).await
+        }
+        .await;
+        dir.close(&result, cancel).await;
+        result
+    })
+    .await
+}
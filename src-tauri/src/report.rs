@@ -0,0 +1,1125 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use pdf_writer::types::{
+    ActionType, AnnotationType, CidFontType, FontFlags, SystemInfo, UnicodeCmap,
+};
+use pdf_writer::{Content, Filter, Finish, Name, Pdf, Rect, Ref, Str};
+use pulldown_cmark::{html, Event, Options, Parser, Tag, TagEnd};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use tauri::Manager;
+use ttf_parser::{name_id, Face};
+
+use super::db::{self, VideoRecord};
+use super::utils::{self, escape_html, format_clock};
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportFormat {
+    Html,
+    Pdf,
+}
+
+const CSS: &str = "
+body { margin: 0; background: #f5f5f4; color: #1c1917; font: 16px/1.6 -apple-system, 'Segoe UI', Roboto, sans-serif; }
+main { max-width: 820px; margin: 0 auto; padding: 32px; background: #fff; }
+h1 { margin: 16px 0 4px; font-size: 28px; line-height: 1.3; }
+h2 { margin-top: 32px; border-bottom: 1px solid #e7e5e4; padding-bottom: 4px; }
+a { color: #2563eb; text-decoration: none; }
+.thumbnail { width: 100%; border-radius: 8px; }
+.meta { margin: 0; color: #57534e; font-size: 14px; }
+.keyword { display: inline-block; margin: 8px 6px 0 0; padding: 0 8px; border-radius: 4px; background: #f5f5f4; font-size: 13px; }
+.chapters { padding-left: 0; list-style: none; }
+.time { display: inline-block; min-width: 64px; font-family: ui-monospace, monospace; font-size: 13px; }
+.segment { margin: 4px 0; }
+.note { margin: 12px 0; padding: 4px 12px; border-left: 4px solid; }
+.note p { margin: 4px 0; }
+.excerpt { color: #57534e; font-style: italic; }
+footer { margin-top: 48px; color: #a8a29e; font-size: 12px; }
+@media print { body { background: #fff; } main { padding: 0; } }
+";
+
+// "2024-03-05" from microseconds since the epoch, days to civil date after Howard Hinnant
+fn format_date(micros: u64) -> Option<String> {
+    if micros == 0 {
+        return None;
+    }
+    let days = (micros / 86_400_000_000) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    Some(format!("{year:04}-{month:02}-{day:02}"))
+}
+
+// channel, duration and upload date, whatever is known
+fn meta_items(video: &VideoRecord) -> Vec<String> {
+    let mut items = Vec::new();
+    if let Some(channel) = video.channel.as_deref().filter(|c| !c.is_empty()) {
+        items.push(channel.to_string());
+    }
+    items.push(format_clock(video.duration));
+    if let Some(date) = format_date(video.upload_date) {
+        items.push(date);
+    }
+    items
+}
+
+fn image_mime_type(bytes: &[u8]) -> Option<&'static str> {
+    match bytes {
+        [0xff, 0xd8, ..] => Some("image/jpeg"),
+        [0x89, b'P', b'N', b'G', ..] => Some("image/png"),
+        [b'G', b'I', b'F', ..] => Some("image/gif"),
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => Some("image/webp"),
+        _ => None,
+    }
+}
+
+// links and images of a model answer may only point to the web or to a mail address
+fn is_safe_url(url: &str) -> bool {
+    let url = url.trim_start().to_ascii_lowercase();
+    ["http://", "https://", "mailto:"]
+        .iter()
+        .any(|scheme| url.starts_with(scheme))
+}
+
+fn markdown_to_html(markdown: &str) -> String {
+    // whether each open link or image is kept, an unsafe one is dropped but its text is shown
+    let mut kept: Vec<bool> = Vec::new();
+    let parser = Parser::new_ext(
+        markdown,
+        Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH,
+    )
+    .filter_map(move |event| match event {
+        // raw html in a model answer is shown as text, a shared report must not run anything
+        Event::Html(text) | Event::InlineHtml(text) => Some(Event::Text(text)),
+        Event::Start(Tag::Link { ref dest_url, .. } | Tag::Image { ref dest_url, .. }) => {
+            let safe = is_safe_url(dest_url);
+            kept.push(safe);
+            safe.then_some(event)
+        }
+        Event::End(TagEnd::Link | TagEnd::Image) => kept.pop().unwrap_or(true).then_some(event),
+        event => Some(event),
+    });
+    let mut content = String::new();
+    html::push_html(&mut content, parser);
+    content
+}
+
+fn time_html(video: &VideoRecord, seconds: u64) -> String {
+    match video.time_url(seconds) {
+        Some(url) => format!(
+            "<a class=\"time\" href=\"{}\">{}</a>",
+            escape_html(&url),
+            format_clock(seconds)
+        ),
+        None => format!("<span class=\"time\">{}</span>", format_clock(seconds)),
+    }
+}
+
+// a single html file with everything inlined, it opens in any browser without the app
+pub fn render_html(video: &VideoRecord, thumbnail: Option<&[u8]>) -> Result<String, String> {
+    let segments = &video.segments;
+    let mut body = String::new();
+
+    if let Some((bytes, mime_type)) =
+        thumbnail.and_then(|bytes| image_mime_type(bytes).map(|mime| (bytes, mime)))
+    {
+        body.push_str(&format!(
+            "<img class=\"thumbnail\" src=\"data:{mime_type};base64,{}\" alt=\"\">\n",
+            STANDARD.encode(bytes)
+        ));
+    }
+    body.push_str(&format!("<h1>{}</h1>\n", escape_html(&video.title)));
+    body.push_str(&format!(
+        "<p class=\"meta\">{}</p>\n",
+        escape_html(&meta_items(video).join(" · "))
+    ));
+    let url = video.url();
+    if url.starts_with("http") {
+        body.push_str(&format!(
+            "<p class=\"meta\"><a href=\"{0}\">{0}</a></p>\n",
+            escape_html(&url)
+        ));
+    }
+    if let Some(keywords) = video.keywords.as_deref().filter(|k| !k.trim().is_empty()) {
+        body.push_str("<p>");
+        for keyword in keywords.split_whitespace() {
+            body.push_str(&format!(
+                "<span class=\"keyword\">{}</span>",
+                escape_html(keyword)
+            ));
+        }
+        body.push_str("</p>\n");
+    }
+
+    if let Some(summary) = video.summary.as_deref().filter(|s| !s.trim().is_empty()) {
+        body.push_str("<section>\n<h2>Summary</h2>\n");
+        body.push_str(&markdown_to_html(summary));
+        body.push_str("</section>\n");
+    }
+
+    let chapters = utils::parse_chapters(video.description.as_deref().unwrap_or_default());
+    if !chapters.is_empty() {
+        body.push_str("<section>\n<h2>Chapters</h2>\n<ol class=\"chapters\">\n");
+        for (seconds, title) in chapters {
+            body.push_str(&format!(
+                "<li>{} {}</li>\n",
+                time_html(video, seconds),
+                escape_html(&title)
+            ));
+        }
+        body.push_str("</ol>\n</section>\n");
+    }
+
+    if !video.notes.is_empty() {
+        body.push_str("<section>\n<h2>Highlights</h2>\n");
+        for note in &video.notes {
+            let color = match utils::is_note_color(&note.color) {
+                true => note.color.as_str(),
+                false => utils::DEFAULT_NOTE_COLOR,
+            };
+            body.push_str(&format!(
+                "<blockquote class=\"note\" style=\"border-color: {color}\">\n<p>{} {}</p>\n",
+                time_html(video, note.start as u64),
+                escape_html(note.text.trim())
+            ));
+            let excerpt = video.excerpt(note.start, note.end);
+            if !excerpt.is_empty() {
+                body.push_str(&format!(
+                    "<p class=\"excerpt\">{}</p>\n",
+                    escape_html(&excerpt)
+                ));
+            }
+            body.push_str("</blockquote>\n");
+        }
+        body.push_str("</section>\n");
+    }
+
+    if !segments.is_empty() {
+        body.push_str("<section>\n<h2>Transcript</h2>\n");
+        for segment in segments.iter().filter(|s| !s.text.trim().is_empty()) {
+            body.push_str(&format!(
+                "<p class=\"segment\">{} {}</p>\n",
+                time_html(video, segment.start as u64),
+                escape_html(segment.text.trim())
+            ));
+        }
+        body.push_str("</section>\n");
+    }
+
+    Ok(format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n<title>{}</title>\n<style>{CSS}</style>\n</head>\n<body>\n<main>\n{body}<footer>Exported with Video Maestro</footer>\n</main>\n</body>\n</html>\n",
+        escape_html(&video.title)
+    ))
+}
+
+const PAGE_WIDTH: f32 = 595.0;
+const PAGE_HEIGHT: f32 = 842.0;
+const MARGIN: f32 = 56.0;
+const TIME_COLUMN: f32 = 52.0;
+
+// advance widths of helvetica for ' '..='~' in 1/1000 em
+const HELVETICA_WIDTHS: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278, 556, 556, 556,
+    556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556, 1015, 667, 667, 722, 722, 667,
+    611, 778, 722, 278, 500, 667, 556, 833, 722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667,
+    667, 611, 278, 278, 278, 469, 556, 333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500,
+    222, 833, 556, 556, 556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584,
+];
+
+// the fallback font is drawn for regular and bold text alike
+const FALLBACK_FONT: Name = Name(b"F3");
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Font {
+    Regular,
+    Bold,
+}
+
+impl Font {
+    fn name(self) -> Name<'static> {
+        match self {
+            Font::Regular => Name(b"F1"),
+            Font::Bold => Name(b"F2"),
+        }
+    }
+
+    fn text_width(self, text: &str, size: f32) -> f32 {
+        let units: u32 = text
+            .chars()
+            .map(|c| match c {
+                ' '..='~' => HELVETICA_WIDTHS[c as usize - 32] as u32,
+                _ => 556,
+            })
+            .sum();
+        // bold glyphs are a little wider, overestimating only wraps a bit earlier
+        let scale = if self == Font::Bold { 1.1 } else { 1.0 };
+        units as f32 * size / 1000.0 * scale
+    }
+}
+
+// the standard pdf fonts only cover windows-1252
+fn win_ansi_byte(c: char) -> Option<u8> {
+    match c {
+        ' '..='~' | '\u{a0}'..='\u{ff}' => Some(c as u8),
+        '€' => Some(0x80),
+        '…' => Some(0x85),
+        '‘' => Some(0x91),
+        '’' => Some(0x92),
+        '“' => Some(0x93),
+        '”' => Some(0x94),
+        '•' => Some(0x95),
+        '–' => Some(0x96),
+        '—' => Some(0x97),
+        c if c.is_whitespace() => Some(b' '),
+        _ => None,
+    }
+}
+
+fn encode_win_ansi(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| win_ansi_byte(c).unwrap_or(b'?'))
+        .collect()
+}
+
+// system fonts with truetype outlines that cover chinese, japanese or korean, then any other
+// script; the first one with a glyph for every character is used
+const FALLBACK_FONTS: [&str; 14] = [
+    "C:\\Windows\\Fonts\\msyh.ttc",
+    "C:\\Windows\\Fonts\\simsun.ttc",
+    "C:\\Windows\\Fonts\\meiryo.ttc",
+    "C:\\Windows\\Fonts\\msgothic.ttc",
+    "C:\\Windows\\Fonts\\malgun.ttf",
+    "/System/Library/Fonts/STHeiti Light.ttc",
+    "/System/Library/Fonts/Supplemental/Arial Unicode.ttf",
+    "/Library/Fonts/Arial Unicode.ttf",
+    "/usr/share/fonts/truetype/wqy/wqy-microhei.ttc",
+    "/usr/share/fonts/truetype/wqy/wqy-zenhei.ttc",
+    "/usr/share/fonts/truetype/droid/DroidSansFallbackFull.ttf",
+    "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf",
+    "C:\\Windows\\Fonts\\arial.ttf",
+    "/System/Library/Fonts/Supplemental/Arial.ttf",
+];
+
+// a font file read from the system for the text the standard fonts cannot show
+pub struct FallbackFont {
+    data: Vec<u8>,
+    // the face in a .ttc collection
+    index: u32,
+}
+
+impl FallbackFont {
+    fn read(path: &str, index: u32) -> Option<FallbackFont> {
+        let font = FallbackFont {
+            data: std::fs::read(path).ok()?,
+            index,
+        };
+        // cff outlines would need their own cid mapping, only truetype ones are embedded
+        font.face()?.tables().glyf?;
+        Some(font)
+    }
+
+    // the listed font with glyphs for most of `chars`, none when no font has any of them
+    pub fn find(chars: &BTreeSet<char>) -> Option<FallbackFont> {
+        let mut best: Option<(usize, FallbackFont)> = None;
+        for path in FALLBACK_FONTS {
+            let Some(font) = FallbackFont::read(path, 0) else {
+                continue;
+            };
+            let covered = match font.face() {
+                Some(face) => chars
+                    .iter()
+                    .filter(|c| face.glyph_index(**c).is_some())
+                    .count(),
+                None => 0,
+            };
+            if covered == chars.len() {
+                return Some(font);
+            }
+            if covered > best.as_ref().map_or(0, |(count, _)| *count) {
+                best = Some((covered, font));
+            }
+        }
+        best.map(|(_, font)| font)
+    }
+
+    fn face(&self) -> Option<Face<'_>> {
+        Face::parse(&self.data, self.index).ok()
+    }
+}
+
+// the fallback font while a pdf is laid out, with the glyphs drawn so far
+struct Fallback<'a> {
+    font: &'a FallbackFont,
+    face: Face<'a>,
+    // each glyph and the character it shows, for the widths and the text copied from the pdf
+    glyphs: BTreeMap<u16, char>,
+}
+
+impl Fallback<'_> {
+    // in 1/1000 em like the standard fonts
+    fn glyph_width(&self, glyph: ttf_parser::GlyphId) -> f32 {
+        let advance = self.face.glyph_hor_advance(glyph).unwrap_or(0);
+        advance as f32 * 1000.0 / self.face.units_per_em() as f32
+    }
+}
+
+// what a video shows in the pdf that the standard fonts cannot, some of it may not be drawn
+pub fn unsupported_chars(video: &VideoRecord) -> BTreeSet<char> {
+    let mut texts = vec![video.title.as_str()];
+    texts.extend(video.channel.as_deref());
+    texts.extend(video.keywords.as_deref());
+    texts.extend(video.summary.as_deref());
+    texts.extend(video.description.as_deref());
+    texts.extend(video.notes.iter().map(|note| note.text.as_str()));
+    texts.extend(video.segments.iter().map(|segment| segment.text.as_str()));
+    texts
+        .iter()
+        .flat_map(|text| text.chars())
+        .filter(|c| win_ansi_byte(*c).is_none())
+        .collect()
+}
+
+fn wrap_text(text: &str, max_width: f32, text_width: impl Fn(&str) -> f32) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut current = String::new();
+    for word in text.split_whitespace() {
+        let candidate = if current.is_empty() {
+            word.to_string()
+        } else {
+            format!("{current} {word}")
+        };
+        if text_width(&candidate) <= max_width {
+            current = candidate;
+            continue;
+        }
+        if !current.is_empty() {
+            lines.push(std::mem::take(&mut current));
+        }
+        // a word wider than the line, e.g. a url, is broken anywhere
+        for c in word.chars() {
+            if text_width(&format!("{current}{c}")) > max_width && !current.is_empty() {
+                lines.push(std::mem::take(&mut current));
+            }
+            current.push(c);
+        }
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+    lines
+}
+
+struct PdfPage {
+    content: Content,
+    links: Vec<(Rect, String)>,
+}
+
+// lays text out top to bottom and starts a new page when one is full
+struct PdfLayout<'a> {
+    pages: Vec<PdfPage>,
+    y: f32,
+    fallback: Option<Fallback<'a>>,
+    // the first character drawn that neither the standard fonts nor the fallback can show
+    unsupported: Option<char>,
+}
+
+impl<'a> PdfLayout<'a> {
+    fn new(fallback: Option<&'a FallbackFont>) -> Self {
+        let fallback = fallback.and_then(|font| {
+            Some(Fallback {
+                font,
+                face: font.face()?,
+                glyphs: BTreeMap::new(),
+            })
+        });
+        let mut layout = Self {
+            pages: Vec::new(),
+            y: 0.0,
+            fallback,
+            unsupported: None,
+        };
+        layout.new_page();
+        layout
+    }
+
+    // split `text` into runs of the standard font and of the fallback, true for the latter
+    fn runs(&self, text: &str) -> Vec<(bool, String)> {
+        let mut runs: Vec<(bool, String)> = Vec::new();
+        for c in text.chars() {
+            let fallback = self.fallback.is_some() && win_ansi_byte(c).is_none();
+            match runs.last_mut() {
+                Some((last, run)) if *last == fallback => run.push(c),
+                _ => runs.push((fallback, c.to_string())),
+            }
+        }
+        runs
+    }
+
+    fn text_width(&self, text: &str, font: Font, size: f32) -> f32 {
+        self.runs(text)
+            .iter()
+            .map(|(fallback, run)| match (fallback, &self.fallback) {
+                (true, Some(fallback)) => {
+                    let units: f32 = run
+                        .chars()
+                        .map(|c| match fallback.face.glyph_index(c) {
+                            Some(glyph) => fallback.glyph_width(glyph),
+                            None => 556.0,
+                        })
+                        .sum();
+                    units * size / 1000.0
+                }
+                _ => font.text_width(run, size),
+            })
+            .sum()
+    }
+
+    // two bytes per glyph for the identity encoding of the fallback font
+    fn encode_fallback(&mut self, text: &str) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let Some(fallback) = self.fallback.as_mut() else {
+            return bytes;
+        };
+        for c in text.chars() {
+            let glyph = match fallback.face.glyph_index(c) {
+                Some(glyph) => {
+                    fallback.glyphs.insert(glyph.0, c);
+                    glyph.0
+                }
+                None => {
+                    self.unsupported.get_or_insert(c);
+                    0
+                }
+            };
+            bytes.extend(glyph.to_be_bytes());
+        }
+        bytes
+    }
+
+    fn new_page(&mut self) {
+        self.pages.push(PdfPage {
+            content: Content::new(),
+            links: Vec::new(),
+        });
+        self.y = PAGE_HEIGHT - MARGIN;
+    }
+
+    fn page(&mut self) -> &mut PdfPage {
+        self.pages.last_mut().unwrap()
+    }
+
+    // move down by `height`, on a new page if it does not fit; returns the new top
+    fn advance(&mut self, height: f32) -> f32 {
+        if self.y - height < MARGIN {
+            self.new_page();
+        }
+        self.y -= height;
+        self.y
+    }
+
+    fn space(&mut self, height: f32) {
+        self.y -= height;
+    }
+
+    fn draw_text(&mut self, x: f32, baseline: f32, text: &str, font: Font, size: f32) {
+        if self.unsupported.is_none() && self.fallback.is_none() {
+            self.unsupported = text.chars().find(|c| win_ansi_byte(*c).is_none());
+        }
+        let runs: Vec<(Name, Vec<u8>)> = self
+            .runs(text)
+            .into_iter()
+            .map(|(fallback, run)| match fallback {
+                true => (FALLBACK_FONT, self.encode_fallback(&run)),
+                false => (font.name(), encode_win_ansi(&run)),
+            })
+            .collect();
+        let content = &mut self.page().content;
+        content.begin_text();
+        content.next_line(x, baseline);
+        // every run moves the text position on, the next one starts where it ends
+        for (name, bytes) in &runs {
+            content.set_font(*name, size);
+            content.show(Str(bytes));
+        }
+        content.end_text();
+    }
+
+    fn link(&mut self, rect: Rect, url: String) {
+        self.page().links.push((rect, url));
+    }
+
+    fn paragraph(&mut self, text: &str, font: Font, size: f32, indent: f32) {
+        let width = PAGE_WIDTH - 2.0 * MARGIN - indent;
+        for line in wrap_text(text, width, |line| self.text_width(line, font, size)) {
+            let y = self.advance(size * 1.4);
+            self.draw_text(MARGIN + indent, y + size * 0.3, &line, font, size);
+        }
+    }
+
+    fn heading(&mut self, text: &str) {
+        self.space(10.0);
+        self.paragraph(text, Font::Bold, 14.0, 0.0);
+        self.space(4.0);
+    }
+
+    // a time in the left column, linked when the source can seek, and wrapped text next to it
+    fn timed_line(&mut self, video: &VideoRecord, seconds: u64, text: &str) {
+        let size = 10.0;
+        let width = PAGE_WIDTH - 2.0 * MARGIN - TIME_COLUMN;
+        let lines = wrap_text(text, width, |line| {
+            self.text_width(line, Font::Regular, size)
+        });
+        for (index, line) in lines.iter().enumerate() {
+            let y = self.advance(size * 1.4);
+            let baseline = y + size * 0.3;
+            if index == 0 {
+                let label = format_clock(seconds);
+                self.draw_text(MARGIN, baseline, &label, Font::Bold, size);
+                if let Some(url) = video.time_url(seconds) {
+                    let label_width = self.text_width(&label, Font::Bold, size);
+                    self.link(
+                        Rect::new(MARGIN, y, MARGIN + label_width, y + size * 1.4),
+                        url,
+                    );
+                }
+            }
+            self.draw_text(MARGIN + TIME_COLUMN, baseline, line, Font::Regular, size);
+        }
+    }
+
+    fn image(&mut self, name: Name, width: f32, height: f32) {
+        let y = self.advance(height);
+        let content = &mut self.page().content;
+        content.save_state();
+        content.transform([width, 0.0, 0.0, height, MARGIN, y]);
+        content.x_object(name);
+        content.restore_state();
+        self.space(8.0);
+    }
+}
+
+// the summary is markdown, the pdf shows it as plain paragraphs with bold headings and bullets
+fn summary_blocks(markdown: &str) -> Vec<(Font, f32, f32, String)> {
+    let mut blocks = Vec::new();
+    let mut text = String::new();
+    let mut heading = false;
+    let mut lists: Vec<Option<u64>> = Vec::new();
+
+    let flush = |blocks: &mut Vec<(Font, f32, f32, String)>,
+                 text: &mut String,
+                 heading: bool,
+                 depth: usize| {
+        let line = text.trim().to_string();
+        text.clear();
+        if line.is_empty() {
+            return;
+        }
+        match heading {
+            true => blocks.push((Font::Bold, 12.0, 0.0, line)),
+            false => blocks.push((Font::Regular, 11.0, depth as f32 * 14.0, line)),
+        }
+    };
+
+    for event in Parser::new_ext(markdown, Options::ENABLE_TABLES) {
+        match event {
+            Event::Start(Tag::Heading { .. }) => {
+                flush(&mut blocks, &mut text, false, lists.len());
+                heading = true;
+            }
+            Event::End(TagEnd::Heading(_)) => {
+                flush(&mut blocks, &mut text, true, 0);
+                heading = false;
+            }
+            Event::Start(Tag::List(start)) => {
+                flush(&mut blocks, &mut text, heading, lists.len());
+                lists.push(start);
+            }
+            Event::End(TagEnd::List(_)) => {
+                lists.pop();
+            }
+            Event::Start(Tag::Item) => {
+                flush(&mut blocks, &mut text, heading, lists.len());
+                match lists.last_mut() {
+                    Some(Some(number)) => {
+                        text.push_str(&format!("{number}. "));
+                        *number += 1;
+                    }
+                    _ => text.push_str("• "),
+                }
+            }
+            Event::End(
+                TagEnd::Item
+                | TagEnd::Paragraph
+                | TagEnd::CodeBlock
+                | TagEnd::TableHead
+                | TagEnd::TableRow,
+            )
+            | Event::HardBreak
+            | Event::Rule => flush(&mut blocks, &mut text, heading, lists.len()),
+            Event::End(TagEnd::TableCell) => text.push_str("   "),
+            Event::Text(value)
+            | Event::Code(value)
+            | Event::Html(value)
+            | Event::InlineHtml(value) => text.push_str(&value),
+            Event::SoftBreak => text.push(' '),
+            _ => {}
+        }
+    }
+    flush(&mut blocks, &mut text, heading, lists.len());
+    blocks
+}
+
+// width, height and color components from the start-of-frame marker of a jpeg
+fn jpeg_info(bytes: &[u8]) -> Option<(u16, u16, u8)> {
+    if !bytes.starts_with(&[0xff, 0xd8]) {
+        return None;
+    }
+    let mut index = 2;
+    while index + 9 < bytes.len() {
+        if bytes[index] != 0xff {
+            return None;
+        }
+        let marker = bytes[index + 1];
+        let length = u16::from_be_bytes([bytes[index + 2], bytes[index + 3]]) as usize;
+        if (0xc0..=0xcf).contains(&marker) && !matches!(marker, 0xc4 | 0xc8 | 0xcc) {
+            let height = u16::from_be_bytes([bytes[index + 5], bytes[index + 6]]);
+            let width = u16::from_be_bytes([bytes[index + 7], bytes[index + 8]]);
+            return Some((width, height, bytes[index + 9]));
+        }
+        index += 2 + length;
+    }
+    None
+}
+
+// write the glyphs of the fallback font drawn in the pdf as a type0 font, only their outlines
+// are embedded; `first_id` and the four ids after it are used
+fn write_fallback(pdf: &mut Pdf, fallback: &Fallback, first_id: i32) -> Result<(), String> {
+    let type0_id = Ref::new(first_id);
+    let cid_id = Ref::new(first_id + 1);
+    let descriptor_id = Ref::new(first_id + 2);
+    let file_id = Ref::new(first_id + 3);
+    let cmap_id = Ref::new(first_id + 4);
+
+    let face = &fallback.face;
+    let postscript_name = face
+        .names()
+        .into_iter()
+        .filter(|name| name.name_id == name_id::POST_SCRIPT_NAME)
+        .find_map(|name| name.to_string())
+        .unwrap_or("Fallback".to_string());
+    // a subset is named with a six letter tag in front
+    let base_font = format!(
+        "VMSUBS+{}",
+        postscript_name
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric() || *c == '-')
+            .collect::<String>()
+    );
+    let base_font = Name(base_font.as_bytes());
+    let system_info = SystemInfo {
+        registry: Str(b"Adobe"),
+        ordering: Str(b"Identity"),
+        supplement: 0,
+    };
+
+    pdf.type0_font(type0_id)
+        .base_font(base_font)
+        .encoding_predefined(Name(b"Identity-H"))
+        .descendant_font(cid_id)
+        .to_unicode(cmap_id);
+
+    let mut cid_font = pdf.cid_font(cid_id);
+    cid_font
+        .subtype(CidFontType::Type2)
+        .base_font(base_font)
+        .system_info(system_info)
+        .font_descriptor(descriptor_id)
+        .cid_to_gid_map_predefined(Name(b"Identity"));
+    let mut widths = cid_font.widths();
+    for glyph in fallback.glyphs.keys() {
+        widths.consecutive(*glyph, [fallback.glyph_width(ttf_parser::GlyphId(*glyph))]);
+    }
+    widths.finish();
+    cid_font.finish();
+
+    let scale = 1000.0 / face.units_per_em() as f32;
+    let bbox = face.global_bounding_box();
+    pdf.font_descriptor(descriptor_id)
+        .name(base_font)
+        .flags(FontFlags::SYMBOLIC)
+        .bbox(Rect::new(
+            bbox.x_min as f32 * scale,
+            bbox.y_min as f32 * scale,
+            bbox.x_max as f32 * scale,
+            bbox.y_max as f32 * scale,
+        ))
+        .italic_angle(0.0)
+        .ascent(face.ascender() as f32 * scale)
+        .descent(face.descender() as f32 * scale)
+        .cap_height(face.capital_height().unwrap_or(face.ascender()) as f32 * scale)
+        .stem_v(80.0)
+        .font_file2(file_id);
+
+    // glyph ids stay the same in the subset, the identity mappings above still hold
+    let mut glyphs: Vec<u16> = vec![0];
+    glyphs.extend(fallback.glyphs.keys());
+    let subset = subsetter::subset(
+        &fallback.font.data,
+        fallback.font.index,
+        subsetter::Profile::pdf(&glyphs),
+    )
+    .map_err(|e| e.to_string())?;
+    pdf.stream(file_id, &subset)
+        .pair(Name(b"Length1"), subset.len() as i32);
+
+    let mut cmap = UnicodeCmap::new(Name(b"Custom"), system_info);
+    for (glyph, c) in &fallback.glyphs {
+        cmap.pair(*glyph, *c);
+    }
+    pdf.cmap(cmap_id, &cmap.finish());
+    Ok(())
+}
+
+// a pdf with the standard fonts, so latin text is written without a browser or font files;
+// anything else is drawn with `fallback`, a system font, and refused when there is none;
+// the thumbnail is embedded when it is a jpeg, which youtube thumbnails are
+pub fn render_pdf(
+    video: &VideoRecord,
+    thumbnail: Option<&[u8]>,
+    fallback: Option<&FallbackFont>,
+) -> Result<Vec<u8>, String> {
+    let segments = &video.segments;
+    let image = thumbnail.and_then(|bytes| jpeg_info(bytes).map(|info| (bytes, info)));
+    let image_name = Name(b"Im1");
+
+    let mut layout = PdfLayout::new(fallback);
+    if let Some((_, (width, height, _))) = image {
+        let draw_width = 240.0;
+        layout.image(
+            image_name,
+            draw_width,
+            draw_width * height as f32 / width.max(1) as f32,
+        );
+    }
+    layout.paragraph(&video.title, Font::Bold, 20.0, 0.0);
+    layout.paragraph(&meta_items(video).join(" · "), Font::Regular, 10.0, 0.0);
+    let url = video.url();
+    if url.starts_with("http") {
+        layout.paragraph(&url, Font::Regular, 10.0, 0.0);
+        let top = layout.y + 14.0;
+        layout.link(Rect::new(MARGIN, layout.y, PAGE_WIDTH - MARGIN, top), url);
+    }
+    if let Some(keywords) = video.keywords.as_deref().filter(|k| !k.trim().is_empty()) {
+        layout.paragraph(keywords, Font::Regular, 10.0, 0.0);
+    }
+
+    if let Some(summary) = video.summary.as_deref().filter(|s| !s.trim().is_empty()) {
+        layout.heading("Summary");
+        for (font, size, indent, text) in summary_blocks(summary) {
+            if font == Font::Bold {
+                layout.space(4.0);
+            }
+            layout.paragraph(&text, font, size, indent);
+        }
+    }
+
+    let chapters = utils::parse_chapters(video.description.as_deref().unwrap_or_default());
+    if !chapters.is_empty() {
+        layout.heading("Chapters");
+        for (seconds, title) in chapters {
+            layout.timed_line(video, seconds, &title);
+        }
+    }
+
+    if !video.notes.is_empty() {
+        layout.heading("Highlights");
+        for note in &video.notes {
+            layout.timed_line(video, note.start as u64, note.text.trim());
+            let excerpt = video.excerpt(note.start, note.end);
+            if !excerpt.is_empty() {
+                layout.paragraph(&excerpt, Font::Regular, 9.0, TIME_COLUMN);
+            }
+            layout.space(4.0);
+        }
+    }
+
+    if !segments.is_empty() {
+        layout.heading("Transcript");
+        for segment in segments.iter().filter(|s| !s.text.trim().is_empty()) {
+            layout.timed_line(video, segment.start as u64, segment.text.trim());
+        }
+    }
+
+    if let Some(c) = layout.unsupported {
+        return Err(format!(
+            "no installed font can show \"{c}\" in the pdf report, export it as html instead"
+        ));
+    }
+
+    let catalog_id = Ref::new(1);
+    let page_tree_id = Ref::new(2);
+    let regular_id = Ref::new(3);
+    let bold_id = Ref::new(4);
+    let image_id = Ref::new(5);
+    let page_ids: Vec<Ref> = (0..layout.pages.len())
+        .map(|index| Ref::new(6 + 2 * index as i32))
+        .collect();
+    let fallback = layout
+        .fallback
+        .take()
+        .filter(|fallback| !fallback.glyphs.is_empty());
+    let fallback_id = Ref::new(6 + 2 * page_ids.len() as i32);
+
+    let mut pdf = Pdf::new();
+    pdf.catalog(catalog_id).pages(page_tree_id);
+    pdf.pages(page_tree_id)
+        .kids(page_ids.iter().copied())
+        .count(page_ids.len() as i32);
+    pdf.type1_font(regular_id)
+        .base_font(Name(b"Helvetica"))
+        .encoding_predefined(Name(b"WinAnsiEncoding"));
+    pdf.type1_font(bold_id)
+        .base_font(Name(b"Helvetica-Bold"))
+        .encoding_predefined(Name(b"WinAnsiEncoding"));
+    if let Some((bytes, (width, height, components))) = image {
+        let mut xobject = pdf.image_xobject(image_id, bytes);
+        xobject.filter(Filter::DctDecode);
+        xobject.width(width as i32);
+        xobject.height(height as i32);
+        xobject.color_space_name(match components {
+            1 => Name(b"DeviceGray"),
+            4 => Name(b"DeviceCMYK"),
+            _ => Name(b"DeviceRGB"),
+        });
+        xobject.bits_per_component(8);
+    }
+    if let Some(fallback) = &fallback {
+        write_fallback(&mut pdf, fallback, fallback_id.get())?;
+    }
+
+    for (page_id, page) in page_ids.iter().zip(layout.pages) {
+        let content_id = Ref::new(page_id.get() + 1);
+        let mut writer = pdf.page(*page_id);
+        writer
+            .media_box(Rect::new(0.0, 0.0, PAGE_WIDTH, PAGE_HEIGHT))
+            .parent(page_tree_id)
+            .contents(content_id);
+        let mut resources = writer.resources();
+        let mut fonts = resources.fonts();
+        fonts
+            .pair(Font::Regular.name(), regular_id)
+            .pair(Font::Bold.name(), bold_id);
+        if fallback.is_some() {
+            fonts.pair(FALLBACK_FONT, fallback_id);
+        }
+        fonts.finish();
+        if image.is_some() {
+            resources.x_objects().pair(image_name, image_id);
+        }
+        resources.finish();
+        if !page.links.is_empty() {
+            let mut annotations = writer.annotations();
+            for (rect, url) in &page.links {
+                let mut annotation = annotations.push();
+                annotation.subtype(AnnotationType::Link).rect(*rect);
+                annotation.border_style().width(0.0);
+                annotation
+                    .action()
+                    .action_type(ActionType::Uri)
+                    .uri(Str(url.as_bytes()));
+            }
+        }
+        writer.finish();
+        pdf.stream(content_id, &page.content.finish());
+    }
+    Ok(pdf.finish())
+}
+
+// write a shareable report of a video to `path`
+#[tauri::command(rename_all = "snake_case")]
+pub async fn export_report(
+    app: tauri::AppHandle,
+    video_id: i64,
+    format: ReportFormat,
+    path: String,
+) -> Result<(), String> {
+    let video = db::get_video_with_id(app.state(), video_id)?;
+    // a report without a thumbnail is still worth having
+    let thumbnail = match video.thumbnail_url.starts_with("http") {
+        true => super::fetch_image(app.clone(), video.thumbnail_url.clone())
+            .await
+            .ok(),
+        false => None,
+    };
+    let content = match format {
+        ReportFormat::Html => render_html(&video, thumbnail.as_deref())?.into_bytes(),
+        ReportFormat::Pdf => {
+            // the standard fonts only cover latin text, a system font draws the rest; font
+            // files are large, they are read off the async threads
+            let chars = unsupported_chars(&video);
+            let fallback = match chars.is_empty() {
+                true => None,
+                false => tauri::async_runtime::spawn_blocking(move || FallbackFont::find(&chars))
+                    .await
+                    .map_err(|e| e.to_string())?,
+            };
+            render_pdf(&video, thumbnail.as_deref(), fallback.as_ref())?
+        }
+    };
+    std::fs::write(&path, content).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Note;
+    use crate::whisper::Segment;
+
+    fn video() -> VideoRecord {
+        let segments: Vec<Segment> = (0..200)
+            .map(|index| Segment {
+                start: index as f64 * 5.0,
+                end: index as f64 * 5.0 + 5.0,
+                text: format!("line {index} <b>of</b> the transcript"),
+                speaker: None,
+            })
+            .collect();
+        VideoRecord {
+            id: 1,
+            video_id: "FdeioVndUhs".to_string(),
+            title: "Rust & <friends>".to_string(),
+            channel: Some("Some Channel".to_string()),
+            duration: 1000,
+            upload_date: 1_709_596_800_000_000,
+            keywords: Some("rust memory".to_string()),
+            description: Some("00:00 Intro\n01:05 Ownership".to_string()),
+            audio_url: String::new(),
+            thumbnail_url: String::new(),
+            segments,
+            notes: vec![Note {
+                id: 1,
+                start: 6.0,
+                end: 9.0,
+                text: "remember <this>".to_string(),
+                color: "#ff0000".to_string(),
+                created_at: 0,
+            }],
+            summary: Some(
+                "## Key Points\n- first <script>x</script>\n- second [safe](https://example.com)\n- [unsafe](javascript:alert(1)) and ![shown](http://example.com/a.png) and ![hidden](data:image/png;base64,AA)"
+                    .to_string(),
+            ),
+            timestamp: 0,
+        }
+    }
+
+    #[test]
+    fn format_date_works() {
+        assert_eq!(
+            format_date(1_709_596_800_000_000).as_deref(),
+            Some("2024-03-05")
+        );
+        assert_eq!(
+            format_date(951_782_400_000_000).as_deref(),
+            Some("2000-02-29")
+        );
+        assert_eq!(format_date(0), None);
+    }
+
+    #[test]
+    fn render_html_works() {
+        let jpeg = [0xff, 0xd8, 0xff, 0xe0];
+        let content = render_html(&video(), Some(&jpeg)).unwrap();
+        assert!(content.contains("<title>Rust &amp; &lt;friends&gt;</title>"));
+        assert!(content.contains("src=\"data:image/jpeg;base64,/9j/4A==\""));
+        assert!(content.contains("Some Channel · 16:40 · 2024-03-05"));
+        assert!(content.contains("<h2>Key Points</h2>"));
+        assert!(content.contains("first &lt;script&gt;x&lt;/script&gt;"));
+        assert!(content.contains(
+            "<li><a class=\"time\" href=\"https://www.youtube.com/watch?v=FdeioVndUhs&amp;t=65\">01:05</a> Ownership</li>"
+        ));
+        assert!(content.contains("line 3 &lt;b&gt;of&lt;/b&gt; the transcript"));
+        assert!(!content.contains("javascript:"));
+        assert!(content.contains("<a href=\"https://example.com\">safe</a>"));
+        assert!(content.contains(
+            "<li>unsafe and <img src=\"http://example.com/a.png\" alt=\"shown\" /> and hidden</li>"
+        ));
+        assert!(content.contains(
+            "<blockquote class=\"note\" style=\"border-color: #ff0000\">\n<p><a class=\"time\" href=\"https://www.youtube.com/watch?v=FdeioVndUhs&amp;t=6\">00:06</a> remember &lt;this&gt;</p>\n<p class=\"excerpt\">line 1 &lt;b&gt;of&lt;/b&gt; the transcript</p>"
+        ));
+    }
+
+    #[test]
+    fn wrap_text_works() {
+        let width = |text: &str| Font::Regular.text_width(text, 10.0);
+        let lines = wrap_text("the quick brown fox jumps", 60.0, width);
+        assert_eq!(lines, vec!["the quick", "brown fox", "jumps"]);
+        let lines = wrap_text(&"x".repeat(30), 60.0, width);
+        assert!(lines.len() > 1);
+        assert_eq!(lines.concat(), "x".repeat(30));
+    }
+
+    #[test]
+    fn summary_blocks_works() {
+        let blocks = summary_blocks("## Key Points\n1. first\n2. second\n   - nested\n\nDone.");
+        let texts: Vec<&str> = blocks.iter().map(|block| block.3.as_str()).collect();
+        assert_eq!(
+            texts,
+            vec!["Key Points", "1. first", "2. second", "• nested", "Done."]
+        );
+        assert_eq!(blocks[0].0, Font::Bold);
+        assert_eq!(blocks[3].2, 28.0);
+    }
+
+    #[test]
+    fn render_pdf_works() {
+        let content = render_pdf(&video(), None, None).unwrap();
+        assert!(content.starts_with(b"%PDF-"));
+        let text = String::from_utf8_lossy(&content);
+        assert!(text.contains("/BaseFont /Helvetica-Bold"));
+        assert!(text.contains("(https://www.youtube.com/watch?v=FdeioVndUhs&t=65)"));
+        // 200 transcript lines do not fit on one page
+        let pages = text.matches("/Type /Page\n").count();
+        assert!(pages > 1, "{pages} pages");
+
+        // a chinese summary would only be question marks
+        let mut chinese = video();
+        chinese.summary = Some("## 要点\n- 第一".to_string());
+        let error = render_pdf(&chinese, None, None).unwrap_err();
+        assert!(error.contains("\"要\""), "{error}");
+        let mut accents = video();
+        accents.title = "Café — “naïve” résumé".to_string();
+        assert!(unsupported_chars(&accents).is_empty());
+        assert!(render_pdf(&accents, None, None).is_ok());
+    }
+
+    #[test]
+    fn fallback_font_is_embedded() {
+        // any truetype font with cyrillic does, the test is skipped where none is installed
+        let mut russian = video();
+        russian.summary = Some("## Итоги\n- первый".to_string());
+        let chars = unsupported_chars(&russian);
+        assert!(chars.contains(&'И'));
+        let Some(font) = FallbackFont::find(&chars) else {
+            return;
+        };
+        let content = render_pdf(&russian, None, Some(&font)).unwrap();
+        let text = String::from_utf8_lossy(&content);
+        assert!(text.contains("/Subtype /Type0"));
+        assert!(text.contains("/Encoding /Identity-H"));
+        assert!(text.contains("/Subtype /CIDFontType2"));
+        assert!(text.contains("/FontFile2"));
+        assert!(text.contains("/F3 "));
+        // the latin text around it keeps the standard fonts
+        assert!(text.contains("/BaseFont /Helvetica-Bold"));
+    }
+}
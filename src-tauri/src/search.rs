@@ -0,0 +1,161 @@
+use tauri::Manager;
+use tube_rs::{SearchFilter, SearchResult, YoutubeAudio};
+
+use super::db;
+use super::events::{self, Phase, Sender};
+use super::jobs::{self, JobKind};
+use super::setting;
+use super::whisper::{self, SummaryMode};
+
+fn get_compare_prompt(language: &str) -> String {
+    format!(
+        r#"
+        You are an assistant specializing in comparing several videos about the same topic. Below are the summaries of each video (each starts with `### Video Number: Title - Channel`). Compare them instead of summarizing them one by one.
+        Guiding principles:
+        - Point out what all videos agree on and where they disagree or contradict each other.
+        - Mention which video covers a point best, refer to videos by their number and title.
+        - Highlight unique insights that only one video provides.
+        - Use emojis where appropriate.
+        The final output should be formatted as follows:
+        ## Common Ground:
+        - Point shared by the videos (Video numbers)
+        ...
+        ## Differences:
+        - Point of disagreement and how each video sees it
+        ...
+        ## Unique Insights:
+        - Video number - Insight
+        ...
+        ## Recommendation:
+        One short paragraph about which video to watch for which need.
+        Write the whole answer in {}.
+        Here are the video summaries:
+        "#,
+        whisper::language_name(language)
+    )
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn search_youtube(
+    app: tauri::AppHandle,
+    query: String,
+    filter: Option<SearchFilter>,
+    max_results: usize,
+) -> Result<Vec<SearchResult>, String> {
+    let youtube_audio = YoutubeAudio::new(
+        setting::get_proxy(&app).as_deref(),
+        setting::get_tube_api_url(&app),
+    );
+    youtube_audio
+        .search(&query, &filter.unwrap_or_default(), max_results)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// import and summarize one search result unless that was done before; returns its title,
+// channel and summary
+async fn summarize_result(
+    app: &tauri::AppHandle,
+    result: &SearchResult,
+    language: &str,
+) -> Result<(String, String, String), String> {
+    let url = format!("https://www.youtube.com/watch?v={}", result.video_id);
+    let id = match db::find_id_with_video_id(app.state(), &result.video_id)? {
+        Some(id) => id,
+        None => {
+            let import = JobKind::Import {
+                url: url.clone(),
+                video_id: None,
+            };
+            jobs::run(app, import).await?;
+            db::find_id_with_video_id(app.state(), &result.video_id)?
+                .ok_or(format!("{url} was not imported"))?
+        }
+    };
+    // a video another run is working on is left to it
+    if let Some(stage) = db::get_status(app.state(), id)?.filter(|stage| stage.is_active()) {
+        return Err(format!(
+            "this video is already {}",
+            stage.as_str().replace('_', " ")
+        ));
+    }
+    let (_, has_transcripts, _) = db::get_progress_with_id(app.state(), id)?;
+    if !has_transcripts {
+        let import = JobKind::Import {
+            url: url.clone(),
+            video_id: Some(id),
+        };
+        jobs::run(app, import).await?;
+    }
+    let (title, _, summary) = db::get_progress_with_id(app.state(), id)?;
+    let summary = match summary {
+        Some(summary) => summary,
+        None => {
+            let summarize = JobKind::Summarize {
+                video_id: id,
+                language: language.to_string(),
+                include_chat: false,
+                mode: SummaryMode::Standard,
+            };
+            jobs::run(app, summarize).await?;
+            let (_, _, summary) = db::get_progress_with_id(app.state(), id)?;
+            summary.ok_or(format!("{url} was not summarized"))?
+        }
+    };
+    Ok((title, result.channel.clone(), summary))
+}
+
+// import and summarize the top results of a query, then compare them in one cross-video summary
+#[tauri::command(rename_all = "snake_case")]
+pub async fn summarize_search_results(
+    app: tauri::AppHandle,
+    query: String,
+    filter: Option<SearchFilter>,
+    top_n: usize,
+    language: String,
+) -> Result<String, String> {
+    let results = search_youtube(app.clone(), query, filter, top_n).await?;
+    if results.is_empty() {
+        return Err("no search results found".to_string());
+    }
+
+    // one result that can not be summarized does not stop the others
+    let mut summaries = Vec::new();
+    let mut skipped = Vec::new();
+    for result in &results {
+        match summarize_result(&app, result, &language).await {
+            Ok(summary) => summaries.push(summary),
+            Err(error) => skipped.push(format!("- {}: {error}", result.title)),
+        }
+    }
+    if summaries.is_empty() {
+        return Err(format!(
+            "no search result could be summarized\n{}",
+            skipped.join("\n")
+        ));
+    }
+
+    let mut content = String::new();
+    for (index, (title, channel, summary)) in summaries.iter().enumerate() {
+        content.push_str(&format!(
+            "### Video {}: {title} - {channel}\n{summary}\n\n",
+            index + 1
+        ));
+    }
+
+    let sender = Sender::new(&app, events::SEARCH_SUMMARY, None, Phase::Summarizing);
+    let cancel = sender.cancel_token();
+    let mut comparison = sender
+        .run(whisper::chat_completion_stream(
+            &app,
+            &sender,
+            get_compare_prompt(&language),
+            content,
+            &cancel,
+        ))
+        .await?;
+    if !skipped.is_empty() {
+        comparison.push_str(&format!("\n\n## Skipped:\n{}\n", skipped.join("\n")));
+    }
+    Ok(comparison)
+}
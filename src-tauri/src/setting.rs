@@ -14,6 +14,14 @@ pub struct AppSettings {
     pub whisper_model_name: Option<String>,
     pub proxy: Option<String>,
     pub tube_api_url: Option<String>,
+    // folder inside an obsidian vault that markdown exports are written to
+    pub vault_folder: Option<String>,
+    // how many jobs download, transcribe or call the llm at once, read at startup
+    pub download_workers: Option<usize>,
+    pub transcription_workers: Option<usize>,
+    pub llm_workers: Option<usize>,
+    // megabytes the working files of failed runs may take in the cache, read at startup
+    pub cache_limit_mb: Option<u64>,
 }
 
 pub fn get_config_path(app: &tauri::AppHandle) -> PathBuf {
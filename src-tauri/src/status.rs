@@ -0,0 +1,303 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use tauri::{Emitter, Manager};
+use tokio_util::sync::CancellationToken;
+
+use super::db;
+use super::events;
+
+// the processing state of a video, persisted so an interrupted run leaves a trace:
+// queued → fetching_metadata → downloading → splitting → transcribing → summarizing → done,
+// or failed with the error from any of them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    Queued,
+    FetchingMetadata,
+    Downloading,
+    Splitting,
+    Transcribing,
+    Summarizing,
+    Done,
+    Failed,
+}
+
+impl Stage {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Stage::Queued => "queued",
+            Stage::FetchingMetadata => "fetching_metadata",
+            Stage::Downloading => "downloading",
+            Stage::Splitting => "splitting",
+            Stage::Transcribing => "transcribing",
+            Stage::Summarizing => "summarizing",
+            Stage::Done => "done",
+            Stage::Failed => "failed",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Stage> {
+        [
+            Stage::Queued,
+            Stage::FetchingMetadata,
+            Stage::Downloading,
+            Stage::Splitting,
+            Stage::Transcribing,
+            Stage::Summarizing,
+            Stage::Done,
+            Stage::Failed,
+        ]
+        .into_iter()
+        .find(|stage| stage.as_str() == value)
+    }
+
+    // a run is going on for the video
+    pub fn is_active(self) -> bool {
+        !matches!(self, Stage::Queued | Stage::Done | Stage::Failed)
+    }
+}
+
+// a run starts from a video at rest, then only moves forward, skipping stages it does not need,
+// until it comes to rest again; a cancelled run goes back to where the video was
+pub fn can_move(from: Stage, to: Stage) -> bool {
+    if !from.is_active() || !to.is_active() {
+        return true;
+    }
+    to as u8 >= from as u8
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StatusEntry {
+    pub stage: Stage,
+    pub error: Option<String>,
+    pub created_at: i64,
+}
+
+// the event sent to the ui whenever a video moves to another stage
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct StatusEvent {
+    pub video_id: i64,
+    pub stage: Stage,
+    pub error: Option<String>,
+}
+
+fn read_stage(value: String) -> rusqlite::Result<Stage> {
+    Stage::parse(&value).ok_or(rusqlite::Error::InvalidColumnType(
+        0,
+        value,
+        rusqlite::types::Type::Text,
+    ))
+}
+
+pub fn current(connection: &Connection, id: i64) -> rusqlite::Result<Option<Stage>> {
+    connection
+        .query_row(
+            "SELECT status FROM audio WHERE id = ?1",
+            params![id],
+            |row| row.get(0),
+        )
+        .optional()?
+        .map(read_stage)
+        .transpose()
+}
+
+// move a video to `stage` and append it to the history; returns false when the move is not allowed
+pub fn record(
+    connection: &Connection,
+    id: i64,
+    stage: Stage,
+    error: Option<&str>,
+) -> rusqlite::Result<bool> {
+    if let Some(from) = current(connection, id)? {
+        if !can_move(from, stage) {
+            return Ok(false);
+        }
+    }
+    connection.execute(
+        "UPDATE audio SET status = ?2, status_error = ?3 WHERE id = ?1",
+        params![id, stage.as_str(), error],
+    )?;
+    connection.execute(
+        "INSERT INTO status_history (audio_id, stage, error) VALUES (?1, ?2, ?3)",
+        params![id, stage.as_str(), error],
+    )?;
+    Ok(true)
+}
+
+pub fn history(connection: &Connection, id: i64) -> rusqlite::Result<Vec<StatusEntry>> {
+    let mut stmt = connection.prepare(
+        "SELECT stage, error, created_at FROM status_history WHERE audio_id = ?1 ORDER BY id",
+    )?;
+    let entry_iter = stmt.query_map(params![id], |row| {
+        Ok(StatusEntry {
+            stage: read_stage(row.get(0)?)?,
+            error: row.get(1)?,
+            created_at: row.get(2)?,
+        })
+    })?;
+    entry_iter.collect()
+}
+
+// the stage a failed video stopped at, the last one recorded before the failure
+pub fn failed_stage(connection: &Connection, id: i64) -> rusqlite::Result<Option<Stage>> {
+    let history = history(connection, id)?;
+    let mut stages = history.iter().rev().map(|entry| entry.stage);
+    if stages.next() != Some(Stage::Failed) {
+        return Ok(None);
+    }
+    Ok(stages.next())
+}
+
+// start a run on a video at `stage`; returns the stage of the run already going on instead
+pub fn begin(connection: &Connection, id: i64, stage: Stage) -> rusqlite::Result<Option<Stage>> {
+    match current(connection, id)? {
+        Some(from) if from.is_active() => Ok(Some(from)),
+        _ => record(connection, id, stage, None).map(|_| None),
+    }
+}
+
+// runs cut off by a crash or a quit are still in an active stage at startup
+pub fn fail_interrupted(connection: &Connection) -> rusqlite::Result<usize> {
+    let mut stmt = connection.prepare(
+        "SELECT id FROM audio
+        WHERE status NOT IN ('queued', 'done', 'failed')",
+    )?;
+    let ids = stmt
+        .query_map([], |row| row.get::<_, i64>(0))?
+        .collect::<rusqlite::Result<Vec<i64>>>()?;
+    for id in &ids {
+        record(
+            connection,
+            *id,
+            Stage::Failed,
+            Some("interrupted, the app was closed while processing"),
+        )?;
+    }
+    Ok(ids.len())
+}
+
+fn emit(app: &tauri::AppHandle, id: i64, stage: Stage, error: Option<&str>) -> Result<(), String> {
+    let event = StatusEvent {
+        video_id: id,
+        stage,
+        error: error.map(str::to_string),
+    };
+    app.emit(events::STATUS, event).map_err(|e| e.to_string())
+}
+
+// move a video to the next stage of the current run and tell the ui
+pub fn update(app: &tauri::AppHandle, id: i64, stage: Stage) -> Result<(), String> {
+    db::set_status(app.state(), id, stage, None)?;
+    emit(app, id, stage, None)
+}
+
+// one run on a video from `first` stage to done, or to failed with the error it stopped on;
+// refused while another run is going on for the same video
+pub async fn track<T>(
+    app: &tauri::AppHandle,
+    id: i64,
+    first: Stage,
+    cancel: &CancellationToken,
+    run: impl Future<Output = Result<T, String>>,
+) -> Result<T, String> {
+    let previous = db::begin_status(app.state(), id, first)?;
+    emit(app, id, first, None)?;
+    let (stage, error) = match run.await {
+        Ok(value) => {
+            db::set_status(app.state(), id, Stage::Done, None)?;
+            emit(app, id, Stage::Done, None)?;
+            return Ok(value);
+        }
+        // nothing went wrong with the video, it is left as it was before
+        Err(error) if cancel.is_cancelled() => (previous, error),
+        Err(error) => (Stage::Failed, error),
+    };
+    let message = (stage == Stage::Failed).then_some(error.as_str());
+    db::set_status(app.state(), id, stage, message)?;
+    emit(app, id, stage, message)?;
+    Err(error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::migrations;
+    use super::*;
+    use std::path::Path;
+
+    fn connection() -> Connection {
+        let mut connection = Connection::open_in_memory().unwrap();
+        connection
+            .pragma_update(None, "foreign_keys", true)
+            .unwrap();
+        migrations::migrate(&mut connection, Path::new(":memory:")).unwrap();
+        connection
+            .execute(
+                "INSERT INTO audio (
+                    video_id, title, duration, upload_date, audio_url, audio_filesize,
+                    mime_type, thumbnail_url
+                ) VALUES ('abc', 'abc', 0, 0, '', 0, '', '')",
+                [],
+            )
+            .unwrap();
+        connection
+    }
+
+    #[test]
+    fn transitions_work() {
+        assert!(can_move(Stage::Queued, Stage::Downloading));
+        assert!(can_move(Stage::Downloading, Stage::Transcribing));
+        assert!(can_move(Stage::Transcribing, Stage::Failed));
+        assert!(can_move(Stage::Failed, Stage::FetchingMetadata));
+        assert!(can_move(Stage::Done, Stage::Summarizing));
+        assert!(!can_move(Stage::Transcribing, Stage::Downloading));
+        assert!(can_move(Stage::Splitting, Stage::Splitting));
+        assert!(!can_move(Stage::Summarizing, Stage::Transcribing));
+        assert!(can_move(Stage::Summarizing, Stage::Done));
+        assert!(can_move(Stage::Downloading, Stage::Queued));
+        assert_eq!(
+            Stage::parse("fetching_metadata"),
+            Some(Stage::FetchingMetadata)
+        );
+        assert_eq!(Stage::parse("unknown"), None);
+    }
+
+    #[test]
+    fn history_keeps_every_stage() {
+        let connection = connection();
+        assert_eq!(current(&connection, 1).unwrap(), Some(Stage::Queued));
+        assert_eq!(begin(&connection, 1, Stage::Downloading).unwrap(), None);
+        assert!(!record(&connection, 1, Stage::FetchingMetadata, None).unwrap());
+        // a second run waits for the first one
+        assert_eq!(
+            begin(&connection, 1, Stage::Summarizing).unwrap(),
+            Some(Stage::Downloading)
+        );
+        assert!(record(&connection, 1, Stage::Transcribing, None).unwrap());
+        assert_eq!(failed_stage(&connection, 1).unwrap(), None);
+
+        // the app quit while transcribing
+        assert_eq!(fail_interrupted(&connection).unwrap(), 1);
+        assert_eq!(fail_interrupted(&connection).unwrap(), 0);
+        assert_eq!(current(&connection, 1).unwrap(), Some(Stage::Failed));
+        assert_eq!(
+            failed_stage(&connection, 1).unwrap(),
+            Some(Stage::Transcribing)
+        );
+        let stages: Vec<Stage> = history(&connection, 1)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.stage)
+            .collect();
+        assert_eq!(
+            stages,
+            [Stage::Downloading, Stage::Transcribing, Stage::Failed]
+        );
+        let error: Option<String> = connection
+            .query_row("SELECT status_error FROM audio WHERE id = 1", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert!(error.unwrap().starts_with("interrupted"));
+    }
+}
@@ -0,0 +1,472 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tauri::Manager;
+
+use super::db;
+use super::events::{self, Change};
+use super::whisper::Segment;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SubtitleFormat {
+    Srt,
+    Vtt,
+    // ass and its older ssa dialect share the same event lines
+    Ass,
+    Ttml,
+}
+
+impl SubtitleFormat {
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "srt" => Some(Self::Srt),
+            "vtt" | "webvtt" => Some(Self::Vtt),
+            "ass" | "ssa" => Some(Self::Ass),
+            "ttml" | "dfxp" | "xml" => Some(Self::Ttml),
+            _ => None,
+        }
+    }
+
+    // guess from the content when the file has no useful extension
+    pub fn detect(content: &str) -> Option<Self> {
+        let head = content.trim_start_matches('\u{feff}').trim_start();
+        if head.starts_with("WEBVTT") {
+            Some(Self::Vtt)
+        } else if head.starts_with("[Script Info]") || content.contains("\nDialogue:") {
+            Some(Self::Ass)
+        } else if head.starts_with("<?xml") || head.starts_with("<tt") {
+            Some(Self::Ttml)
+        } else if content.contains("-->") {
+            Some(Self::Srt)
+        } else {
+            None
+        }
+    }
+}
+
+pub fn parse_subtitles(content: &str, format: SubtitleFormat) -> Vec<Segment> {
+    let content = content.trim_start_matches('\u{feff}').replace("\r\n", "\n");
+    match format {
+        SubtitleFormat::Srt => parse_srt(&content),
+        SubtitleFormat::Vtt => parse_vtt(&content),
+        SubtitleFormat::Ass => parse_ass(&content),
+        SubtitleFormat::Ttml => parse_ttml(&content),
+    }
+}
+
+// "01:02:03,456", "01:02:03.456", "02:03.456" or the ass style "1:02:03.45" to seconds
+fn parse_clock(text: &str) -> Option<f64> {
+    let text = text.trim();
+    let (clock, fraction) = match text.find(['.', ',']) {
+        Some(index) => (&text[..index], &text[index + 1..]),
+        None => (text, "0"),
+    };
+    let seconds = clock
+        .split(':')
+        .map(|part| part.trim().parse::<u64>().ok())
+        .try_fold(0, |total, part| part.map(|part| total * 60 + part))?;
+    let fraction: f64 = format!("0.{fraction}").parse().ok()?;
+    Some(seconds as f64 + fraction)
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&#39;", "'")
+        .replace("&nbsp;", " ")
+        .replace("&lrm;", "")
+        .replace("&rlm;", "")
+        .replace("&amp;", "&")
+}
+
+// join the lines of a cue and drop the markup left in them
+fn clean_text(lines: &[&str], tag_re: &Regex) -> String {
+    let text = lines
+        .iter()
+        .map(|line| tag_re.replace_all(line.trim(), "").trim().to_string())
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<String>>()
+        .join(" ");
+    decode_entities(&text)
+}
+
+// the "start --> end" line shared by srt and webvtt, anything after the end time is cue settings
+fn parse_timing(line: &str) -> Option<(f64, f64)> {
+    let (start, end) = line.split_once("-->")?;
+    let end = end.split_whitespace().next()?;
+    Some((parse_clock(start)?, parse_clock(end)?))
+}
+
+fn parse_srt(content: &str) -> Vec<Segment> {
+    // html style tags and the `{\an8}` position overrides some editors write
+    let tag_re = Regex::new(r"<[^>]+>|\{\\[^}]*\}").unwrap();
+    let mut segments = Vec::new();
+    for block in content.split("\n\n") {
+        let lines: Vec<&str> = block.lines().filter(|l| !l.trim().is_empty()).collect();
+        let Some(timing_index) = lines.iter().position(|line| line.contains("-->")) else {
+            continue;
+        };
+        let Some((start, end)) = parse_timing(lines[timing_index]) else {
+            continue;
+        };
+        let text = clean_text(&lines[timing_index + 1..], &tag_re);
+        if !text.is_empty() {
+            segments.push(Segment {
+                start,
+                end,
+                text,
+                speaker: None,
+            });
+        }
+    }
+    segments
+}
+
+fn parse_vtt(content: &str) -> Vec<Segment> {
+    // voice spans, classes and the `<00:00:01.000>` karaoke timestamps
+    let tag_re = Regex::new(r"<[^>]+>").unwrap();
+    let voice_re = Regex::new(r"<v(?:\.[^\s>]*)?\s+([^>]+)>").unwrap();
+    let mut segments: Vec<Segment> = Vec::new();
+    let mut last_line = String::new();
+    for block in content.split("\n\n") {
+        let lines: Vec<&str> = block.lines().filter(|l| !l.trim().is_empty()).collect();
+        let Some(timing_index) = lines.iter().position(|line| line.contains("-->")) else {
+            // header, NOTE, STYLE and REGION blocks
+            continue;
+        };
+        let Some((start, end)) = parse_timing(lines[timing_index]) else {
+            continue;
+        };
+        let mut cue_lines: Vec<String> = lines[timing_index + 1..]
+            .iter()
+            .map(|line| clean_text(&[line], &tag_re))
+            .filter(|line| !line.is_empty())
+            .collect();
+        // rolling captions repeat the previous line on top of the new one
+        if cue_lines.first() == Some(&last_line) {
+            cue_lines.remove(0);
+        }
+        let Some(new_last_line) = cue_lines.last() else {
+            continue;
+        };
+        last_line = new_last_line.clone();
+        let speaker = lines[timing_index + 1..]
+            .iter()
+            .find_map(|line| voice_re.captures(line))
+            .map(|voice| decode_entities(voice[1].trim()));
+        segments.push(Segment {
+            start,
+            end,
+            text: cue_lines.join(" "),
+            speaker,
+        });
+    }
+    segments
+}
+
+fn parse_ass(content: &str) -> Vec<Segment> {
+    let override_re = Regex::new(r"\{[^}]*\}").unwrap();
+    // the default field order of [Events], replaced by the section's own Format line
+    let mut fields: Vec<String> = [
+        "layer", "start", "end", "style", "name", "marginl", "marginr", "marginv", "effect", "text",
+    ]
+    .iter()
+    .map(|f| f.to_string())
+    .collect();
+    let mut in_events = false;
+    let mut segments = Vec::new();
+    for line in content.lines() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            in_events = line.eq_ignore_ascii_case("[events]");
+            continue;
+        }
+        if !in_events {
+            continue;
+        }
+        let Some((kind, value)) = line.split_once(':') else {
+            continue;
+        };
+        match kind.trim() {
+            "Format" => {
+                fields = value
+                    .split(',')
+                    .map(|f| f.trim().to_ascii_lowercase())
+                    .collect();
+            }
+            "Dialogue" => {
+                // the text is the last field and may contain commas itself
+                let values: Vec<&str> = value.splitn(fields.len(), ',').collect();
+                let field = |name: &str| {
+                    fields
+                        .iter()
+                        .position(|f| f == name)
+                        .and_then(|index| values.get(index))
+                };
+                let (Some(start), Some(end), Some(text)) = (
+                    field("start").and_then(|v| parse_clock(v)),
+                    field("end").and_then(|v| parse_clock(v)),
+                    field("text"),
+                ) else {
+                    continue;
+                };
+                let text = override_re
+                    .replace_all(text, "")
+                    .replace("\\N", " ")
+                    .replace("\\n", " ")
+                    .replace("\\h", " ");
+                let text = text.split_whitespace().collect::<Vec<&str>>().join(" ");
+                let speaker = field("name")
+                    .map(|name| name.trim())
+                    .filter(|name| !name.is_empty())
+                    .map(str::to_string);
+                if !text.is_empty() {
+                    segments.push(Segment {
+                        start,
+                        end,
+                        text,
+                        speaker,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+    segments.sort_by(|a, b| a.start.total_cmp(&b.start));
+    segments
+}
+
+struct TtmlTiming {
+    frame_rate: f64,
+    tick_rate: f64,
+}
+
+impl TtmlTiming {
+    // clock time "00:00:01.500" or "00:00:01:12" with frames, or offset time like "1.5s" and "10t"
+    fn parse(&self, value: &str) -> Option<f64> {
+        let value = value.trim();
+        if value.contains(':') {
+            let parts: Vec<&str> = value.split(':').collect();
+            if parts.len() == 4 {
+                let seconds = parse_clock(&parts[..3].join(":"))?;
+                let frames: f64 = parts[3].parse().ok()?;
+                return Some(seconds + frames / self.frame_rate);
+            }
+            return parse_clock(value);
+        }
+        let unit_index = value.find(|c: char| c.is_ascii_alphabetic())?;
+        let (number, unit) = value.split_at(unit_index);
+        let number: f64 = number.parse().ok()?;
+        match unit {
+            "h" => Some(number * 3600.0),
+            "m" => Some(number * 60.0),
+            "s" => Some(number),
+            "ms" => Some(number / 1000.0),
+            "f" => Some(number / self.frame_rate),
+            "t" => Some(number / self.tick_rate),
+            _ => None,
+        }
+    }
+}
+
+fn parse_ttml(content: &str) -> Vec<Segment> {
+    let attribute_re = Regex::new(r#"([\w:]+)\s*=\s*"([^"]*)""#).unwrap();
+    let paragraph_re = Regex::new(r"(?s)<(?:\w+:)?p\b([^>]*)>(.*?)</(?:\w+:)?p>").unwrap();
+    let break_re = Regex::new(r"<(?:\w+:)?br\s*/?>").unwrap();
+    let tag_re = Regex::new(r"<[^>]+>").unwrap();
+
+    let root_attribute = |name: &str| {
+        let root_start = content.find("<tt")?;
+        let root_end = content[root_start..].find('>')? + root_start;
+        attribute_re
+            .captures_iter(&content[root_start..root_end])
+            .find(|cap| cap[1].ends_with(name))
+            .and_then(|cap| cap[2].parse::<f64>().ok())
+    };
+    let timing = TtmlTiming {
+        frame_rate: root_attribute("frameRate").unwrap_or(30.0),
+        tick_rate: root_attribute("tickRate").unwrap_or(1.0),
+    };
+
+    let mut segments = Vec::new();
+    for paragraph in paragraph_re.captures_iter(content) {
+        let attribute = |name: &str| {
+            attribute_re
+                .captures_iter(&paragraph[1])
+                .find(|cap| &cap[1] == name)
+                .and_then(|cap| timing.parse(&cap[2]))
+        };
+        let Some(start) = attribute("begin") else {
+            continue;
+        };
+        let Some(end) = attribute("end").or_else(|| attribute("dur").map(|dur| start + dur)) else {
+            continue;
+        };
+        let text = break_re.replace_all(&paragraph[2], " ");
+        let text = decode_entities(&tag_re.replace_all(&text, ""));
+        let text = text.split_whitespace().collect::<Vec<&str>>().join(" ");
+        if !text.is_empty() {
+            segments.push(Segment {
+                start,
+                end,
+                text,
+                speaker: None,
+            });
+        }
+    }
+    segments
+}
+
+// read a subtitle file into the transcripts of a video, so it does not need to be transcribed
+#[tauri::command(rename_all = "snake_case")]
+pub fn import_subtitle_file(
+    app: tauri::AppHandle,
+    video_id: i64,
+    path: String,
+) -> Result<usize, String> {
+    let content = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+    let format = Path::new(&path)
+        .extension()
+        .and_then(|extension| SubtitleFormat::from_extension(&extension.to_string_lossy()))
+        .or(SubtitleFormat::detect(&content))
+        .ok_or(format!("unknown subtitle format: {path}"))?;
+    let segments = parse_subtitles(&content, format);
+    if segments.is_empty() {
+        return Err(format!("no subtitles found in {path}"));
+    }
+
+    db::save_segments(app.state(), video_id, &segments, "subtitle_file")?;
+    events::library_changed(&app, video_id, Change::Updated)?;
+    Ok(segments.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::export::{render_segments, ExportFormat, ExportOptions};
+    use super::*;
+
+    fn expected() -> Vec<Segment> {
+        vec![
+            Segment {
+                start: 1.0,
+                end: 3.5,
+                text: "Hello & welcome".to_string(),
+                speaker: None,
+            },
+            Segment {
+                start: 62.25,
+                end: 65.0,
+                text: "to the second line, with commas".to_string(),
+                speaker: None,
+            },
+        ]
+    }
+
+    #[test]
+    fn parse_clock_works() {
+        assert_eq!(parse_clock("00:01:02,500"), Some(62.5));
+        assert_eq!(parse_clock("01:02.500"), Some(62.5));
+        assert_eq!(parse_clock("0:01:02.25"), Some(62.25));
+        assert_eq!(parse_clock("1:00:00"), Some(3600.0));
+        assert_eq!(parse_clock("bad"), None);
+    }
+
+    #[test]
+    fn srt_round_trip() {
+        let content = "1\r\n00:00:01,000 --> 00:00:03,500\r\n<i>Hello</i> &amp;\r\nwelcome\r\n\r\n\
+            2\r\n00:01:02,250 --> 00:01:05,000\r\n{\\an8}to the second line, with commas\r\n";
+        assert_eq!(SubtitleFormat::detect(content), Some(SubtitleFormat::Srt));
+        assert_eq!(parse_subtitles(content, SubtitleFormat::Srt), expected());
+
+        // what the export writes reads back the same
+        let rendered =
+            render_segments(&expected(), ExportFormat::Srt, &ExportOptions::default()).unwrap();
+        assert_eq!(SubtitleFormat::detect(&rendered), Some(SubtitleFormat::Srt));
+        assert_eq!(parse_subtitles(&rendered, SubtitleFormat::Srt), expected());
+    }
+
+    #[test]
+    fn vtt_round_trip() {
+        let content = "WEBVTT\nKind: captions\n\nNOTE written by hand\n\n\
+            intro\n00:01.000 --> 00:03.500 align:start position:10%\n\
+            <v Bob>Hello</v> <c.yellow>&amp;</c><00:00:02.000><c> welcome</c>\n\n\
+            00:01:02.250 --> 00:01:05.000\nHello &amp; welcome\nto the second line, with commas\n";
+        // the voice span names the speaker of its cue
+        let mut voiced = expected();
+        voiced[0].speaker = Some("Bob".to_string());
+        assert_eq!(SubtitleFormat::detect(content), Some(SubtitleFormat::Vtt));
+        assert_eq!(parse_subtitles(content, SubtitleFormat::Vtt), voiced);
+
+        let rendered =
+            render_segments(&voiced, ExportFormat::Vtt, &ExportOptions::default()).unwrap();
+        assert_eq!(SubtitleFormat::detect(&rendered), Some(SubtitleFormat::Vtt));
+        assert_eq!(parse_subtitles(&rendered, SubtitleFormat::Vtt), voiced);
+    }
+
+    #[test]
+    fn ass_round_trip() {
+        let content = "[Script Info]\nScriptType: v4.00+\n\n[V4+ Styles]\n\
+            Format: Name, Fontname\nStyle: Default,Arial\n\n[Events]\n\
+            Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n\
+            Dialogue: 0,0:01:02.25,0:01:05.00,Default,,0,0,0,,to the second line, with commas\n\
+            Comment: 0,0:00:00.00,0:00:01.00,Default,,0,0,0,,ignored\n\
+            Dialogue: 0,0:00:01.00,0:00:03.50,Default,Alice,0,0,0,,{\\b1}Hello{\\b0} &\\Nwelcome\n";
+        let mut voiced = expected();
+        voiced[0].speaker = Some("Alice".to_string());
+        assert_eq!(SubtitleFormat::detect(content), Some(SubtitleFormat::Ass));
+        assert_eq!(parse_subtitles(content, SubtitleFormat::Ass), voiced);
+
+        let rendered =
+            render_segments(&voiced, ExportFormat::Ass, &ExportOptions::default()).unwrap();
+        assert_eq!(SubtitleFormat::detect(&rendered), Some(SubtitleFormat::Ass));
+        assert_eq!(parse_subtitles(&rendered, SubtitleFormat::Ass), voiced);
+    }
+
+    #[test]
+    fn ttml_round_trip() {
+        let content = r#"<?xml version="1.0" encoding="UTF-8"?>
+<tt xmlns="http://www.w3.org/ns/ttml" xmlns:ttp="http://www.w3.org/ns/ttml#parameter" ttp:tickRate="10000000">
+  <body><div>
+    <p begin="10000000t" end="35000000t">Hello &amp;<br/>welcome</p>
+    <p begin="00:01:02.250" dur="2.75s"><span tts:color="white">to the second line,</span> with commas</p>
+  </div></body>
+</tt>"#;
+        assert_eq!(SubtitleFormat::detect(content), Some(SubtitleFormat::Ttml));
+        assert_eq!(parse_subtitles(content, SubtitleFormat::Ttml), expected());
+
+        let rendered =
+            render_segments(&expected(), ExportFormat::Ttml, &ExportOptions::default()).unwrap();
+        assert_eq!(
+            SubtitleFormat::detect(&rendered),
+            Some(SubtitleFormat::Ttml)
+        );
+        assert_eq!(parse_subtitles(&rendered, SubtitleFormat::Ttml), expected());
+    }
+
+    #[test]
+    fn ttml_frames_work() {
+        let timing = TtmlTiming {
+            frame_rate: 25.0,
+            tick_rate: 1.0,
+        };
+        assert_eq!(timing.parse("00:00:01:05"), Some(1.2));
+        assert_eq!(timing.parse("1500ms"), Some(1.5));
+        assert_eq!(timing.parse("2m"), Some(120.0));
+    }
+
+    #[test]
+    fn format_from_extension_works() {
+        assert_eq!(
+            SubtitleFormat::from_extension("SSA"),
+            Some(SubtitleFormat::Ass)
+        );
+        assert_eq!(
+            SubtitleFormat::from_extension("dfxp"),
+            Some(SubtitleFormat::Ttml)
+        );
+        assert_eq!(SubtitleFormat::from_extension("txt"), None);
+    }
+}
@@ -0,0 +1,189 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tauri::Manager;
+use tokio_util::sync::CancellationToken;
+
+use super::db;
+use super::events::{self, Phase, Sender};
+use super::utils;
+use super::whisper::{self, Segment};
+
+// segments per request, small enough for the answer to fit in one completion
+const BATCH_SIZE: usize = 40;
+const MAX_ATTEMPTS: usize = 3;
+
+#[derive(Debug, Deserialize, Serialize)]
+struct TranslationItem {
+    id: usize,
+    text: String,
+}
+
+fn get_translate_prompt(language: &str) -> String {
+    format!(
+        r#"
+        You are a professional subtitle translator. Translate every subtitle line below into {}.
+        Guiding principles:
+        - The input is a JSON array of objects with an `id` and a `text`.
+        - Translate each `text` on its own, keep the meaning and tone, and keep it short enough to read as a subtitle.
+        - Never merge, split, skip or reorder lines, and never change an `id`.
+        - Answer with the JSON array only, in exactly the same shape, without any explanation or code fence.
+        "#,
+        whisper::language_name(language)
+    )
+}
+
+fn parse_translation_items(answer: &str) -> Result<Vec<TranslationItem>, String> {
+    let json = utils::extract_json_array(answer).ok_or("no json array in the translation")?;
+    serde_json::from_str(json).map_err(|e| e.to_string())
+}
+
+// map every requested id to its translated text, fails with the ids the model left out
+fn validate_batch(
+    ids: &[usize],
+    items: Vec<TranslationItem>,
+) -> Result<HashMap<usize, String>, String> {
+    let mut translated: HashMap<usize, String> = items
+        .into_iter()
+        .filter(|item| ids.contains(&item.id))
+        .map(|item| (item.id, item.text.trim().to_string()))
+        .collect();
+    translated.retain(|_, text| !text.is_empty());
+    let missing: Vec<String> = ids
+        .iter()
+        .filter(|id| !translated.contains_key(id))
+        .map(|id| id.to_string())
+        .collect();
+    if !missing.is_empty() {
+        return Err(format!(
+            "translation is missing segment ids: {}",
+            missing.join(", ")
+        ));
+    }
+    Ok(translated)
+}
+
+async fn translate_batch(
+    app: &tauri::AppHandle,
+    sender: &Sender,
+    language: &str,
+    ids: &[usize],
+    segments: &[Segment],
+    cancel: &CancellationToken,
+) -> Result<HashMap<usize, String>, String> {
+    let items: Vec<TranslationItem> = ids
+        .iter()
+        .map(|id| TranslationItem {
+            id: *id,
+            text: segments[*id].text.trim().to_string(),
+        })
+        .collect();
+    let message = serde_json::to_string(&items).map_err(|e| e.to_string())?;
+
+    let mut last_error = String::new();
+    for _ in 0..MAX_ATTEMPTS {
+        let answer = whisper::chat_completion_stream(
+            app,
+            sender,
+            get_translate_prompt(language),
+            message.clone(),
+            cancel,
+        )
+        .await?;
+        match parse_translation_items(&answer).and_then(|items| validate_batch(ids, items)) {
+            Ok(translated) => return Ok(translated),
+            Err(e) => last_error = e,
+        }
+    }
+    Err(last_error)
+}
+
+// translate the stored transcripts in batches and store them next to the original
+#[tauri::command(rename_all = "snake_case")]
+pub async fn translate_transcript(
+    app: tauri::AppHandle,
+    video_id: i64,
+    language: String,
+) -> Result<Vec<Segment>, String> {
+    let (segments, _) = db::get_subtitle_with_id(app.state(), video_id)?;
+
+    let sender = Sender::new(
+        &app,
+        events::TRANSLATION,
+        Some(video_id),
+        Phase::Translating,
+    );
+    let cancel = sender.cancel_token();
+    let translate = async {
+        // the index in the transcripts is the stable id of a segment
+        let ids: Vec<usize> = (0..segments.len())
+            .filter(|id| !segments[*id].text.trim().is_empty())
+            .collect();
+        let batches: Vec<&[usize]> = ids.chunks(BATCH_SIZE).collect();
+        let mut texts = HashMap::new();
+        for (done, batch) in batches.iter().enumerate() {
+            sender.progress(done, batches.len())?;
+            texts.extend(
+                translate_batch(&app, &sender, &language, batch, &segments, &cancel).await?,
+            );
+        }
+        Ok(segments
+            .iter()
+            .enumerate()
+            .map(|(id, segment)| Segment {
+                start: segment.start,
+                end: segment.end,
+                text: texts.remove(&id).unwrap_or_default(),
+                speaker: segment.speaker.clone(),
+            })
+            .collect::<Vec<Segment>>())
+    };
+    let translated = sender.run(translate).await?;
+
+    db::save_translation(app.state(), video_id, &language, &translated)?;
+    Ok(translated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_translation_items_works() {
+        let answer =
+            "```json\n[{\"id\": 3, \"text\": \"Hallo\"}, {\"id\": 4, \"text\": \"Welt\"}]\n```";
+        let items = parse_translation_items(answer).unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[1].id, 4);
+        assert!(parse_translation_items("sorry, I can not").is_err());
+    }
+
+    #[test]
+    fn validate_batch_works() {
+        let items = vec![
+            TranslationItem {
+                id: 1,
+                text: " eins ".to_string(),
+            },
+            TranslationItem {
+                id: 2,
+                text: "zwei".to_string(),
+            },
+            TranslationItem {
+                id: 9,
+                text: "unknown".to_string(),
+            },
+        ];
+        let translated = validate_batch(&[1, 2], items).unwrap();
+        assert_eq!(translated.len(), 2);
+        assert_eq!(translated[&1], "eins");
+
+        let items = vec![TranslationItem {
+            id: 1,
+            text: "eins".to_string(),
+        }];
+        assert_eq!(
+            validate_batch(&[1, 2, 3], items).unwrap_err(),
+            "translation is missing segment ids: 2, 3"
+        );
+    }
+}
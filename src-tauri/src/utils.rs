@@ -1,14 +1,16 @@
+use crate::db::Note;
 use crate::whisper::Segment;
 use regex::Regex;
 use std::time::Duration;
-use tube_rs::SubtitleEntry;
+use tube_rs::{ChatMessage, HeatMarker, SubtitleEntry};
 
 struct TimelineEntry {
     timestamp: Duration,
     content: String,
 }
 
-fn parse_timeline(content: &str) -> Vec<TimelineEntry> {
+// chapters written in a video description as "mm:ss title", returns (seconds, title)
+pub fn parse_chapters(content: &str) -> Vec<(u64, String)> {
     let re = Regex::new(r"(\d+):(\d{2})(?:\s*[—-])?\s+(.+)").unwrap();
 
     content
@@ -18,16 +20,26 @@ fn parse_timeline(content: &str) -> Vec<TimelineEntry> {
                 let minutes: u64 = caps.get(1).unwrap().as_str().parse().unwrap_or(0);
                 let seconds: u64 = caps.get(2).unwrap().as_str().parse().unwrap_or(0);
                 let description = caps.get(3).unwrap().as_str().to_string();
-
-                TimelineEntry {
-                    timestamp: Duration::from_secs(minutes * 60 + seconds),
-                    content: format!("[{minutes:02}:{seconds:02} - {description}] \n"),
-                }
+                (minutes * 60 + seconds, description)
             })
         })
         .collect()
 }
 
+fn parse_timeline(content: &str) -> Vec<TimelineEntry> {
+    parse_chapters(content)
+        .into_iter()
+        .map(|(seconds, description)| TimelineEntry {
+            timestamp: Duration::from_secs(seconds),
+            content: format!(
+                "[{:02}:{:02} - {description}] \n",
+                seconds / 60,
+                seconds % 60
+            ),
+        })
+        .collect()
+}
+
 pub fn transform_subtitles_to_segments(subtitles: Vec<SubtitleEntry>) -> Vec<Segment> {
     let mut segments = Vec::new();
     for subtitle in subtitles {
@@ -35,6 +47,7 @@ pub fn transform_subtitles_to_segments(subtitles: Vec<SubtitleEntry>) -> Vec<Seg
             start: subtitle.timestamp as f64 / 1000.0,
             end: (subtitle.timestamp + subtitle.duration as u64) as f64 / 1000.0,
             text: subtitle.text,
+            speaker: None,
         })
     }
     segments
@@ -95,12 +108,53 @@ pub fn transform_segments_to_chunks(description: &str, segments: Vec<Segment>) -
     chunks
 }
 
+// file extension for a downloaded audio stream, ffmpeg picks the muxer from it when splitting
+pub fn audio_extension(mime_type: &str) -> &str {
+    let mime_type = mime_type.split(';').next().unwrap_or_default().trim();
+    match mime_type {
+        "audio/webm" | "video/webm" => "webm",
+        "audio/mpeg" | "audio/mp3" => "mp3",
+        "audio/ogg" | "audio/opus" => "ogg",
+        "audio/wav" | "audio/x-wav" | "audio/wave" => "wav",
+        "audio/flac" | "audio/x-flac" => "flac",
+        _ => "m4a",
+    }
+}
+
 fn convert_seconds_to_time(seconds: u64) -> String {
     let minutes = seconds / 60;
     let seconds = seconds % 60;
     format!("{:02}:{:02}", minutes, seconds)
 }
 
+// "01:05" or "1:01:05" once past the hour
+pub fn format_clock(seconds: u64) -> String {
+    if seconds >= 3600 {
+        format!(
+            "{}:{:02}:{:02}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        )
+    } else {
+        format!("{:02}:{:02}", seconds / 60, seconds % 60)
+    }
+}
+
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// the json array in a model answer, models like to wrap it in text or code fences
+pub fn extract_json_array(answer: &str) -> Option<&str> {
+    let start = answer.find('[')?;
+    let end = answer.rfind(']')?;
+    (start < end).then(|| &answer[start..=end])
+}
+
 pub fn transform_segment_to_string(segments: Vec<Segment>) -> String {
     let mut content = String::new();
     for segment in segments {
@@ -116,10 +170,111 @@ pub fn transform_segment_to_string(segments: Vec<Segment>) -> String {
     content
 }
 
+// live chat goes after the transcript as its own labeled block so the model never
+// mistakes viewer messages for what is said in the video
+pub fn transform_chat_to_string(messages: &[ChatMessage]) -> String {
+    let mut content = String::from(
+        "\n=== LIVE CHAT REPLAY (messages from viewers, not spoken in the video) ===\n",
+    );
+    for message in messages {
+        content.push_str(
+            format!(
+                "{} - {}: {}\n",
+                convert_seconds_to_time(message.offset / 1000),
+                message.author,
+                message.text
+            )
+            .as_ref(),
+        );
+    }
+    content.push_str("=== END OF LIVE CHAT REPLAY ===\n");
+    content
+}
+
+// pick the strongest replay peaks, skipping markers too close to an already chosen peak
+fn most_replayed_moments(markers: &[HeatMarker], count: usize) -> Vec<&HeatMarker> {
+    const MIN_GAP_MS: u64 = 30_000;
+
+    let mut ranked: Vec<&HeatMarker> = markers.iter().collect();
+    ranked.sort_by(|a, b| b.intensity.total_cmp(&a.intensity));
+
+    let mut peaks: Vec<&HeatMarker> = Vec::new();
+    for marker in ranked {
+        if peaks.len() >= count {
+            break;
+        }
+        if peaks
+            .iter()
+            .all(|peak| peak.start.abs_diff(marker.start) >= MIN_GAP_MS)
+        {
+            peaks.push(marker);
+        }
+    }
+    peaks.sort_by_key(|peak| peak.start);
+    peaks
+}
+
+pub fn transform_heatmap_to_string(markers: &[HeatMarker]) -> String {
+    let mut content = String::from(
+        "\n=== MOST REPLAYED MOMENTS (from the audience heatmap) ===\nViewers replayed the following moments the most. Give the chapters covering them more emphasis and a longer detailed explanation, and mention that they are the most replayed parts:\n",
+    );
+    for peak in most_replayed_moments(markers, 5) {
+        content.push_str(
+            format!(
+                "{} - {} (replay intensity {:.0}%)\n",
+                convert_seconds_to_time(peak.start / 1000),
+                convert_seconds_to_time((peak.start + peak.duration) / 1000),
+                peak.intensity * 100.0
+            )
+            .as_ref(),
+        );
+    }
+    content.push_str("=== END OF MOST REPLAYED MOMENTS ===\n");
+    content
+}
+
+pub const DEFAULT_NOTE_COLOR: &str = "yellow";
+const NOTE_COLORS: [&str; 6] = ["yellow", "green", "blue", "pink", "purple", "orange"];
+
+// a color from the highlighter palette or "#rrggbb", it ends up in exported html as is
+pub fn is_note_color(color: &str) -> bool {
+    NOTE_COLORS.contains(&color)
+        || (color.len() == 7
+            && color.starts_with('#')
+            && color[1..].chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+pub fn transform_notes_to_string(notes: &[Note]) -> String {
+    let mut content = String::from(
+        "\n=== MY HIGHLIGHTS (moments the user marked, with their own notes) ===\nFocus the summary on these moments: give the chapters covering them the most emphasis and a longer detailed explanation, relate them to the user's notes, and keep the rest of the content brief:\n",
+    );
+    for note in notes {
+        content.push_str(
+            format!(
+                "{} - {}: {}\n",
+                convert_seconds_to_time(note.start as u64),
+                convert_seconds_to_time(note.end.ceil() as u64),
+                note.text.trim()
+            )
+            .as_ref(),
+        );
+    }
+    content.push_str("=== END OF MY HIGHLIGHTS ===\n");
+    content
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn note_colors_work() {
+        assert!(is_note_color("yellow"));
+        assert!(is_note_color("#1a2B3c"));
+        assert!(!is_note_color("#12345"));
+        assert!(!is_note_color("red;background:url(x)"));
+    }
+
     #[test]
     fn test_simple_timestamp() {
         let input = "00:00 Simple description";
@@ -150,6 +305,44 @@ mod tests {
         assert_eq!(result.len(), 3);
     }
 
+    #[test]
+    fn test_chat_is_labeled() {
+        let messages = vec![ChatMessage {
+            offset: 65_000,
+            author: "viewer".to_string(),
+            text: "hello".to_string(),
+        }];
+        let result = transform_chat_to_string(&messages);
+        assert!(result.contains("LIVE CHAT REPLAY"));
+        assert!(result.contains("01:05 - viewer: hello"));
+    }
+
+    #[test]
+    fn test_most_replayed_moments() {
+        let marker = |start: u64, intensity: f64| HeatMarker {
+            start,
+            duration: 5_000,
+            intensity,
+        };
+        let markers = vec![
+            marker(0, 0.2),
+            marker(60_000, 1.0),
+            marker(65_000, 0.9),
+            marker(300_000, 0.7),
+        ];
+        let peaks = most_replayed_moments(&markers, 2);
+        assert_eq!(peaks.len(), 2);
+        assert_eq!(peaks[0].start, 60_000);
+        assert_eq!(peaks[1].start, 300_000);
+    }
+
+    #[test]
+    fn test_audio_extension() {
+        assert_eq!(audio_extension("audio/webm; codecs=\"opus\""), "webm");
+        assert_eq!(audio_extension("audio/mp4; codecs=\"mp4a.40.2\""), "m4a");
+        assert_eq!(audio_extension("audio/mpeg"), "mp3");
+    }
+
     #[test]
     fn test_invalid_input() {
         let input = "Invalid timestamp";
@@ -1,52 +1,26 @@
 use super::setting;
+use super::subtitles::{parse_subtitles, SubtitleFormat};
 use std::path::{Path, PathBuf};
-use std::time::Duration;
 use tauri::Manager;
 use tauri_plugin_shell::ShellExt;
-use tokio::fs::{self, File};
-use tokio::io::{AsyncBufReadExt, BufReader};
-
-fn parse_timestamp(timestamp: &str) -> Option<Duration> {
-    let parts: Vec<&str> = timestamp.split(&['.', ':'][..]).collect();
-    if parts.len() < 3 {
-        return None;
-    }
-    let hour: u64 = parts[0].parse().ok()?;
-    let minute: u64 = parts[1].parse().ok()?;
-    let second: u64 = parts[2].parse().ok()?;
-    let millis: u64 = parts.get(4).and_then(|&ms| ms.parse().ok()).unwrap_or(0);
-    Some(Duration::from_secs(hour * 3600 + minute * 60 + second) + Duration::from_millis(millis))
-}
+use tokio::fs;
 
 pub async fn extract_vtt_chunks(vtt_file: &Path) -> Result<Vec<String>, String> {
-    let interval = Duration::from_secs(500);
-    let mut last_split_duration = Duration::ZERO;
+    let interval = 500.0;
+    let mut last_split = 0.0;
 
     let mut chunks = Vec::new();
     let mut text_parse = Vec::new();
-    let file = File::open(vtt_file).await.map_err(|e| e.to_string())?;
-    let reader = BufReader::new(file);
-    let mut lines = reader.lines();
-
-    let mut is_inblock: bool = false;
-
-    while let Some(line) = lines.next_line().await.map_err(|e| e.to_string())? {
-        if line.contains("-->") {
-            if let Some((start, _)) = line.split_once(" --> ") {
-                is_inblock = true;
-                if let Some(start_time) = parse_timestamp(start) {
-                    if start_time >= last_split_duration + interval {
-                        last_split_duration = start_time;
-                        chunks.push(text_parse.join(" "));
-                        text_parse.clear();
-                    }
-                }
-            }
-        } else if !line.is_empty() & is_inblock {
-            text_parse.push(line);
-        } else if line.is_empty() {
-            is_inblock = false;
+    let content = fs::read_to_string(vtt_file)
+        .await
+        .map_err(|e| e.to_string())?;
+    for segment in parse_subtitles(&content, SubtitleFormat::Vtt) {
+        if segment.start >= last_split + interval {
+            last_split = segment.start;
+            chunks.push(text_parse.join(" "));
+            text_parse.clear();
         }
+        text_parse.push(segment.text);
     }
     if !text_parse.is_empty() {
         chunks.push(text_parse.join(" "))
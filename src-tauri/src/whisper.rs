@@ -3,17 +3,20 @@ use futures_util::StreamExt;
 use reqwest::multipart::{Form, Part};
 use reqwest::{Client, Proxy, StatusCode};
 use serde::{Deserialize, Serialize};
-use std::io::Write;
 use std::path::Path;
 use tauri::Manager;
-use tauri::{Emitter, State};
 use tokio::fs::{self, File};
 use tokio::io::AsyncReadExt;
+use tokio_util::sync::CancellationToken;
+use tube_rs::{HeatMarker, YoutubeAudio};
 
-use crate::gemini::parse_gemini;
+use crate::gemini::{parse_gemini, parse_gemini_usage};
 
-use super::db::{self, DataBase};
+use super::db;
+use super::events::{self, Phase, Sender};
+use super::jobs::{self, JobKind, Pool};
 use super::setting;
+use super::status::{self, Stage};
 use super::utils;
 
 // define the transcription struct with only text in my interest
@@ -58,6 +61,20 @@ struct ChatRequest {
     messages: Vec<Message>,
     model: String,
     stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream_options: Option<StreamOptions>,
+}
+
+// asks for a last chunk carrying the token usage of the whole stream
+#[derive(Debug, Serialize)]
+struct StreamOptions {
+    include_usage: bool,
+}
+
+// only openai itself is known to take `stream_options`, other servers with the same api
+// may refuse the whole request over a field they do not know
+fn supports_stream_options(api_url: &str) -> bool {
+    reqwest::Url::parse(api_url).is_ok_and(|url| url.host_str() == Some("api.openai.com"))
 }
 
 #[derive(Debug, Deserialize)]
@@ -75,19 +92,23 @@ struct Choice {
 #[derive(Debug, Deserialize)]
 struct ChatResponse {
     // id: String,
+    #[serde(default)]
     choices: Vec<Choice>,
+    usage: Option<TokenUsage>,
 }
 
-// after process the file remove the file
-pub async fn remove_files_from_directory(dir_path: &Path) -> Result<()> {
-    let mut dir = fs::read_dir(dir_path).await?;
-    while let Some(entry) = dir.next_entry().await? {
-        let _path = entry.path();
-        if _path.is_file() {
-            fs::remove_file(_path).await?;
-        }
-    }
-    Ok(())
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct TokenUsage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+// the streamed answer with the model that wrote it, usage is none when the api did not report it
+#[derive(Debug, Clone)]
+pub struct Completion {
+    pub text: String,
+    pub model: String,
+    pub usage: Option<TokenUsage>,
 }
 
 pub async fn create_client(app: &tauri::AppHandle) -> Result<Client> {
@@ -98,20 +119,64 @@ pub async fn create_client(app: &tauri::AppHandle) -> Result<Client> {
     Ok(client)
 }
 
+#[derive(Debug, Deserialize, Serialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SummaryMode {
+    #[default]
+    Standard,
+    // emphasize the chapters viewers replayed most according to the heatmap
+    MostReplayed,
+    // focus on the moments the user highlighted with notes
+    Highlights,
+}
+
+impl SummaryMode {
+    // stored with every summary so versions made with different prompts can be told apart
+    pub fn prompt_template(&self, include_chat: bool) -> String {
+        let name = match self {
+            SummaryMode::Standard => "chapters",
+            SummaryMode::MostReplayed => "chapters_most_replayed",
+            SummaryMode::Highlights => "chapters_highlights",
+        };
+        if include_chat {
+            format!("{name}+live_chat")
+        } else {
+            name.to_string()
+        }
+    }
+}
+
 // System Prompt: summarize with mindmap?
 //
 //
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct Segment {
     pub start: f64,
     pub end: f64,
     pub text: String,
+    // who is talking, only subtitle files name the voice of a cue
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speaker: Option<String>,
 }
 
 //
 //
 //
 
+// language names for prompts that are written once in English and answered in the target language
+pub fn language_name(language: &str) -> &str {
+    match language {
+        "zh" => "Simplified Chinese",
+        "zh-TW" => "Traditional Chinese",
+        "es" => "Spanish",
+        "fr" => "French",
+        "de" => "German",
+        "ja" => "Japanese",
+        "en" => "English",
+        _ => "the same language as the content",
+    }
+}
+
 fn get_system_prompt(language: &str) -> String {
     let prompt = match language {
         "zh" => {
@@ -320,42 +385,145 @@ fn get_system_prompt(language: &str) -> String {
 #[tauri::command(rename_all = "snake_case")]
 pub async fn run_summary(
     app: tauri::AppHandle,
-    db: State<'_, DataBase>,
     video_id: i64, // id in database
     language: String,
     auto: bool,
+    include_chat: Option<bool>,
+    mode: Option<SummaryMode>,
 ) -> Result<(), String> {
     let mut lang = language;
     if auto {
         lang = "auto".to_string()
     }
+    jobs::run(
+        &app,
+        JobKind::Summarize {
+            video_id,
+            language: lang,
+            include_chat: include_chat.unwrap_or(false),
+            mode: mode.unwrap_or_default(),
+        },
+    )
+    .await
+}
 
-    let (transcripts, description) = db::get_subtitle_with_id(db, video_id)?;
-    let subtitles: Vec<Segment> = serde_json::from_str(&transcripts).map_err(|e| e.to_string())?;
+// summarize the stored transcripts of a video and save the result as the active version;
+// returns the summary
+pub async fn summarize_video(
+    app: &tauri::AppHandle,
+    video_id: i64,
+    lang: &str,
+    include_chat: bool,
+    mode: SummaryMode,
+    cancel: &CancellationToken,
+) -> Result<String, String> {
+    let (subtitles, description) = db::get_subtitle_with_id(app.state(), video_id)?;
 
-    let content = utils::transform_segment_to_string(subtitles);
+    let mut content = utils::transform_segment_to_string(subtitles);
+    if include_chat {
+        let messages = db::get_live_chat(app.state(), video_id)?;
+        if !messages.is_empty() {
+            content.push_str(&utils::transform_chat_to_string(&messages));
+        }
+    }
+    if mode == SummaryMode::MostReplayed {
+        let markers = get_or_fetch_heatmap(app, video_id).await?;
+        if markers.is_empty() {
+            return Err("no most replayed data for this video".to_string());
+        }
+        content.push_str(&utils::transform_heatmap_to_string(&markers));
+    }
+    if mode == SummaryMode::Highlights {
+        let notes = db::get_notes(app.state(), video_id)?;
+        if notes.is_empty() {
+            return Err("no highlights for this video, add notes first".to_string());
+        }
+        content.push_str(&utils::transform_notes_to_string(&notes));
+    }
 
-    app.emit("summary", "[start]".to_string())
-        .map_err(|e| e.to_string())?;
-    let summary_content = chat_stream(&app, &content, &lang, &description).await?;
-    app.emit("summary", "[end]".to_string())
-        .map_err(|e| e.to_string())?;
+    status::track(app, video_id, Stage::Summarizing, cancel, async {
+        let sender = Sender::new(app, events::SUMMARY, Some(video_id), Phase::Summarizing);
+        let completion = sender
+            .run(chat_stream(
+                app,
+                &sender,
+                &content,
+                lang,
+                &description,
+                cancel,
+            ))
+            .await?;
+
+        db::save_summary(
+            app.state(),
+            video_id,
+            lang,
+            &completion.model,
+            &mode.prompt_template(include_chat),
+            &completion.text,
+            completion.usage,
+        )?;
+        Ok(completion.text)
+    })
+    .await
+}
 
-    db::update_video(
-        app.state(),
-        video_id,
-        "summary".to_string(),
-        summary_content,
-    )?;
-    Ok(())
+async fn get_or_fetch_heatmap(
+    app: &tauri::AppHandle,
+    video_id: i64,
+) -> Result<Vec<HeatMarker>, String> {
+    let markers = db::get_heatmap(app.state(), video_id)?;
+    if !markers.is_empty() {
+        return Ok(markers);
+    }
+    let youtube_id = db::get_youtube_id_with_id(app.state(), video_id)?;
+    let youtube_audio = YoutubeAudio::new(
+        setting::get_proxy(app).as_deref(),
+        setting::get_tube_api_url(app),
+    );
+    let markers = youtube_audio
+        .get_heatmap(&format!("https://www.youtube.com/watch?v={youtube_id}"))
+        .await
+        .map_err(|e| e.to_string())?;
+    db::save_heatmap(app.state(), video_id, &markers)?;
+    Ok(markers)
 }
 
 pub async fn chat_stream(
     app: &tauri::AppHandle,
+    sender: &Sender,
     user_message: &str,
     lang: &str,
     description: &str,
+    cancel: &CancellationToken,
+) -> Result<Completion, String> {
+    let message = format!(
+        "short description for the whole content: {description}. full subtitles: {user_message}"
+    );
+    chat_completion(app, sender, get_system_prompt(lang), message, cancel).await
+}
+
+// stream a chat completion for any prompt, sending each delta with `sender`
+pub async fn chat_completion_stream(
+    app: &tauri::AppHandle,
+    sender: &Sender,
+    system_prompt: String,
+    message: String,
+    cancel: &CancellationToken,
 ) -> Result<String, String> {
+    chat_completion(app, sender, system_prompt, message, cancel)
+        .await
+        .map(|completion| completion.text)
+}
+
+// dropping the request when `cancel` fires closes the stream, the provider stops generating
+pub async fn chat_completion(
+    app: &tauri::AppHandle,
+    sender: &Sender,
+    system_prompt: String,
+    message: String,
+    cancel: &CancellationToken,
+) -> Result<Completion, String> {
     let settings_value = setting::get_settings(app);
 
     let (api_url, llm_model, api_key) = match settings_value {
@@ -368,32 +536,58 @@ pub async fn chat_stream(
         _ => return Err("no api settings found".to_string()),
     };
     let client = create_client(app).await.map_err(|e| e.to_string())?;
-
-    let message = format!(
-        "short description for the whole content: {description}. full subtitles: {user_message}"
-    );
+    let _permit = jobs::until_cancelled(cancel, jobs::permit(app, Pool::Llm)).await?;
 
     if api_url.contains("googleapis") {
-        handle_gemini_api(app, lang, message, llm_model, client, &api_url, &api_key).await
+        let request = handle_gemini_api(
+            sender,
+            system_prompt,
+            message,
+            llm_model,
+            client,
+            &api_url,
+            &api_key,
+        );
+        jobs::until_cancelled(cancel, request).await
     } else {
-        handle_open_api(app, lang, message, llm_model, client, &api_url, &api_key).await
+        let request = handle_open_api(
+            sender,
+            system_prompt,
+            message,
+            llm_model,
+            client,
+            &api_url,
+            &api_key,
+        );
+        jobs::until_cancelled(cancel, request).await
     }
 }
 
+// an error answer of the llm api keeps its http status, which tells whether a retry may help
+async fn check_status(response: reqwest::Response) -> Result<reqwest::Response, String> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let error_text = response.text().await.unwrap_or_default();
+    Err(format!("API error {} - {}", status, error_text))
+}
+
+#[allow(clippy::too_many_arguments)]
 async fn handle_gemini_api(
-    app: &tauri::AppHandle,
-    lang: &str,
+    sender: &Sender,
+    system_prompt: String,
     message: String,
     llm_model: String,
     client: Client,
     api_url: &str,
     _api_key: &str,
-) -> Result<String, String> {
+) -> Result<Completion, String> {
     let contents: Vec<GeminiMessage> = vec![
         GeminiMessage {
             role: "model".to_string(),
             parts: vec![GeminiPart {
-                text: get_system_prompt(lang),
+                text: system_prompt,
             }],
         },
         GeminiMessage {
@@ -403,7 +597,7 @@ async fn handle_gemini_api(
     ];
 
     let request = GeminiRequest {
-        model: llm_model,
+        model: llm_model.clone(),
         contents,
     };
 
@@ -414,8 +608,10 @@ async fn handle_gemini_api(
         .send()
         .await
         .map_err(|e| e.to_string())?;
+    let response = check_status(response).await?;
 
     let mut summary = Vec::new();
+    let mut usage = None;
 
     let mut stream = response.bytes_stream();
     while let Some(chunk) = stream.next().await {
@@ -431,39 +627,50 @@ async fn handle_gemini_api(
             if line.is_empty() || line == "[DONE]" {
                 continue;
             }
+            // every chunk reports the usage so far, the last one counts
+            if let Some(chunk_usage) = parse_gemini_usage(line) {
+                usage = Some(chunk_usage);
+            }
             if let Ok(content) = parse_gemini(line) {
                 summary.push(content.clone());
-                app.emit("summary", content).map_err(|e| e.to_string())?;
-                std::io::stdout().flush().unwrap();
+                sender.delta(&content)?;
             }
         }
     }
 
-    Ok(summary.join(""))
+    Ok(Completion {
+        text: summary.join(""),
+        model: llm_model,
+        usage,
+    })
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn handle_open_api(
-    app: &tauri::AppHandle,
-    lang: &str,
+    sender: &Sender,
+    system_prompt: String,
     message: String,
     llm_model: String,
     client: Client,
     api_url: &str,
     api_key: &str,
-) -> Result<String, String> {
+) -> Result<Completion, String> {
     let request = ChatRequest {
         messages: vec![
             Message {
                 role: Role::System,
-                content: get_system_prompt(lang),
+                content: system_prompt,
             },
             Message {
                 role: Role::User,
                 content: message,
             },
         ],
-        model: llm_model,
+        model: llm_model.clone(),
         stream: true,
+        stream_options: supports_stream_options(api_url).then_some(StreamOptions {
+            include_usage: true,
+        }),
     };
 
     let response = client
@@ -474,8 +681,10 @@ async fn handle_open_api(
         .send()
         .await
         .map_err(|e| e.to_string())?;
+    let response = check_status(response).await?;
 
     let mut summary = Vec::new();
+    let mut usage = None;
 
     let mut stream = response.bytes_stream();
     while let Some(chunk) = stream.next().await {
@@ -493,18 +702,24 @@ async fn handle_open_api(
             }
 
             if let Ok(response) = serde_json::from_str::<ChatResponse>(line) {
+                if response.usage.is_some() {
+                    usage = response.usage;
+                }
                 for choice in response.choices {
                     if let Some(content) = choice.delta.content {
                         summary.push(content.clone());
-                        app.emit("summary", content).map_err(|e| e.to_string())?;
-                        std::io::stdout().flush().unwrap();
+                        sender.delta(&content)?;
                     }
                 }
             }
         }
     }
 
-    Ok(summary.join(""))
+    Ok(Completion {
+        text: summary.join(""),
+        model: llm_model,
+        usage,
+    })
 }
 
 async fn transcribe_audio(
@@ -548,7 +763,13 @@ async fn transcribe_audio(
     }
 }
 
-pub async fn trancript(app: &tauri::AppHandle, audio_path: &Path) -> Result<Vec<Segment>, String> {
+pub async fn trancript(
+    app: &tauri::AppHandle,
+    sender: &Sender,
+    audio_path: &Path,
+    chunk_duration: i64,
+    cancel: &CancellationToken,
+) -> Result<Vec<Segment>, String> {
     let settings_value = setting::get_settings(app);
 
     let (api_url, model_name, api_key) = match settings_value {
@@ -565,38 +786,71 @@ pub async fn trancript(app: &tauri::AppHandle, audio_path: &Path) -> Result<Vec<
 
     if !audio_path.is_dir() {
         let audio_path_str = audio_path.to_str().unwrap();
-        match transcribe_audio(app, &api_key, audio_path_str, &model_name, &api_url).await {
-            Ok(response) => {
-                app.emit("stream", response.text.clone()).unwrap();
-                chunks.extend(response.segments);
-            }
-            Err(e) => return Err(e.to_string()),
-        };
+        let request = transcribe_audio(app, &api_key, audio_path_str, &model_name, &api_url);
+        let response = jobs::until_cancelled(cancel, request).await?;
+        sender.delta(&response.text)?;
+        chunks.extend(response.segments);
     } else {
+        // the chunks are numbered, the directory lists them in any order
         let mut dir = fs::read_dir(audio_path).await.map_err(|e| e.to_string())?;
-
-        let mut end_time = 0.0;
+        let mut chunk_paths = Vec::new();
         while let Some(entry) = dir.next_entry().await.map_err(|e| e.to_string())? {
-            let audio_path = entry.path();
+            chunk_paths.push(entry.path());
+        }
+        chunk_paths.sort();
+
+        let total = chunk_paths.len();
+        for (done, audio_path) in chunk_paths.into_iter().enumerate() {
+            sender.progress(done, total)?;
             let audio_path_str = audio_path.to_str().unwrap();
-            match transcribe_audio(app, &api_key, audio_path_str, &model_name, &api_url).await {
-                Ok(response) => {
-                    app.emit("stream", response.text.clone()).unwrap();
-                    let mut current_end = 0.0;
-                    for mut segment in response.segments {
-                        segment.start += end_time;
-                        current_end = segment.end;
-                        chunks.push(segment);
-                    }
-                    end_time = current_end;
-                }
-                Err(_) => continue,
-            };
+            let request = transcribe_audio(app, &api_key, audio_path_str, &model_name, &api_url);
+            // a missing chunk would leave a hole in the transcript, fail the job so it is retried
+            let response = jobs::until_cancelled(cancel, request).await?;
+            sender.delta(&response.text)?;
+            let offset = done as f64 * chunk_duration as f64;
+            chunks.extend(offset_segments(response.segments, offset));
         }
-        remove_files_from_directory(audio_path)
-            .await
-            .map_err(|e| e.to_string())?;
     }
 
     Ok(chunks)
 }
+
+// whisper times every chunk from zero, move its segments to where the chunk starts in the audio
+fn offset_segments(segments: Vec<Segment>, offset: f64) -> impl Iterator<Item = Segment> {
+    segments.into_iter().map(move |mut segment| {
+        segment.start += offset;
+        segment.end += offset;
+        segment
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(start: f64, end: f64, text: &str) -> Segment {
+        Segment {
+            start,
+            end,
+            text: text.to_string(),
+            speaker: None,
+        }
+    }
+
+    #[test]
+    fn chunks_are_offset_by_their_start() {
+        let first = vec![segment(0.0, 4.5, "one"), segment(4.5, 598.0, "two")];
+        let second = vec![segment(1.0, 3.0, "three"), segment(3.0, 7.5, "four")];
+
+        let chunk_duration = 600.0;
+        let segments: Vec<Segment> = offset_segments(first, 0.0)
+            .chain(offset_segments(second, chunk_duration))
+            .collect();
+
+        let times: Vec<(f64, f64)> = segments.iter().map(|s| (s.start, s.end)).collect();
+        assert_eq!(
+            times,
+            vec![(0.0, 4.5), (4.5, 598.0), (601.0, 603.0), (603.0, 607.5)]
+        );
+    }
+}
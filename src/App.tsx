@@ -1,5 +1,5 @@
 import * as React from "react";
-import { Captions, FileText, XIcon } from "lucide-react";
+import { Captions, FileText, Square, XIcon } from "lucide-react";
 
 import { invoke } from "@tauri-apps/api/core";
 import { listen } from "@tauri-apps/api/event";
@@ -12,7 +12,9 @@ import SettingsModal from "components/SettingsModal";
 import StreamText from "components/StreamText";
 import { useVideoData } from "store/DataContext";
 import LanguageSelector from "components/LanguageSelector";
+import SummaryVersionSelector from "components/SummaryVersionSelector";
 import { formatDate } from "utils/files";
+import type { Job, LibraryEvent, StatusEvent, StreamEvent } from "types/db";
 
 function App() {
   const [url, setUrl] = React.useState<string>("");
@@ -32,9 +34,20 @@ function App() {
   const [content, setContent] = React.useState<string>("");
   const [summary, setSummary] = React.useState<string>("");
   const [auto, setAuto] = React.useState<boolean>(false);
+  const [summaryRuns, setSummaryRuns] = React.useState<number>(0);
+  // steps of the phase streamed now, chunks of audio or batches of segments
+  const [progress, setProgress] = React.useState<{
+    done: number;
+    total: number;
+  } | null>(null);
 
   const { addToast } = useToast();
 
+  const currentVideoId = React.useRef<number | null>(null);
+  React.useEffect(() => {
+    currentVideoId.current = currentVideo?.id ?? null;
+  }, [currentVideo]);
+
   React.useEffect(() => {
     if (currentVideo !== null) {
       setImgUrl(null);
@@ -111,6 +124,7 @@ function App() {
         language: selectedLanguage,
         auto: auto,
       });
+      setSummaryRuns((runs) => runs + 1);
       fetchVideos(false);
     } catch (error) {
       addToast({
@@ -123,49 +137,111 @@ function App() {
     }
   }
 
+  // stop the jobs of the current video, and any import of a url not in the library yet
+  async function handle_stop() {
+    try {
+      const jobs = await invoke<Job[]>("get_jobs");
+      const targets = jobs.filter(
+        (job) =>
+          (job.status === "running" || job.status === "pending") &&
+          (job.kind.video_id === null ||
+            job.kind.video_id === currentVideo?.id),
+      );
+      await Promise.all(
+        targets.map((job) => invoke("cancel_job", { job_id: job.id })),
+      );
+    } catch (error) {
+      addToast({
+        message: error as string,
+        variant: "error",
+        duration: 5000,
+      });
+    }
+  }
+
   React.useEffect(() => {
-    const unlisten = listen("stream", (event) => {
-      if (event.payload === "[start]") {
-        setInProgress(true);
-        setContent("");
-        setSummary("");
-      } else if (event.payload === "[end]") {
-        setInProgress(false);
-        addToast({
-          message: "Stream ended successfully",
-          variant: "success",
-          duration: 5000,
-        });
-      } else {
-        setContent((prevContent) => prevContent + event.payload);
+    // other videos run in the background, only the selected one is shown;
+    // nothing is selected while a new url is imported
+    const isShown = (event: StreamEvent) =>
+      currentVideoId.current === null ||
+      event.video_id === currentVideoId.current;
+
+    // progress and the end of a phase are shown the same on every channel
+    const onPhaseEvent = (payload: StreamEvent) => {
+      switch (payload.type) {
+        case "progress":
+          setProgress({ done: payload.done, total: payload.total });
+          break;
+        case "finished":
+          setInProgress(false);
+          setProgress(null);
+          addToast({
+            message: "Stream ended successfully",
+            variant: "success",
+            duration: 5000,
+          });
+          break;
+        case "failed":
+          setInProgress(false);
+          setProgress(null);
+          addToast({
+            message: payload.error,
+            variant: "error",
+            duration: 5000,
+          });
+          break;
       }
-    });
+    };
 
-    const unlisten_summary = listen("summary", (event) => {
-      if (event.payload === "[start]") {
-        setInProgress(true);
-        setSummary("");
-      } else if (event.payload === "[end]") {
-        addToast({
-          message: "Stream ended successfully",
-          variant: "success",
-          duration: 5000,
-        });
-      } else {
-        setSummary((prevContent) => prevContent + event.payload);
+    const unlisten = listen<StreamEvent>("stream/v1", (event) => {
+      const payload = event.payload;
+      if (!isShown(payload)) return;
+      switch (payload.type) {
+        case "started":
+          setInProgress(true);
+          setProgress(null);
+          setContent("");
+          setSummary("");
+          break;
+        case "delta":
+          setContent((prevContent) => prevContent + payload.text);
+          break;
+        default:
+          onPhaseEvent(payload);
       }
     });
 
-    const unlisten_state = listen("state", (event) => {
-      if (event.payload === "update video") {
-        fetchVideos();
+    const unlisten_summary = listen<StreamEvent>("summary/v1", (event) => {
+      const payload = event.payload;
+      if (!isShown(payload)) return;
+      switch (payload.type) {
+        case "started":
+          setInProgress(true);
+          setProgress(null);
+          setSummary("");
+          break;
+        case "delta":
+          setSummary((prevContent) => prevContent + payload.text);
+          break;
+        default:
+          onPhaseEvent(payload);
       }
     });
 
+    const unlisten_library = listen<LibraryEvent>("library/v1", () => {
+      fetchVideos();
+    });
+
+    // keep the status shown in the list current without moving the selection
+    const unlisten_status = listen<StatusEvent>("status/v1", () => {
+      fetchVideos(false);
+    });
+
     return () => {
       unlisten.then((fn) => fn());
       unlisten_summary.then((fn) => fn());
-      unlisten_state.then((fn) => fn());
+      unlisten_library.then((fn) => fn());
+      unlisten_status.then((fn) => fn());
     };
   }, [setInProgress, addToast, fetchVideos]);
 
@@ -230,6 +306,23 @@ function App() {
               <Captions className="w-7 h-7" />
               <span>Transcript</span>
             </button>
+            {inProgress && (
+              <button
+                type="button"
+                className="flex items-center space-x-2 px-4 py-2
+                            bg-red-500 text-white rounded-lg
+                            hover:bg-red-600 active:bg-red-700"
+                onClick={handle_stop}
+              >
+                <Square className="w-7 h-7" />
+                <span>Stop</span>
+              </button>
+            )}
+            {inProgress && progress && (
+              <span className="self-center text-white">
+                {progress.done}/{progress.total}
+              </span>
+            )}
           </div>
 
           <div className="flex flex-row justify-between items-stretch w-full overflow-hidden h-full">
@@ -260,6 +353,16 @@ function App() {
                   onLanguageChange={setSelectedLanguage}
                 />
                 <CaptionCheckBox ischecked={auto} handleChecked={setAuto} />
+                {currentVideo && (
+                  <SummaryVersionSelector
+                    videoId={currentVideo.id}
+                    refreshKey={summaryRuns}
+                    onVersionChange={(content) => {
+                      setSummary(content);
+                      fetchVideos(false);
+                    }}
+                  />
+                )}
                 <button
                   type="button"
                   onClick={handle_summary}
@@ -21,6 +21,12 @@ const SettingsModal: React.FC = () => {
     setSettings({ ...settings, [name]: value });
   };
 
+  // worker counts are numbers for the backend, empty means the default
+  const handleNumberChange = (e: React.ChangeEvent<HTMLInputElement>) => {
+    const { name, value } = e.target;
+    setSettings({ ...settings, [name]: value === "" ? null : Number(value) });
+  };
+
   const handleSave = () => {
     updateSettings(settings);
     setIsOpen(false);
@@ -257,6 +263,75 @@ const SettingsModal: React.FC = () => {
                   placeholder="Enter tube api URL"
                 />
               </div>
+
+              <div>
+                <label
+                  htmlFor="vaultFolder"
+                  className="block text-sm font-medium text-gray-700 mb-1"
+                >
+                  Obsidian Vault Folder
+                </label>
+                <input
+                  type="text"
+                  id="vaultFolder"
+                  name="vaultFolder"
+                  value={settings.vaultFolder || ""}
+                  onChange={handleInputChange}
+                  className="w-full px-3 py-2 border border-gray-300 rounded-md shadow-sm 
+                             focus:outline-none focus:ring-2 focus:ring-blue-500"
+                  placeholder="Enter the folder markdown notes are exported to"
+                />
+              </div>
+
+              <div>
+                <span className="block text-sm font-medium text-gray-700 mb-1">
+                  Parallel Jobs (applied after a restart)
+                </span>
+                <div className="flex space-x-2">
+                  {(
+                    [
+                      ["downloadWorkers", "Downloads", 2],
+                      ["transcriptionWorkers", "Transcriptions", 1],
+                      ["llmWorkers", "LLM calls", 2],
+                    ] as const
+                  ).map(([name, label, fallback]) => (
+                    <label key={name} className="flex-1 text-xs text-gray-500">
+                      {label}
+                      <input
+                        type="number"
+                        min={1}
+                        max={16}
+                        name={name}
+                        value={settings[name] ?? ""}
+                        onChange={handleNumberChange}
+                        className="w-full px-3 py-2 border border-gray-300 rounded-md shadow-sm 
+                                   focus:outline-none focus:ring-2 focus:ring-blue-500"
+                        placeholder={String(fallback)}
+                      />
+                    </label>
+                  ))}
+                </div>
+              </div>
+
+              <div>
+                <label
+                  htmlFor="cacheLimitMb"
+                  className="block text-sm font-medium text-gray-700 mb-1"
+                >
+                  Cache Size Limit in MB (applied after a restart)
+                </label>
+                <input
+                  type="number"
+                  min={0}
+                  id="cacheLimitMb"
+                  name="cacheLimitMb"
+                  value={settings.cacheLimitMb ?? ""}
+                  onChange={handleNumberChange}
+                  className="w-full px-3 py-2 border border-gray-300 rounded-md shadow-sm 
+                             focus:outline-none focus:ring-2 focus:ring-blue-500"
+                  placeholder="2048"
+                />
+              </div>
             </div>
           </div>
 
@@ -0,0 +1,110 @@
+import * as React from "react";
+import * as Select from "@radix-ui/react-select";
+import { CheckIcon, ChevronDownIcon, History } from "lucide-react";
+import { invoke } from "@tauri-apps/api/core";
+
+import type { SummaryVersion } from "types/db";
+
+interface SummaryVersionSelectorProps {
+  videoId: number;
+  // changes whenever a new summary was generated
+  refreshKey: number;
+  onVersionChange: (content: string) => void;
+}
+
+function versionLabel(version: SummaryVersion) {
+  const date = new Date(version.created_at * 1000).toLocaleString();
+  const tokens =
+    version.prompt_tokens !== null && version.completion_tokens !== null
+      ? ` · ${version.prompt_tokens + version.completion_tokens} tokens`
+      : "";
+  return `${date} · ${version.language} · ${version.model}${tokens}`;
+}
+
+const SummaryVersionSelector: React.FC<SummaryVersionSelectorProps> = ({
+  videoId,
+  refreshKey,
+  onVersionChange,
+}) => {
+  const [versions, setVersions] = React.useState<SummaryVersion[]>([]);
+
+  React.useEffect(() => {
+    invoke<SummaryVersion[]>("get_summaries", { video_id: videoId })
+      .then(setVersions)
+      .catch((error) => console.error("Failed to load summaries:", error));
+  }, [videoId, refreshKey]);
+
+  async function onValueChange(value: string) {
+    const summaryId = Number(value);
+    try {
+      await invoke("set_active_summary", {
+        video_id: videoId,
+        summary_id: summaryId,
+      });
+      setVersions((prev) =>
+        prev.map((version) => ({
+          ...version,
+          active: version.id === summaryId,
+        })),
+      );
+      const selected = versions.find((version) => version.id === summaryId);
+      if (selected) onVersionChange(selected.content);
+    } catch (error) {
+      console.error("Failed to switch summary:", error);
+    }
+  }
+
+  if (versions.length < 2) return null;
+  const active = versions.find((version) => version.active);
+
+  return (
+    <div className="flex items-center gap-5">
+      <History className="w-7 h-7 text-green-200" />
+      <Select.Root value={active?.id.toString()} onValueChange={onValueChange}>
+        <Select.Trigger
+          className="flex items-center w-40 justify-between px-3 py-2 
+                     text-left bg-white border border-gray-300 rounded-md 
+                     shadow-sm focus:outline-none"
+          aria-label="Select summary version"
+        >
+          <span className="truncate">
+            {active ? `${active.language} · ${active.model}` : "Versions"}
+          </span>
+          <Select.Icon>
+            <ChevronDownIcon className="w-4 h-4 text-gray-400" />
+          </Select.Icon>
+        </Select.Trigger>
+
+        <Select.Portal>
+          <Select.Content
+            className="bg-white rounded-md shadow-lg 
+                       z-50 overflow-hidden"
+            position="popper"
+          >
+            <Select.Viewport className="p-1 focus:outline-none">
+              {versions.map((version) => (
+                <Select.Item
+                  key={version.id}
+                  value={version.id.toString()}
+                  className="relative flex items-center pl-3 pr-8 py-2 
+                             select-none hover:bg-gray-100 
+                             focus:bg-gray-100 cursor-pointer 
+                             text-gray-900 rounded-md 
+                             data-[highlighted]:outline-none 
+                             data-[highlighted]:bg-gray-100"
+                >
+                  <Select.ItemText>{versionLabel(version)}</Select.ItemText>
+                  <Select.ItemIndicator className="absolute right-2">
+                    <CheckIcon className="w-4 h-4 text-blue-600" />
+                  </Select.ItemIndicator>
+                </Select.Item>
+              ))}
+            </Select.Viewport>
+          </Select.Content>
+        </Select.Portal>
+      </Select.Root>
+    </div>
+  );
+};
+
+export default SummaryVersionSelector;
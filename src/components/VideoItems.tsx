@@ -1,18 +1,35 @@
 import * as React from "react";
-import { Activity, CheckCircle } from "lucide-react";
+import { Activity, AlertCircle, CheckCircle, RotateCcw } from "lucide-react";
+import { invoke } from "@tauri-apps/api/core";
 import { formatTime, formatTimestamp } from "../utils/files";
 import { useVideoData } from "../store/DataContext";
+import { useToast } from "hooks/ToastProvider";
 
 import type { VideoItemProps } from "../types/db";
 
 const VideoItem = ({ item }: VideoItemProps) => {
   const { currentVideo, updateCurrentVideo, inProgress } = useVideoData();
+  const { addToast } = useToast();
 
   const onClick = () => {
     if (!inProgress) {
       updateCurrentVideo(item.id);
     }
   };
+  const onRetry = async (event: React.MouseEvent) => {
+    event.stopPropagation();
+    try {
+      await invoke("retry_video", { video_id: item.id });
+    } catch (error) {
+      addToast({
+        message: error as string,
+        variant: "error",
+        duration: 5000,
+      });
+    }
+  };
+
+  const failed = item.status === "failed";
   return (
     <div
       className={`rounded-md ${
@@ -24,8 +41,14 @@ const VideoItem = ({ item }: VideoItemProps) => {
       onKeyDown={onClick}
     >
       <div className="flex flex-row items-center space-x-3">
-        {item.transcripts === null || item.transcripts.length === 0 ? (
-          <Activity className="text-gray-200" />
+        {failed ? (
+          <AlertCircle className="text-red-500">
+            <title>{item.status_error ?? "failed"}</title>
+          </AlertCircle>
+        ) : !item.has_transcripts ? (
+          <Activity className="text-gray-200">
+            <title>{item.status.replace("_", " ")}</title>
+          </Activity>
         ) : (
           <CheckCircle className="text-green-500" />
         )}
@@ -34,6 +57,16 @@ const VideoItem = ({ item }: VideoItemProps) => {
             ? `${item.title.substring(0, 17)}...`
             : item.title}
         </p>
+        {failed && !inProgress && (
+          <button
+            type="button"
+            className="ml-auto text-gray-400 hover:text-gray-200"
+            title="Retry"
+            onClick={onRetry}
+          >
+            <RotateCcw size={16} />
+          </button>
+        )}
       </div>
       <div className="flex flex-row justify-between text-sm text-gray-400">
         <p>{formatTime(item.duration * 1000)}</p>
@@ -48,7 +81,7 @@ const VideoItems = () => {
     window.innerHeight - 10,
   );
 
-  const { videos } = useVideoData();
+  const { videos, hasMore, loadMoreVideos } = useVideoData();
 
   React.useEffect(() => {
     const handleResize = () => {
@@ -74,6 +107,15 @@ const VideoItems = () => {
       {videos.map((video) => (
         <VideoItem key={video.id} item={video} />
       ))}
+      {hasMore && (
+        <button
+          type="button"
+          className="py-1 text-sm text-gray-400 hover:text-gray-200"
+          onClick={() => loadMoreVideos()}
+        >
+          Load more
+        </button>
+      )}
     </div>
   );
 };
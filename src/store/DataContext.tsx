@@ -1,12 +1,21 @@
 import * as React from "react";
 import { invoke } from "@tauri-apps/api/core";
-import type { VideoData } from "../types/db";
+import type {
+  VideoData,
+  VideoListItem,
+  VideoPage,
+  VideoQuery,
+} from "../types/db";
+
+const PAGE_SIZE = 50;
 
 interface VideoDataContextType {
-  videos: VideoData[];
+  videos: VideoListItem[];
+  hasMore: boolean;
   currentVideo: VideoData | null;
   updateCurrentVideo: (video_id: number) => void;
   fetchVideos: (updateFirst?: boolean) => Promise<void>;
+  loadMoreVideos: () => Promise<void>;
   saveVideo: (videoData: Omit<VideoData, "id">) => Promise<VideoData>;
   // updateVideo: (
   //   id: number,
@@ -14,7 +23,7 @@ interface VideoDataContextType {
   // ) => Promise<VideoData>;
   deleteVideo: (id: number) => Promise<void>;
   deleteAll: () => Promise<void>;
-  getVideoById: (id: number) => VideoData | undefined;
+  getVideoById: (id: number) => VideoListItem | undefined;
 
   inProgress: boolean;
   setInProgress: (value: boolean) => void;
@@ -27,34 +36,70 @@ const VideoDataContext = React.createContext<VideoDataContextType | undefined>(
 export const VideoDataProvider: React.FC<{ children: React.ReactNode }> = ({
   children,
 }) => {
-  const [videos, setVideos] = React.useState<VideoData[]>([]);
+  const [videos, setVideos] = React.useState<VideoListItem[]>([]);
+  const [nextCursor, setNextCursor] = React.useState<string | null>(null);
   const [inProgress, setInProgress] = React.useState<boolean>(false);
   const [currentVideo, setCurrentVideo] = React.useState<VideoData | null>(
     null,
   );
 
-  const updateCurrentVideo = (video_id: number) => {
-    const video = videos.find((video) => video.id === video_id);
-    if (video !== undefined) {
+  const currentVideoId = React.useRef<number | null>(null);
+  React.useEffect(() => {
+    currentVideoId.current = currentVideo?.id ?? null;
+  }, [currentVideo]);
+
+  const loadVideoDetail = React.useCallback(async (video_id: number) => {
+    try {
+      const video = await invoke<VideoData>("get_video_detail", { video_id });
       setCurrentVideo(video);
-    } else {
+    } catch (error) {
+      console.error("Failed to load video:", error);
       setCurrentVideo(null);
     }
-  };
+  }, []);
 
-  const fetchVideos = React.useCallback(async (updateFirst = true) => {
-    try {
-      const fetchedVideos = await invoke<VideoData[]>("get_videos");
-      setVideos(fetchedVideos);
+  const updateCurrentVideo = (video_id: number) => {
+    if (video_id === -1) {
+      setCurrentVideo(null);
+    } else {
+      loadVideoDetail(video_id);
+    }
+  };
 
-      if (updateFirst && fetchedVideos.length > 0) {
-        setCurrentVideo(fetchedVideos[0]);
+  // the first page again, keeping the selected video up to date unless `updateFirst` picks the newest
+  const fetchVideos = React.useCallback(
+    async (updateFirst = true) => {
+      try {
+        const query: VideoQuery = { page_size: PAGE_SIZE };
+        const page = await invoke<VideoPage>("get_videos", { query });
+        setVideos(page.items);
+        setNextCursor(page.next_cursor);
+
+        if (updateFirst && page.items.length > 0) {
+          await loadVideoDetail(page.items[0].id);
+        } else if (currentVideoId.current !== null) {
+          await loadVideoDetail(currentVideoId.current);
+        }
+      } catch (error) {
+        console.error("Failed to fetch videos:", error);
+        throw error;
       }
+    },
+    [loadVideoDetail],
+  );
+
+  const loadMoreVideos = React.useCallback(async () => {
+    if (nextCursor === null) return;
+    try {
+      const query: VideoQuery = { page_size: PAGE_SIZE, cursor: nextCursor };
+      const page = await invoke<VideoPage>("get_videos", { query });
+      setVideos((prevVideos) => [...prevVideos, ...page.items]);
+      setNextCursor(page.next_cursor);
     } catch (error) {
       console.error("Failed to fetch videos:", error);
       throw error;
     }
-  }, []);
+  }, [nextCursor]);
 
   const saveVideo = React.useCallback(
     async (videoData: Omit<VideoData, "id">) => {
@@ -62,14 +107,14 @@ export const VideoDataProvider: React.FC<{ children: React.ReactNode }> = ({
         const savedVideo = await invoke<VideoData>("create_video", {
           videoData,
         });
-        setVideos((prevVideos) => [...prevVideos, savedVideo]);
+        await fetchVideos(false);
         return savedVideo;
       } catch (error) {
         console.error("Failed to save video:", error);
         throw error;
       }
     },
-    [],
+    [fetchVideos],
   );
 
   // const updateVideo = React.useCallback(
@@ -123,6 +168,8 @@ export const VideoDataProvider: React.FC<{ children: React.ReactNode }> = ({
 
   const contextValue = {
     videos,
+    hasMore: nextCursor !== null,
+    loadMoreVideos,
     currentVideo,
     updateCurrentVideo,
     fetchVideos,
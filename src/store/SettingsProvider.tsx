@@ -12,6 +12,7 @@ const defaultSettings: SettingsType = {
   whisperModelName: null,
   proxy: null,
   tubeApiUrl: null,
+  vaultFolder: null,
 };
 
 interface SettingsContextType {
@@ -4,17 +4,165 @@ export interface VideoData {
   title: string;
   duration: number;
   upload_date: number;
-  transcripts: string;
-  summary: string;
+  transcripts: string | null;
+  summary: string | null;
   keywords: string;
   timestamp: number;
   thumbnail_url: string;
 }
 
+// a row of the video list, without the transcript and summary bodies
+export type VideoStatus =
+  | "queued"
+  | "fetching_metadata"
+  | "downloading"
+  | "splitting"
+  | "transcribing"
+  | "summarizing"
+  | "done"
+  | "failed";
+
+export interface StatusEntry {
+  stage: VideoStatus;
+  error: string | null;
+  created_at: number;
+}
+
+export interface VideoListItem {
+  id: number;
+  video_id: string;
+  title: string;
+  channel: string | null;
+  duration: number;
+  upload_date: number;
+  keywords: string | null;
+  timestamp: number;
+  thumbnail_url: string;
+  caption_lang: string | null;
+  has_transcripts: boolean;
+  has_summary: boolean;
+  status: VideoStatus;
+  status_error: string | null;
+}
+
+export interface VideoPage {
+  items: VideoListItem[];
+  next_cursor: string | null;
+  total: number;
+}
+
+export interface VideoQuery {
+  sort?: "date_added" | "upload_date" | "duration" | "title";
+  order?: "ascending" | "descending";
+  page_size?: number;
+  cursor?: string;
+  offset?: number;
+  status?: VideoStatus;
+  language?: string;
+  has_summary?: boolean;
+  tag_ids?: number[];
+  collection_id?: number;
+}
+
 export interface VideoItemProps {
-  item: VideoData;
+  item: VideoListItem;
 }
 
 export interface VideoListProps {
-  items: VideoData[];
+  items: VideoListItem[];
+}
+
+export interface SummaryVersion {
+  id: number;
+  language: string;
+  model: string;
+  prompt_template: string;
+  content: string;
+  prompt_tokens: number | null;
+  completion_tokens: number | null;
+  active: boolean;
+  created_at: number;
+}
+
+export interface Tag {
+  id: number;
+  name: string;
+  video_count: number;
+}
+
+export interface Collection {
+  id: number;
+  name: string;
+  parent_id: number | null;
+  video_count: number;
+}
+
+export interface Note {
+  id: number;
+  start: number;
+  end: number;
+  text: string;
+  color: string;
+  created_at: number;
+}
+
+export type JobKind =
+  | { kind: "import"; url: string; video_id: number | null }
+  | {
+      kind: "summarize";
+      video_id: number;
+      language: string;
+      include_chat: boolean;
+      mode: "standard" | "most_replayed" | "highlights";
+    };
+
+export interface Job {
+  id: number;
+  kind: JobKind;
+  status: "pending" | "running" | "done" | "failed" | "cancelled";
+  attempts: number;
+  max_attempts: number;
+  run_after: number;
+  error: string | null;
+  created_at: number;
+}
+
+// streamed output of a phase of work, sent on versioned channels such as "stream/v1"
+export type StreamPhase =
+  | "captions"
+  | "transcribing"
+  | "summarizing"
+  | "translating"
+  | "flashcards";
+
+export type StreamPayload =
+  | { type: "started" }
+  | { type: "progress"; done: number; total: number }
+  | { type: "delta"; text: string }
+  | { type: "finished" }
+  | { type: "failed"; error: string };
+
+export type StreamEvent = {
+  job_id: number | null;
+  video_id: number | null;
+  phase: StreamPhase;
+} & StreamPayload;
+
+// a video moved to another stage, sent on "status/v1"
+export interface StatusEvent {
+  video_id: number;
+  stage: VideoStatus;
+  error: string | null;
+}
+
+// a video was added to the library, changed or removed, sent on "library/v1"
+export interface LibraryEvent {
+  video_id: number;
+  change: "added" | "updated" | "removed";
+}
+
+// the queue changed, sent on "jobs/v1" with every job in it
+export interface JobsEvent {
+  paused: boolean;
+  jobs: Job[];
 }
@@ -7,4 +7,9 @@ export interface SettingsType {
   whisperModelName: string | null;
   proxy: string | null;
   tubeApiUrl: string | null;
+  vaultFolder: string | null;
+  downloadWorkers?: number | null;
+  transcriptionWorkers?: number | null;
+  llmWorkers?: number | null;
+  cacheLimitMb?: number | null;
 }